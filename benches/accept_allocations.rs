@@ -0,0 +1,138 @@
+//! Measures how many allocations accepting a connection takes while clients
+//! keep connecting and disconnecting as fast as they can, and how long it
+//! takes. Only the allocations made by the accepting thread are counted.
+//!
+//! Run with `cargo bench --bench accept_allocations`.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::allocations_made_by;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+const ACCEPTS: usize = 20_000;
+const CLIENTS: usize = 4;
+
+fn main() {
+    imp::run();
+}
+
+/// Spawns client threads which run `connect_once` in a loop until told to
+/// stop.
+fn spawn_clients(
+    connect_once: impl Fn() + Clone + Send + 'static,
+) -> (Arc<AtomicBool>, Vec<JoinHandle<()>>) {
+    let stop = Arc::new(AtomicBool::new(false));
+    let clients = (0..CLIENTS)
+        .map(|_| {
+            let (stop, connect_once) = (Arc::clone(&stop), connect_once.clone());
+            thread::spawn(move || {
+                while !stop.load(SeqCst) {
+                    connect_once();
+                }
+            })
+        })
+        .collect();
+    (stop, clients)
+}
+
+/// Accepts `ACCEPTS` connections with `accept_once`, then prints how many
+/// allocations and how much time that took.
+fn measure(what: &str, mut accept_once: impl FnMut()) {
+    let mut allocations = 0;
+    let start = Instant::now();
+    for _ in 0..ACCEPTS {
+        allocations += allocations_made_by(&mut accept_once);
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{}: {} accepts with {} clients in {:?}, {:.3} allocations and {:?} per accept",
+        what,
+        ACCEPTS,
+        CLIENTS,
+        elapsed,
+        allocations as f64 / ACCEPTS as f64,
+        elapsed / ACCEPTS as u32,
+    );
+}
+
+fn stop_clients(stop: Arc<AtomicBool>, clients: Vec<JoinHandle<()>>) {
+    stop.store(true, SeqCst);
+    for client in clients {
+        client.join().unwrap();
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::*;
+    use interprocess::{
+        local_socket::{LocalSocketListener, LocalSocketStream},
+        os::unix::udsocket::{UdStream, UdStreamListener},
+    };
+    use std::{env, fs::remove_file, process};
+
+    pub fn run() {
+        let path = env::temp_dir().join(format!(
+            "interprocess_accept_allocations_{}.sock",
+            process::id()
+        ));
+
+        let _ = remove_file(&path);
+        let listener = UdStreamListener::bind(&*path).unwrap();
+        let client_path = path.clone();
+        let (stop, clients) = spawn_clients(move || {
+            let _ = UdStream::connect(&*client_path);
+        });
+        measure("UdStreamListener", || drop(listener.accept().unwrap()));
+        // Makes the clients which are waiting for a connection fail, so that
+        // they notice that they should stop.
+        drop(listener);
+        stop_clients(stop, clients);
+
+        let _ = remove_file(&path);
+        let listener = LocalSocketListener::bind(&*path).unwrap();
+        let client_path = path.clone();
+        let (stop, clients) = spawn_clients(move || {
+            let _ = LocalSocketStream::connect(&*client_path);
+        });
+        // Local socket streams allocate their cancellation state, which is
+        // shared with their cancellation tokens, once each.
+        measure("LocalSocketListener", || drop(listener.accept().unwrap()));
+        drop(listener);
+        stop_clients(stop, clients);
+        let _ = remove_file(&path);
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::*;
+    use interprocess::os::windows::named_pipe::{DuplexBytePipeStream, PipeListenerOptions};
+    use std::{ffi::OsStr, process};
+
+    pub fn run() {
+        let name = format!("interprocess-accept-allocations-{}", process::id());
+        let listener = PipeListenerOptions::new()
+            .name(OsStr::new(&name))
+            .create::<DuplexBytePipeStream>()
+            .unwrap();
+        let client_name = name.clone();
+        let (stop, clients) = spawn_clients(move || {
+            // Fails right away if there's no free instance, so back off a bit.
+            if DuplexBytePipeStream::connect(&client_name).is_err() {
+                thread::yield_now();
+            }
+        });
+        measure("PipeListener", || drop(listener.accept().unwrap()));
+        drop(listener);
+        stop_clients(stop, clients);
+    }
+}
@@ -17,6 +17,9 @@ fn main() {
     if checkver(&version, 53) {
         define("io_error_kind_unsupported_stable");
     }
+    if checkver(&version, 63) {
+        define("io_safety_stable");
+    }
 }
 
 fn is_unix() -> bool {
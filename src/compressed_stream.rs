@@ -0,0 +1,472 @@
+use std::{
+    convert::TryInto,
+    io::{self, prelude::*},
+};
+
+/// The maximum size of the uncompressed data in one block of a
+/// [`CompressedStream`], which is also the maximum block size which can be
+/// set with [`with_block_size`].
+///
+/// [`CompressedStream`]: struct.CompressedStream.html " "
+/// [`with_block_size`]: struct.CompressedStream.html#method.with_block_size " "
+pub const MAX_COMPRESSED_BLOCK_LEN: usize = 1024 * 1024;
+
+/// The size of the blocks which [`CompressedStream::new`] compresses data in.
+///
+/// [`CompressedStream::new`]: struct.CompressedStream.html#method.new " "
+pub const DEFAULT_COMPRESSED_BLOCK_LEN: usize = 64 * 1024;
+
+/// The size of a block header: the compressed size and the uncompressed size,
+/// both as `u32` in little endian.
+const HEADER_LEN: usize = 8;
+
+/// A compression algorithm used by [`CompressedStream`].
+///
+/// The crate does not include any compression algorithms itself, so that it
+/// doesn't have to depend on any of them; implement this trait for a wrapper of
+/// the compression library of your choice instead. Every block is compressed
+/// and decompressed independently, so the codec does not need to keep any
+/// state between calls, although it may do so to reuse its working memory.
+///
+/// [`CompressedStream`]: struct.CompressedStream.html " "
+pub trait Codec {
+    /// Compresses the input, appending the result to the output.
+    fn compress(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()>;
+    /// Decompresses the input, which was produced by `compress` on the other
+    /// end of the stream, appending the result to the output.
+    /// `uncompressed_len` is the size which the input had before compression,
+    /// which is the amount of bytes which is expected to be appended.
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        uncompressed_len: usize,
+        output: &mut Vec<u8>,
+    ) -> io::Result<()>;
+}
+impl<C: Codec + ?Sized> Codec for &mut C {
+    fn compress(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        (**self).compress(input, output)
+    }
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        uncompressed_len: usize,
+        output: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        (**self).decompress(input, uncompressed_len, output)
+    }
+}
+impl<C: Codec + ?Sized> Codec for Box<C> {
+    fn compress(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        (**self).compress(input, output)
+    }
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        uncompressed_len: usize,
+        output: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        (**self).decompress(input, uncompressed_len, output)
+    }
+}
+
+/// A stream adapter which transparently compresses the data written into it and
+/// decompresses the data read from it, using a pluggable [`Codec`].
+///
+/// Written data is collected into blocks, which are compressed and sent when
+/// they're full or when the stream is [flushed]. Reading decompresses one block
+/// at a time, so the receiving end can process data as it arrives instead of
+/// waiting for the whole payload. This is mostly useful for large payloads on
+/// links where bandwidth is more expensive than CPU time, such as named pipes
+/// connecting to a remote computer; on local connections, compression is
+/// usually slower than just sending the data.
+///
+/// **Data written into the stream is only sent when it's flushed or when a
+/// block fills up**, so the stream has to be flushed after every message which
+/// the other end is supposed to answer, and before it's dropped — dropping it
+/// discards the unflushed data.
+///
+/// # Framing format
+/// Both ends must use the same codec. The data is sent as a sequence of
+/// blocks, each of which consists of:
+/// - the size of the compressed data, as an unsigned 32-bit integer in little
+///   endian;
+/// - the size of the data before compression, in the same format;
+/// - the compressed data itself, as produced by [`Codec::compress`].
+///
+/// The uncompressed size of a block is at most [`MAX_COMPRESSED_BLOCK_LEN`],
+/// and the compressed size is at most twice that; blocks which claim to be
+/// bigger are rejected as invalid data without allocating memory for them.
+/// Empty blocks are never sent, and are rejected as invalid data rather than
+/// being mistaken for the end of the stream. The block size which the writing
+/// end uses does not need to match the one of the reading end.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use interprocess::{local_socket::LocalSocketStream, Codec, CompressedStream};
+/// use std::io::{self, prelude::*};
+///
+/// /// Run-length encoding, as a stand-in for a real compression library.
+/// struct Rle;
+/// impl Codec for Rle {
+///     fn compress(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+///         for run in input.chunks(255) {
+///             let mut i = 0;
+///             while i < run.len() {
+///                 let len = run[i..].iter().take_while(|&&b| b == run[i]).count();
+///                 output.extend_from_slice(&[len as u8, run[i]]);
+///                 i += len;
+///             }
+///         }
+///         Ok(())
+///     }
+///     fn decompress(&mut self, input: &[u8], _: usize, output: &mut Vec<u8>) -> io::Result<()> {
+///         for pair in input.chunks(2) {
+///             output.extend(std::iter::repeat(pair[1]).take(pair[0] as usize));
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let conn = LocalSocketStream::connect("/tmp/example.sock")?;
+/// let mut conn = CompressedStream::new(conn, Rle);
+/// conn.write_all(&[0; 1_000_000])?;
+/// conn.flush()?;
+/// # Ok(()) }
+/// ```
+///
+/// [`Codec`]: trait.Codec.html " "
+/// [flushed]: #method.flush " "
+/// [`Codec::compress`]: trait.Codec.html#tymethod.compress " "
+/// [`MAX_COMPRESSED_BLOCK_LEN`]: constant.MAX_COMPRESSED_BLOCK_LEN.html " "
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "compression")))]
+#[derive(Debug)]
+pub struct CompressedStream<S, C: Codec> {
+    stream:      S,
+    codec:       C,
+    block_size:  usize,
+    /// Uncompressed data which is waiting to be compressed and sent.
+    write_buf:   Vec<u8>,
+    /// The compressed block which is being sent, including its header.
+    frame:       Vec<u8>,
+    /// How much of `frame` has been written into the stream.
+    frame_sent:  usize,
+    /// Scratch space for receiving blocks.
+    scratch:     Vec<u8>,
+    /// The decompressed block which is being read.
+    read_buf:    Vec<u8>,
+    read_offset: usize,
+}
+impl<S, C: Codec> CompressedStream<S, C> {
+    /// Wraps the stream, compressing data in blocks of
+    /// [`DEFAULT_COMPRESSED_BLOCK_LEN`] bytes.
+    ///
+    /// [`DEFAULT_COMPRESSED_BLOCK_LEN`]: constant.DEFAULT_COMPRESSED_BLOCK_LEN.html " "
+    pub fn new(stream: S, codec: C) -> Self {
+        Self::with_block_size(stream, codec, DEFAULT_COMPRESSED_BLOCK_LEN)
+    }
+    /// Wraps the stream, compressing data in blocks of the specified size.
+    ///
+    /// Bigger blocks usually compress better, at the cost of more memory and
+    /// of the other end having to wait longer before it can start
+    /// decompressing.
+    ///
+    /// # Panics
+    /// If `block_size` is zero or bigger than [`MAX_COMPRESSED_BLOCK_LEN`].
+    ///
+    /// [`MAX_COMPRESSED_BLOCK_LEN`]: constant.MAX_COMPRESSED_BLOCK_LEN.html " "
+    pub fn with_block_size(stream: S, codec: C, block_size: usize) -> Self {
+        assert!(
+            block_size != 0 && block_size <= MAX_COMPRESSED_BLOCK_LEN,
+            "block size must be between 1 and MAX_COMPRESSED_BLOCK_LEN"
+        );
+        Self {
+            stream,
+            codec,
+            block_size,
+            write_buf: Vec::new(),
+            frame: Vec::new(),
+            frame_sent: 0,
+            scratch: Vec::new(),
+            read_buf: Vec::new(),
+            read_offset: 0,
+        }
+    }
+    /// Borrows the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+    /// Mutably borrows the underlying stream. Reading from it or writing into
+    /// it directly corrupts the framing.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+    /// Returns the underlying stream and the codec, discarding the unflushed
+    /// data and the decompressed data which has not been read yet, if any.
+    pub fn into_inner(self) -> (S, C) {
+        (self.stream, self.codec)
+    }
+}
+impl<S: Read, C: Codec> CompressedStream<S, C> {
+    /// Receives and decompresses the next block. Returns `false` if the stream
+    /// has ended cleanly, i.e. before the header of a block.
+    fn fill_block(&mut self) -> io::Result<bool> {
+        let mut header = [0; HEADER_LEN];
+        if !read_exact_or_eof(&mut self.stream, &mut header)? {
+            return Ok(false);
+        }
+        let compressed_len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let uncompressed_len = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+        if compressed_len > 2 * MAX_COMPRESSED_BLOCK_LEN
+            || uncompressed_len > MAX_COMPRESSED_BLOCK_LEN
+        {
+            return Err(invalid_data("compressed block exceeds the maximum size"));
+        }
+        if uncompressed_len == 0 {
+            // Would otherwise be indistinguishable from end of file for the
+            // caller of read.
+            return Err(invalid_data("received an empty compressed block"));
+        }
+        self.scratch.resize(compressed_len, 0);
+        self.stream.read_exact(&mut self.scratch)?;
+        self.read_buf.clear();
+        self.read_offset = 0;
+        self.codec.decompress(&self.scratch, uncompressed_len, &mut self.read_buf)?;
+        if self.read_buf.len() != uncompressed_len {
+            self.read_buf.clear();
+            return Err(invalid_data(
+                "decompressed block does not have the size specified in its header",
+            ));
+        }
+        Ok(true)
+    }
+}
+impl<S: Write, C: Codec> CompressedStream<S, C> {
+    /// Compresses and sends the data which is waiting to be sent, if any.
+    ///
+    /// If writing into the stream fails, the rest of the block is sent by the
+    /// next call, so that the other end never receives a block twice or only
+    /// a part of it followed by another block.
+    fn send_block(&mut self) -> io::Result<()> {
+        self.send_frame()?;
+        if self.write_buf.is_empty() {
+            return Ok(());
+        }
+        self.frame.clear();
+        self.frame.extend_from_slice(&[0; HEADER_LEN]);
+        if let Err(e) = self.codec.compress(&self.write_buf, &mut self.frame) {
+            self.frame.clear();
+            return Err(e);
+        }
+        let compressed_len = self.frame.len() - HEADER_LEN;
+        if compressed_len > 2 * MAX_COMPRESSED_BLOCK_LEN {
+            self.frame.clear();
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the codec made the block more than twice as big",
+            ));
+        }
+        self.frame[..4].copy_from_slice(&(compressed_len as u32).to_le_bytes());
+        self.frame[4..HEADER_LEN].copy_from_slice(&(self.write_buf.len() as u32).to_le_bytes());
+        // The data is in the frame now, and is sent with it.
+        self.write_buf.clear();
+        self.send_frame()
+    }
+    /// Writes the part of the current frame which hasn't been written yet.
+    fn send_frame(&mut self) -> io::Result<()> {
+        while self.frame_sent < self.frame.len() {
+            match self.stream.write(&self.frame[self.frame_sent..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(bytes_written) => self.frame_sent += bytes_written,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+        self.frame.clear();
+        self.frame_sent = 0;
+        Ok(())
+    }
+}
+impl<S: Read, C: Codec> Read for CompressedStream<S, C> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.read_offset == self.read_buf.len() && !self.fill_block()? {
+            return Ok(0);
+        }
+        let available = &self.read_buf[self.read_offset..];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.read_offset += len;
+        Ok(len)
+    }
+}
+impl<S: Write, C: Codec> Write for CompressedStream<S, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.write_buf.len() == self.block_size {
+            self.send_block()?;
+        }
+        let len = buf.len().min(self.block_size - self.write_buf.len());
+        self.write_buf.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+    /// Compresses and sends the data written so far, even if it doesn't fill
+    /// up a block, and flushes the underlying stream.
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_block()?;
+        self.stream.flush()
+    }
+}
+
+/// Like `read_exact`, but returns `false` instead of failing if the reader is
+/// at end of file before anything has been read.
+fn read_exact_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(bytes_read) => filled += bytes_read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Run-length encoding, which compresses the test data well and is simple
+    /// enough to not need testing itself.
+    struct Rle;
+    impl Codec for Rle {
+        fn compress(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+            let mut i = 0;
+            while i < input.len() {
+                let len = input[i..].iter().take(255).take_while(|&&b| b == input[i]).count();
+                output.extend_from_slice(&[len as u8, input[i]]);
+                i += len;
+            }
+            Ok(())
+        }
+        fn decompress(
+            &mut self,
+            input: &[u8],
+            _uncompressed_len: usize,
+            output: &mut Vec<u8>,
+        ) -> io::Result<()> {
+            for pair in input.chunks(2) {
+                output.extend(std::iter::repeat(pair[1]).take(pair[0] as usize));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = (0..10_000_u32).map(|i| (i / 100) as u8).collect::<Vec<_>>();
+        let mut writer = CompressedStream::with_block_size(Vec::new(), Rle, 4096);
+        writer.write_all(&data).unwrap();
+        writer.write_all(b"tail").unwrap();
+        writer.flush().unwrap();
+        let (compressed, _) = writer.into_inner();
+        assert!(compressed.len() < data.len() / 10);
+
+        let mut reader = CompressedStream::new(Cursor::new(compressed), Rle);
+        // The reader only ever holds one block, but that's invisible here.
+        let mut received = Vec::new();
+        reader.read_to_end(&mut received).unwrap();
+        assert_eq!(received[..data.len()], data[..]);
+        assert_eq!(&received[data.len()..], b"tail");
+    }
+
+    #[test]
+    fn unflushed_data_is_not_sent() {
+        let mut writer = CompressedStream::new(Vec::new(), Rle);
+        writer.write_all(b"aaaa").unwrap();
+        assert!(writer.get_ref().is_empty());
+        writer.flush().unwrap();
+        assert_eq!(writer.get_ref()[..], [2, 0, 0, 0, 4, 0, 0, 0, 4, b'a']);
+    }
+
+    #[test]
+    fn reject_oversized_block() {
+        let mut frame = Vec::new();
+        frame.extend_from_slice(&2_u32.to_le_bytes());
+        frame.extend_from_slice(&(MAX_COMPRESSED_BLOCK_LEN as u32 + 1).to_le_bytes());
+        frame.extend_from_slice(&[255, b'a']);
+        let mut reader = CompressedStream::new(Cursor::new(frame), Rle);
+        let error = reader.read(&mut [0; 16]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reject_empty_block() {
+        let frame = [0; HEADER_LEN];
+        let mut reader = CompressedStream::new(Cursor::new(frame), Rle);
+        let error = reader.read(&mut [0; 16]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    /// Accepts a limited amount of bytes, then fails until more is allowed.
+    struct Choppy {
+        written: Vec<u8>,
+        allowed: usize,
+    }
+    impl Write for Choppy {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.allowed == 0 {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let len = buf.len().min(self.allowed);
+            self.written.extend_from_slice(&buf[..len]);
+            self.allowed -= len;
+            Ok(len)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn resume_after_partial_write() {
+        let stream = Choppy {
+            written: Vec::new(),
+            allowed: 5,
+        };
+        let mut writer = CompressedStream::new(stream, Rle);
+        writer.write_all(b"aaaa").unwrap();
+        let error = writer.flush().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+        writer.get_mut().allowed = usize::MAX;
+        writer.write_all(b"bb").unwrap();
+        writer.flush().unwrap();
+        // The first block is finished instead of being sent again from the
+        // start, and the data written after the failure goes into a new one.
+        assert_eq!(
+            writer.get_ref().written[..],
+            [2, 0, 0, 0, 4, 0, 0, 0, 4, b'a', 2, 0, 0, 0, 2, 0, 0, 0, 2, b'b'],
+        );
+    }
+
+    #[test]
+    fn truncated_block() {
+        let mut writer = CompressedStream::new(Vec::new(), Rle);
+        writer.write_all(b"abc").unwrap();
+        writer.flush().unwrap();
+        let (mut compressed, _) = writer.into_inner();
+        compressed.pop();
+        let mut reader = CompressedStream::new(Cursor::new(compressed), Rle);
+        let error = reader.read(&mut [0; 16]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::Sealed;
+use std::io::{self, Read, Write};
+
+/// The operations which every kind of IPC connection in the crate supports,
+/// for code which wants to work with any of them without caring about the
+/// backend, such as protocol implementations.
+///
+/// Implemented by [`LocalSocketStream`] on all platforms and by Windows named
+/// pipe streams which can be both read from and written to, i.e.
+/// [`DuplexBytePipeStream`] and [`DuplexMsgPipeStream`]. Generic code can
+/// take an `impl Connection` and run unchanged on top of either.
+///
+/// # Support
+/// Reading, writing, [`peek`] and [`is_connected`] work with every
+/// implementor on every platform. The others may fail with
+/// `ErrorKind::Unsupported` (`ErrorKind::Other` on compilers which predate
+/// it) depending on the platform:
+/// - [`peer_process_id`] is unsupported on Unix systems which don't report the
+///   credentials of the peer, currently macOS and iOS among the common ones;
+/// - [`shutdown`] is unsupported with named pipes, since they cannot be shut
+///   down without closing the handle — the connection ends when the stream is
+///   dropped instead.
+///
+/// The asynchronous streams are read and written through the `futures` traits
+/// rather than [`Read`] and [`Write`], and implement [`AsyncConnection`]
+/// instead.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use interprocess::{local_socket::LocalSocketListener, Connection};
+/// use std::io::{self, prelude::*};
+///
+/// fn serve(conn: &mut impl Connection) -> io::Result<()> {
+///     let pid = conn.peer_process_id().ok();
+///     let mut request = [0; 4];
+///     conn.read_exact(&mut request)?;
+///     write!(conn, "{:?}", pid)
+/// }
+///
+/// let listener = LocalSocketListener::bind("/tmp/example.sock")?;
+/// for conn in listener.incoming() {
+///     serve(&mut conn?)?;
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// [`LocalSocketStream`]: local_socket/struct.LocalSocketStream.html " "
+/// [`DuplexBytePipeStream`]: os/windows/named_pipe/struct.DuplexBytePipeStream.html " "
+/// [`DuplexMsgPipeStream`]: os/windows/named_pipe/struct.DuplexMsgPipeStream.html " "
+/// [`peek`]: #tymethod.peek " "
+/// [`is_connected`]: #tymethod.is_connected " "
+/// [`peer_process_id`]: #tymethod.peer_process_id " "
+/// [`shutdown`]: #tymethod.shutdown " "
+/// [`Read`]: https://doc.rust-lang.org/std/io/trait.Read.html " "
+/// [`Write`]: https://doc.rust-lang.org/std/io/trait.Write.html " "
+/// [`AsyncConnection`]: trait.AsyncConnection.html " "
+pub trait Connection: Read + Write + Sealed {
+    /// Retrieves the identifier of the process on the other end of the
+    /// connection.
+    fn peer_process_id(&self) -> io::Result<u32>;
+    /// Shuts down both directions of the connection: the peer reads end of
+    /// file once it has read everything sent before the call, and reading and
+    /// writing on this side fail or report end of file from then on.
+    ///
+    /// Unsupported with named pipes.
+    fn shutdown(&self) -> io::Result<()>;
+    /// Returns `false` once the peer has closed the connection and everything
+    /// it sent has been read, `true` otherwise. Doesn't consume any input.
+    fn is_connected(&self) -> io::Result<bool>;
+    /// Copies the input which has arrived into `buf` without consuming it, so
+    /// that the next read returns the same data, and returns how many bytes
+    /// were copied.
+    ///
+    /// Never waits for input: if none has arrived and the peer is still
+    /// connected, fails with `ErrorKind::WouldBlock`. Returns `0` once the peer
+    /// has closed the connection and everything it sent has been read, or if
+    /// `buf` is empty. With message-mode pipes, only the message which the next
+    /// read would start with is copied.
+    fn peek(&self, buf: &mut [u8]) -> io::Result<usize>;
+}
+
+/// The asynchronous counterpart of [`Connection`], for code which works with
+/// any of the asynchronous IPC connections in the crate.
+///
+/// Implemented by the asynchronous [`LocalSocketStream`] on all platforms and
+/// by the Tokio named pipe streams which can be both read from and written to,
+/// i.e. [`DuplexBytePipeStream`] and [`DuplexMsgPipeStream`]. Reading and
+/// writing go through [`AsyncRead`] and [`AsyncWrite`]; the other methods
+/// never block, and thus are not asynchronous themselves. They behave like
+/// their namesakes on `Connection`, including which of them are supported
+/// where.
+///
+/// [`Connection`]: trait.Connection.html " "
+/// [`LocalSocketStream`]: nonblocking/local_socket/struct.LocalSocketStream.html " "
+/// [`DuplexBytePipeStream`]: os/windows/named_pipe/tokio/struct.DuplexBytePipeStream.html " "
+/// [`DuplexMsgPipeStream`]: os/windows/named_pipe/tokio/struct.DuplexMsgPipeStream.html " "
+/// [`AsyncRead`]: https://docs.rs/futures/*/futures/io/trait.AsyncRead.html " "
+/// [`AsyncWrite`]: https://docs.rs/futures/*/futures/io/trait.AsyncWrite.html " "
+#[cfg(any(feature = "nonblocking", feature = "tokio_support"))]
+#[cfg_attr(
+    feature = "doc_cfg",
+    doc(cfg(any(feature = "nonblocking", feature = "tokio_support")))
+)]
+pub trait AsyncConnection: futures::AsyncRead + futures::AsyncWrite + Sealed {
+    /// See [`Connection::peer_process_id`](trait.Connection.html#tymethod.
+    /// peer_process_id).
+    fn peer_process_id(&self) -> io::Result<u32>;
+    /// See [`Connection::shutdown`](trait.Connection.html#tymethod.shutdown).
+    fn shutdown(&self) -> io::Result<()>;
+    /// See [`Connection::is_connected`](trait.Connection.html#tymethod.
+    /// is_connected).
+    fn is_connected(&self) -> io::Result<bool>;
+    /// See [`Connection::peek`](trait.Connection.html#tymethod.peek).
+    fn peek(&self, buf: &mut [u8]) -> io::Result<usize>;
+}
@@ -50,6 +50,9 @@
 #![deny(rust_2018_idioms)]
 #![warn(missing_docs)]
 #![allow(clippy::nonstandard_macro_braces)]
+// Deriving `Default` for enums requires `#[default]`, which is too new for the
+// supported compilers.
+#![allow(clippy::derivable_impls)]
 #![cfg_attr(
     unsafe_op_in_unsafe_fn_stable, // This is set by the build script on Rust 1.52+
     forbid(unsafe_op_in_unsafe_fn),
@@ -100,7 +103,7 @@ compile_error!(
      an issue on the GitHub repository"
 );
 
-pub(crate) use private::Sealed;
+pub(crate) use private::{copy_to_writer, read_chunks, read_framed, unsupported, Sealed};
 use std::{
     error::Error,
     fmt::{self, Display, Formatter},
@@ -130,6 +133,35 @@ pub(crate) mod private {
                     ::std::os::unix::io::AsRawFd::as_raw_fd(&self.inner)
                 }
             }
+            impl_as_borrowed_handle!($ty);
+        };
+    }
+    macro_rules! impl_as_borrowed_handle {
+        ($ty:ident) => {
+            #[cfg(all(windows, io_safety_stable))]
+            impl ::std::os::windows::io::AsHandle for $ty {
+                fn as_handle(&self) -> ::std::os::windows::io::BorrowedHandle<'_> {
+                    unsafe {
+                        // SAFETY: the handle is owned by self and thus stays open for as long as
+                        // it's borrowed
+                        ::std::os::windows::io::BorrowedHandle::borrow_raw(
+                            ::std::os::windows::io::AsRawHandle::as_raw_handle(self),
+                        )
+                    }
+                }
+            }
+            #[cfg(all(unix, io_safety_stable))]
+            impl ::std::os::unix::io::AsFd for $ty {
+                fn as_fd(&self) -> ::std::os::unix::io::BorrowedFd<'_> {
+                    unsafe {
+                        // SAFETY: the file descriptor is owned by self and thus stays open for as
+                        // long as it's borrowed
+                        ::std::os::unix::io::BorrowedFd::borrow_raw(
+                            ::std::os::unix::io::AsRawFd::as_raw_fd(self),
+                        )
+                    }
+                }
+            }
         };
     }
     macro_rules! impl_into_raw_handle {
@@ -146,6 +178,38 @@ pub(crate) mod private {
                     ::std::os::unix::io::IntoRawFd::into_raw_fd(self.inner)
                 }
             }
+            #[cfg(windows)]
+            impl $ty {
+                /// Consumes the object and returns the handle which it owns, without
+                /// closing it.
+                ///
+                /// This is the same as `into_raw_handle`, for code which transfers
+                /// ownership of the handle out-of-band, such as by passing it to a C API
+                /// which will close it. **Closing the handle becomes the caller's
+                /// responsibility**: it is leaked unless closed by whoever it's given to
+                /// or turned back into an object with `from_raw_handle`, and closing it
+                /// more than once might close an unrelated handle which has been given
+                /// the same value in the meantime.
+                pub fn forget(self) -> *mut ::std::ffi::c_void {
+                    ::std::os::windows::io::IntoRawHandle::into_raw_handle(self)
+                }
+            }
+            #[cfg(unix)]
+            impl $ty {
+                /// Consumes the object and returns the file descriptor which it owns,
+                /// without closing it.
+                ///
+                /// This is the same as `into_raw_fd`, for code which transfers ownership
+                /// of the file descriptor out-of-band, such as by passing it to a C API
+                /// which will close it. **Closing the file descriptor becomes the
+                /// caller's responsibility**: it is leaked unless closed by whoever it's
+                /// given to or turned back into an object with `from_raw_fd`, and
+                /// closing it more than once might close an unrelated file descriptor
+                /// which has been given the same number in the meantime.
+                pub fn forget(self) -> ::libc::c_int {
+                    ::std::os::unix::io::IntoRawFd::into_raw_fd(self)
+                }
+            }
         };
     }
     macro_rules! impl_from_raw_handle {
@@ -181,6 +245,102 @@ pub(crate) mod private {
     // public traits. We use a private module instead to make it impossible to
     // name the trait from outside the crate.
     pub trait Sealed {}
+
+    /// Creates an error for features which are not available on the current
+    /// platform, using `ErrorKind::Unsupported` where it's available.
+    #[allow(dead_code)] // Not every platform has something unsupported
+    pub fn unsupported(message: &'static str) -> std::io::Error {
+        #[cfg(io_error_kind_unsupported_stable)]
+        let kind = std::io::ErrorKind::Unsupported;
+        #[cfg(not(io_error_kind_unsupported_stable))]
+        let kind = std::io::ErrorKind::Other;
+        std::io::Error::new(kind, message)
+    }
+
+    /// Shared implementation of the `read_chunks` methods: reads into a
+    /// buffer of the specified size using `read` until end of file, passing
+    /// every chunk to `f` and returning the total amount of bytes processed.
+    pub fn read_chunks(
+        chunk_size: usize,
+        mut read: impl FnMut(&mut [u8]) -> std::io::Result<usize>,
+        mut f: impl FnMut(&[u8]) -> std::io::Result<()>,
+    ) -> std::io::Result<u64> {
+        assert_ne!(chunk_size, 0, "chunk size must not be zero");
+        // Allocated once, reused for every chunk.
+        let mut buffer = vec![0; chunk_size];
+        let mut total = 0;
+        loop {
+            let bytes_read = match read(&mut buffer) {
+                Ok(0) => return Ok(total),
+                Ok(bytes_read) => bytes_read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            f(&buffer[..bytes_read])?;
+            total += bytes_read as u64;
+        }
+    }
+
+    /// Copies up to `count` bytes from `read` into `writer` through a userspace
+    /// buffer, stopping early at end of file. Returns the amount of bytes
+    /// copied.
+    pub fn copy_to_writer(
+        count: usize,
+        mut read: impl FnMut(&mut [u8]) -> std::io::Result<usize>,
+        writer: &mut impl std::io::Write,
+    ) -> std::io::Result<usize> {
+        let mut buffer = vec![0; count.min(COPY_BUFFER_SIZE)];
+        let mut total = 0;
+        while total < count {
+            let to_read = (count - total).min(buffer.len());
+            let bytes_read = match read(&mut buffer[..to_read]) {
+                Ok(0) => break,
+                Ok(bytes_read) => bytes_read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            writer.write_all(&buffer[..bytes_read])?;
+            total += bytes_read;
+        }
+        Ok(total)
+    }
+    const COPY_BUFFER_SIZE: usize = 8192;
+
+    /// Shared implementation of the `read_framed` methods: reads a header of
+    /// `header_len` bytes using `read`, asks `body_len_from_header` how long the
+    /// body is, and reads the body.
+    pub fn read_framed(
+        header_len: usize,
+        body_len_from_header: impl FnOnce(&[u8]) -> std::io::Result<usize>,
+        mut read: impl FnMut(&mut [u8]) -> std::io::Result<usize>,
+    ) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+        let mut header = vec![0; header_len];
+        fill(&mut header, &mut read)?;
+        // Only allocated once the length has been vetted.
+        let mut body = vec![0; body_len_from_header(&header)?];
+        fill(&mut body, &mut read)?;
+        Ok((header, body))
+    }
+    /// `read_exact` in terms of a closure.
+    fn fill(
+        mut buf: &mut [u8],
+        read: &mut impl FnMut(&mut [u8]) -> std::io::Result<usize>,
+    ) -> std::io::Result<()> {
+        while !buf.is_empty() {
+            match read(buf) {
+                Ok(0) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "end of file reached in the middle of a frame",
+                    ))
+                },
+                Ok(bytes_read) => buf = &mut buf[bytes_read..],
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
 }
 
 pub mod local_socket;
@@ -197,6 +357,29 @@ pub mod unnamed_pipe;
 
 pub mod os;
 
+mod message_writer;
+pub use message_writer::*;
+mod priority_writer;
+pub use priority_writer::*;
+#[cfg(any(doc, feature = "ring_reader"))]
+mod ring_reader;
+#[cfg(any(doc, feature = "ring_reader"))]
+pub use ring_reader::*;
+#[cfg(any(doc, feature = "pipe_muxer"))]
+mod pipe_muxer;
+#[cfg(any(doc, feature = "pipe_muxer"))]
+pub use pipe_muxer::*;
+#[cfg(any(doc, feature = "compression"))]
+mod compressed_stream;
+#[cfg(any(doc, feature = "compression"))]
+pub use compressed_stream::*;
+mod vectored;
+pub use vectored::*;
+mod mode_negotiation;
+pub use mode_negotiation::*;
+mod connection;
+pub use connection::*;
+
 /// Reading from named pipes with message boundaries reliably, without
 /// truncation.
 ///
@@ -291,3 +474,54 @@ impl Display for PartialMsgWriteError {
 }
 impl Error for PartialMsgWriteError {
 }
+
+/// What a listener does with clients which arrive while it is already serving
+/// as many connections as its connection limit allows.
+///
+/// The limit is counted in streams produced by the listener which are still
+/// alive: dropping such a stream frees up its place for the next client.
+///
+/// # Client-visible behavior
+/// ## `Queue`
+/// New clients wait until the server has room for them. On Unix, they are kept
+/// in the listen backlog of the socket: `connect` succeeds (or blocks, if the
+/// backlog itself is full), but nothing sent by the client is read until one of
+/// the existing connections is closed and the server accepts it. On Windows,
+/// there is no backlog — clients receive `ERROR_PIPE_BUSY` while all instances
+/// of the pipe are in use, just like with any other named pipe server, and
+/// those which wait for an instance to free up instead of giving up are
+/// connected once the server accepts again.
+///
+/// ## `Reject`
+/// New clients are turned away right away, so that they can fail over to
+/// another endpoint instead of waiting. On Unix, the connection is accepted and
+/// immediately closed without any data being sent, meaning that the first read
+/// performed by the client reports end of file (or, if the client has already
+/// sent something, a "connection reset" error) and writes fail with a "broken
+/// pipe" error. On Windows, the server stops having listening instances, so
+/// clients get `ERROR_PIPE_BUSY` immediately when trying to connect.
+///
+/// # Server-side behavior
+/// With `Queue`, calls to `accept` wait until a place frees up before
+/// accepting the next client. With `Reject`, `accept` only returns clients
+/// which fit into the limit: on Unix, the ones which don't are closed
+/// internally and `accept` continues waiting for another client, while on
+/// Windows, `accept` returns the `ERROR_PIPE_BUSY` error itself when there's no
+/// free instance, leaving it to the caller to decide when to try again. In
+/// nonblocking mode, `accept` fails with [`WouldBlock`] instead of waiting for
+/// a place with `Queue`.
+///
+/// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock " "
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// Keep clients which arrive at the limit waiting until there's room for
+    /// them. This is the default.
+    Queue,
+    /// Turn away clients which arrive at the limit.
+    Reject,
+}
+impl Default for OverflowPolicy {
+    fn default() -> Self {
+        Self::Queue
+    }
+}
@@ -0,0 +1,2159 @@
+//! Local sockets, an IPC primitive featuring a server and multiple clients
+//! connecting to that server using a filesystem path inside a special
+//! namespace, each having a private connection to that server.
+//!
+//! Local sockets are not a real IPC method implemented by the OS — they were
+//! introduced because of the difference between named pipes on Windows and
+//! Unix: named pipes on Windows are almost the same as Unix domain sockets on
+//! Linux while Unix named pipes (which are referred to as FIFO files in this
+//! crate to avoid confusion) are like unnamed pipes but identifiable with a
+//! filesystem path: there's no distinction between writers and the first reader
+//! takes all. **Simply put, local sockets use named pipes on Windows and Unix
+//! domain sockets on Unix.**
+//!
+//! ## Platform-specific namespaces
+//! There's one more problem regarding platform differences: since only Linux
+//! supports putting Ud-sockets in a separate namespace which is isolated from
+//! the filesystem, the `LocalSocketName`/`LocalSocketNameBuf` types are used to
+//! identify local sockets rather than `OsStr`/`OsString`: on Unix platforms
+//! other than Linux, which includes macOS, all flavors of BSD and possibly
+//! other Unix-like systems, the only way to name a Ud-socket is to use a
+//! filesystem path. As such, those platforms don't have the namespaced socket
+//! creation method available. Complicatng matters further, Windows does not
+//! support named pipes in the normal filesystem, meaning that namespaced local
+//! sockets are the only functional method on Windows. As a way to solve this
+//! issue, `LocalSocketName`/`LocalSocketNameBuf` only provide creation in a
+//! platform-specific way, meaning that crate users are required to use
+//! conditional compilation to decide on the name for the socket names.
+
+mod pool;
+mod server;
+pub use pool::*;
+pub use server::*;
+
+use crate::{Connection, OverflowPolicy, Sealed};
+use std::{
+    borrow::Cow,
+    error::Error,
+    ffi::{CStr, CString, OsStr, OsString},
+    fmt::{self, Debug, Display, Formatter},
+    fs::File,
+    io::{self, prelude::*, IoSlice, IoSliceMut},
+    iter::FusedIterator,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    str,
+    sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Arc,
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+impmod! {local_socket,
+    name_type_support_query as name_type_support_query_impl,
+    NAME_TYPE_ALWAYS_SUPPORTED as NAME_TYPE_ALWAYS_SUPPORTED_REAL,
+    to_local_socket_name_osstr,
+    to_local_socket_name_osstring,
+    LocalSocketListener as LocalSocketListenerImpl,
+    LocalSocketStream as LocalSocketStreamImpl,
+    CancelTarget,
+    check_availability as check_availability_impl,
+}
+
+/// Whether a stream has input waiting to be read, as reported by
+/// `input_state` on the platform-specific streams without consuming any of it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum InputState {
+    Empty,
+    Pending,
+    /// The other side has closed the connection, and everything it sent
+    /// before that has been read.
+    Disconnected,
+}
+
+/// A local socket server, listening for connections.
+///
+/// # Example
+/// ```no_run
+/// use interprocess::local_socket::{LocalSocketListener, LocalSocketStream};
+/// use std::io::{self, prelude::*, BufReader};
+///
+/// fn handle_error(conn: io::Result<LocalSocketStream>) -> Option<LocalSocketStream> {
+///     match conn {
+///         Ok(val) => Some(val),
+///         Err(error) => {
+///             eprintln!("Incoming connection failed: {}", error);
+///             None
+///         }
+///     }
+/// }
+///
+/// let listener = LocalSocketListener::bind("/tmp/example.sock")?;
+/// for mut conn in listener.incoming().filter_map(handle_error) {
+///     conn.write_all(b"Hello from server!\n")?;
+///     let mut conn = BufReader::new(conn);
+///     let mut buffer = String::new();
+///     conn.read_line(&mut buffer);
+///     println!("Client answered: {}", buffer);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// # Handing connections off to a thread pool
+/// Both the listener and the streams it produces are [`Send`], and neither
+/// borrows anything from the other, so accepted streams are `Send + 'static`
+/// and can be moved into the task queue of a thread pool right away, leaving
+/// the accepting thread free to wait for the next client. The listener is also
+/// [`Sync`], allowing several threads to call [`accept`] on a shared reference
+/// to it at the same time.
+/// ```no_run
+/// use interprocess::local_socket::{LocalSocketListener, LocalSocketStream};
+/// use std::{
+///     io::{prelude::*, BufReader},
+///     sync::{mpsc, Arc, Mutex},
+///     thread,
+/// };
+///
+/// let (sender, receiver) = mpsc::channel::<LocalSocketStream>();
+/// let receiver = Arc::new(Mutex::new(receiver));
+/// for _ in 0..4 {
+///     let receiver = Arc::clone(&receiver);
+///     thread::spawn(move || loop {
+///         // The lock is only held for as long as it takes to dequeue a connection.
+///         let conn = match receiver.lock().unwrap().recv() {
+///             Ok(conn) => conn,
+///             Err(_) => break,
+///         };
+///         let mut conn = BufReader::new(conn);
+///         let mut buffer = String::new();
+///         if conn.read_line(&mut buffer).is_ok() {
+///             let _ = conn.get_mut().write_all(buffer.as_bytes());
+///         }
+///     });
+/// }
+///
+/// let listener = LocalSocketListener::bind("/tmp/example.sock")?;
+/// for conn in listener.incoming().filter_map(Result::ok) {
+///     sender.send(conn)?;
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// [`accept`]: #method.accept " "
+pub struct LocalSocketListener {
+    inner:  LocalSocketListenerImpl,
+    closed: AtomicBool,
+}
+impl LocalSocketListener {
+    fn from_impl(inner: LocalSocketListenerImpl) -> Self {
+        Self {
+            inner,
+            closed: AtomicBool::new(false),
+        }
+    }
+    /// Creates a socket server with the specified local socket name.
+    pub fn bind<'a>(name: impl ToLocalSocketName<'a>) -> io::Result<Self> {
+        Ok(Self::from_impl(LocalSocketListenerImpl::bind(name)?))
+    }
+    /// Creates a socket server with the specified local socket name which
+    /// serves at most `max_connections` clients at a time.
+    ///
+    /// Each stream produced by the listener counts towards the limit until it
+    /// is dropped. Clients which arrive while the server is at the limit are
+    /// handled according to `overflow_policy`: they either wait until a
+    /// connection is closed or are turned away immediately, so that they can
+    /// try another endpoint. See [`OverflowPolicy`] for what clients observe in
+    /// each case.
+    ///
+    /// # Platform-specific behavior
+    /// ## Windows
+    /// The limit is enforced by the system as the instance limit of the named
+    /// pipe, which cannot be higher than 254 — an `InvalidInput` error is
+    /// returned for bigger limits.
+    ///
+    /// [`OverflowPolicy`]: ../enum.OverflowPolicy.html " "
+    pub fn bind_with_limit<'a>(
+        name: impl ToLocalSocketName<'a>,
+        max_connections: NonZeroUsize,
+        overflow_policy: OverflowPolicy,
+    ) -> io::Result<Self> {
+        Ok(Self::from_impl(LocalSocketListenerImpl::bind_with_limit(
+            name,
+            max_connections,
+            overflow_policy,
+        )?))
+    }
+    /// Listens for incoming connections to the socket, blocking until a client
+    /// is connected.
+    ///
+    /// See [`incoming`] for a convenient way to create a main loop for a
+    /// server.
+    ///
+    /// Once the listener has been [closed], fails with a
+    /// [`ListenerClosedError`] right away instead.
+    ///
+    /// [`incoming`]: #method.incoming " "
+    /// [closed]: #method.close " "
+    /// [`ListenerClosedError`]: struct.ListenerClosedError.html " "
+    pub fn accept(&self) -> io::Result<LocalSocketStream> {
+        if self.is_closed() {
+            return Err(ListenerClosedError.into());
+        }
+        let accepted = self.inner.accept();
+        // Closing wakes up the accepts which are waiting, making this one fail or
+        // return a throwaway client, neither of which is reported.
+        if self.is_closed() {
+            return Err(ListenerClosedError.into());
+        }
+        Ok(LocalSocketStream::from_impl(accepted?))
+    }
+    /// Closes the listener, making all further calls to [`accept`] fail with a
+    /// [`ListenerClosedError`] and ending iteration through [`incoming`], so
+    /// that server loops running on other threads terminate deterministically
+    /// instead of running into whatever error the system would report.
+    ///
+    /// Calls to `accept` which are already waiting for a client are woken up
+    /// and fail with the same error. Besides that, this only stops the
+    /// listener from accepting: the socket keeps its name until the listener
+    /// is dropped, and streams which have already been accepted are not
+    /// affected. Closing is permanent, and closing a listener which is already
+    /// closed does nothing.
+    ///
+    /// # Platform-specific behavior
+    /// ## Unix
+    /// Waiting calls are woken up by shutting down the receiving half of the
+    /// listening socket, which makes new clients get refused from then on.
+    /// Some systems other than Linux ignore that for listening sockets, in
+    /// which case waiting calls keep waiting until a client connects.
+    /// ## Windows
+    /// Waiting calls are woken up by connecting a throwaway client to each
+    /// instance of the pipe which is waiting for one, which is disconnected
+    /// right away.
+    ///
+    /// [`accept`]: #method.accept " "
+    /// [`ListenerClosedError`]: struct.ListenerClosedError.html " "
+    /// [`incoming`]: #method.incoming " "
+    pub fn close(&self) {
+        if !self.closed.swap(true, SeqCst) {
+            self.inner.wake_accepts();
+        }
+    }
+    /// Returns `true` if the listener has been [closed].
+    ///
+    /// [closed]: #method.close " "
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(SeqCst)
+    }
+    /// Creates an iterator which calls `accept()` with each iteration, until
+    /// the listener is [closed]. Used together with `for` loops to
+    /// conveniently create a main loop for a socket server.
+    ///
+    /// # Example
+    /// See the struct-level documentation for a full example which already uses
+    /// this method.
+    ///
+    /// [closed]: #method.close " "
+    pub fn incoming(&self) -> Incoming<'_> {
+        Incoming::from(self)
+    }
+    /// Enables or disables the nonblocking mode for the listener. By default,
+    /// it is disabled.
+    ///
+    /// In nonblocking mode, calling [`accept`] and iterating through
+    /// [`incoming`] will immediately return a [`WouldBlock`] error if there is
+    /// no client attempting to connect at the moment instead of blocking until
+    /// one arrives.
+    ///
+    /// # Platform-specific behavior
+    /// ## Windows
+    /// The nonblocking mode will be also be set for the streams produced by
+    /// [`accept`] and [`incoming`], both existing and new ones.
+    ///
+    /// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock " "
+    /// [`accept`]: #method.accept " "
+    /// [`incoming`]: #method.incoming " "
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.inner.set_nonblocking(nonblocking)
+    }
+    /// Converts the listener into its file descriptor, prepared to be
+    /// inherited across `exec` by clearing its close-on-exec flag, for handing
+    /// the listener over to a new image of the server without ever closing the
+    /// socket.
+    ///
+    /// # Handoff protocol
+    /// 1. The old image calls this method and stores the returned number
+    ///    somewhere the new image will find it, typically an environment
+    ///    variable such as `LISTEN_FD=3`.
+    /// 2. The old image calls `exec` (e.g. via `CommandExt::exec` from the
+    ///    standard library) with its new version.
+    /// 3. The new image finds the variable, removes it from its environment so
+    ///    that its own children don't pick it up, and calls
+    ///    [`from_inherited_fd`] with the number to resume accepting.
+    ///
+    /// Clients which connect during the handoff wait in the listen backlog of
+    /// the socket and are accepted by the new image. Should `exec` fail, the
+    /// old image can take the listener back with `from_inherited_fd` too.
+    ///
+    /// See [`UdStreamListener::into_inheritable_fd`] for the details.
+    ///
+    /// [`from_inherited_fd`]: #method.from_inherited_fd " "
+    /// [`UdStreamListener::into_inheritable_fd`]: ../os/unix/udsocket/struct.UdStreamListener.html#method.into_inheritable_fd " "
+    #[cfg(unix)]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(unix)))]
+    pub fn into_inheritable_fd(self) -> io::Result<std::os::unix::io::RawFd> {
+        self.inner.into_inheritable_fd()
+    }
+    /// Resumes accepting connections on a listener inherited across `exec`,
+    /// as produced by [`into_inheritable_fd`], making the file descriptor
+    /// close-on-exec again.
+    ///
+    /// # Safety
+    /// The file descriptor must be a listening socket of the kind created by
+    /// `LocalSocketListener` and must not be owned by anything else, which is
+    /// the case if it was passed on as described by the handoff protocol.
+    ///
+    /// [`into_inheritable_fd`]: #method.into_inheritable_fd " "
+    #[cfg(unix)]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(unix)))]
+    pub unsafe fn from_inherited_fd(fd: std::os::unix::io::RawFd) -> io::Result<Self> {
+        Ok(Self::from_impl(unsafe {
+            LocalSocketListenerImpl::from_inherited_fd(fd)
+        }?))
+    }
+    /// Converts the listener into a handle to one of the instances of its
+    /// named pipe, marked as inheritable by child processes, for handing the
+    /// listener over to a new version of the server without the pipe ever
+    /// ceasing to exist.
+    ///
+    /// # Handoff protocol
+    /// Windows has no `exec`, so the handoff happens between two processes:
+    /// 1. The old process calls this method and spawns the new one with the
+    ///    returned handle value in an environment variable, such as
+    ///    `LISTEN_HANDLE=0x1a4`. `std::process::Command` lets child processes
+    ///    inherit all inheritable handles, so nothing else is needed.
+    /// 2. The new process calls [`from_inherited_handle`] with the handle and
+    ///    the same name which the old listener was bound to.
+    /// 3. The old process exits once it's done serving its existing
+    ///    connections, which are unaffected.
+    ///
+    /// The inherited pipe instance lets clients connect during the handoff;
+    /// they are accepted once the new process starts accepting.
+    ///
+    /// [`from_inherited_handle`]: #method.from_inherited_handle " "
+    #[cfg(windows)]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(windows)))]
+    pub fn into_inheritable_handle(self) -> io::Result<std::os::windows::io::RawHandle> {
+        self.inner.into_inheritable_handle()
+    }
+    /// Resumes accepting connections on a listener inherited from another
+    /// process, as produced by [`into_inheritable_handle`], making the handle
+    /// non-inheritable again. The name is needed to create further instances of
+    /// the named pipe, and must be the same one which the original listener was
+    /// bound to.
+    ///
+    /// # Safety
+    /// The handle must be a named pipe instance of the kind created by
+    /// `LocalSocketListener` and must not be owned by anything else, which is
+    /// the case if it was passed on as described by the handoff protocol.
+    ///
+    /// [`into_inheritable_handle`]: #method.into_inheritable_handle " "
+    #[cfg(windows)]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(windows)))]
+    pub unsafe fn from_inherited_handle<'a>(
+        handle: std::os::windows::io::RawHandle,
+        name: impl ToLocalSocketName<'a>,
+    ) -> io::Result<Self> {
+        Ok(Self::from_impl(unsafe {
+            LocalSocketListenerImpl::from_inherited_handle(handle, name)
+        }?))
+    }
+}
+impl Debug for LocalSocketListener {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self.inner, f)
+    }
+}
+// On Windows, a listener is made out of several named pipe instances rather
+// than one handle, so there is nothing to expose in the same way there.
+#[cfg(unix)]
+impl LocalSocketListener {
+    /// Consumes the listener and returns the file descriptor of the socket
+    /// without closing it, the same way as `into_raw_fd` does. Closing the
+    /// file descriptor becomes the caller's responsibility — see
+    /// [`LocalSocketStream::forget`] for details.
+    ///
+    /// [`LocalSocketStream::forget`]: struct.LocalSocketStream.html#method.forget " "
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(unix)))]
+    pub fn forget(self) -> libc::c_int {
+        std::os::unix::io::IntoRawFd::into_raw_fd(self)
+    }
+}
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for LocalSocketListener {
+    fn as_raw_fd(&self) -> libc::c_int {
+        std::os::unix::io::AsRawFd::as_raw_fd(&self.inner)
+    }
+}
+#[cfg(unix)]
+impl std::os::unix::io::IntoRawFd for LocalSocketListener {
+    fn into_raw_fd(self) -> libc::c_int {
+        std::os::unix::io::IntoRawFd::into_raw_fd(self.inner)
+    }
+}
+#[cfg(unix)]
+impl std::os::unix::io::FromRawFd for LocalSocketListener {
+    unsafe fn from_raw_fd(fd: libc::c_int) -> Self {
+        Self::from_impl(unsafe { LocalSocketListenerImpl::from_raw_fd(fd) })
+    }
+}
+
+/// An iterator over incoming client connections of a [`LocalSocketListener`],
+/// which ends once the listener is [closed].
+///
+/// This iterator is created by the [`incoming`] method on
+/// [`LocalSocketListener`] — see its documentation for more.
+///
+/// [`LocalSocketListener`]: struct.LocalSocketListener.html " "
+/// [`incoming`]: struct.LocalSocketListener.html#method.incoming " "
+/// [closed]: struct.LocalSocketListener.html#method.close " "
+#[derive(Debug)]
+pub struct Incoming<'a> {
+    listener: &'a LocalSocketListener,
+}
+impl<'a> From<&'a LocalSocketListener> for Incoming<'a> {
+    fn from(listener: &'a LocalSocketListener) -> Self {
+        Self { listener }
+    }
+}
+impl Iterator for Incoming<'_> {
+    type Item = io::Result<LocalSocketStream>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.listener.is_closed() {
+            return None;
+        }
+        Some(self.listener.accept())
+    }
+}
+impl FusedIterator for Incoming<'_> {
+}
+
+/// A local socket byte stream, obtained eiter from [`LocalSocketListener`] or
+/// by connecting to an existing local socket.
+///
+/// # Example
+/// ```no_run
+/// use interprocess::local_socket::LocalSocketStream;
+/// use std::io::{prelude::*, BufReader};
+///
+/// // Replace the path as necessary on Windows.
+/// let mut conn = LocalSocketStream::connect("/tmp/example.sock")?;
+/// conn.write_all(b"Hello from client!\n")?;
+/// let mut conn = BufReader::new(conn);
+/// let mut buffer = String::new();
+/// conn.read_line(&mut buffer)?;
+/// println!("Server answered: {}", buffer);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// [`LocalSocketListener`]: struct.LocalSocketListener.html " "
+pub struct LocalSocketStream {
+    // Declared first so that the cancellation target is cleared before the
+    // handle is closed, which is what makes reusing it impossible.
+    cancel:                   CancelGuard,
+    inner:                    LocalSocketStreamImpl,
+    connected_at:             Instant,
+    connected_at_system_time: SystemTime,
+}
+impl LocalSocketStream {
+    fn from_impl(inner: LocalSocketStreamImpl) -> Self {
+        Self {
+            cancel: CancelGuard::new(&inner),
+            inner,
+            connected_at: Instant::now(),
+            connected_at_system_time: SystemTime::now(),
+        }
+    }
+    /// Connects to a remote local socket server.
+    ///
+    /// If there is no server with the specified name, the error is of kind
+    /// [`NotFound`]. On Unix, a socket file which exists but has no server
+    /// listening on it, usually left behind by a server which didn't exit
+    /// cleanly, is reported as [`ConnectionRefused`] instead.
+    ///
+    /// [`NotFound`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.NotFound " "
+    /// [`ConnectionRefused`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.ConnectionRefused " "
+    pub fn connect<'a>(name: impl ToLocalSocketName<'a>) -> io::Result<Self> {
+        Ok(Self::from_impl(LocalSocketStreamImpl::connect(name)?))
+    }
+    /// Connects to a remote local socket server, failing right away with an
+    /// error of kind [`AddrInUse`] if the server is too busy to take the
+    /// connection at the moment, without waiting or retrying.
+    ///
+    /// This is meant for tools which probe whether a server is available and
+    /// must not block doing so.
+    ///
+    /// # Platform-specific behavior
+    /// ## Unix
+    /// The server is busy if its listen backlog is full, which is when a
+    /// regular `connect` would wait for room in it. The connection attempt is
+    /// performed in nonblocking mode, and `EAGAIN` is reported as `AddrInUse`.
+    ///
+    /// ## Windows
+    /// The server is busy if there are no free instances of the named pipe, in
+    /// which case the system reports `ERROR_PIPE_BUSY`, which is mapped to
+    /// `AddrInUse`.
+    ///
+    /// [`AddrInUse`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.AddrInUse " "
+    pub fn connect_immediate<'a>(name: impl ToLocalSocketName<'a>) -> io::Result<Self> {
+        Ok(Self::from_impl(LocalSocketStreamImpl::connect_immediate(
+            name,
+        )?))
+    }
+    /// Connects to a remote local socket server and verifies that the server is
+    /// the one which the client expects, closing the connection and failing
+    /// with an [`UntrustedServerError`] if it isn't.
+    ///
+    /// The verification is performed by the specified closure, which receives
+    /// the freshly connected stream before anything is sent over it and
+    /// typically checks the server process using [`peer_pid`], e.g. against a
+    /// PID file or by inspecting the executable or the owner of the process.
+    /// Errors returned by it are returned as-is, also closing the connection.
+    ///
+    /// This is the client-side counterpart of checking client credentials on
+    /// the server: it protects the client from an impostor which has taken
+    /// over the name of the server — by binding it first, or after the real
+    /// server has exited — and would otherwise receive whatever the client
+    /// sends. Since the check is performed on the connection itself rather than
+    /// on the name, it cannot be defeated by the name changing owners between
+    /// the check and the connection. What it cannot account for is the state of
+    /// the server process changing after the check, such as the process
+    /// exiting and its identifier being reused by another one, which the
+    /// closure should keep in mind when deciding what to look at.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use interprocess::local_socket::LocalSocketStream;
+    /// use std::{fs, io::prelude::*};
+    ///
+    /// let expected_pid: u32 = fs::read_to_string("/run/example.pid")?.trim().parse()?;
+    /// let mut conn = LocalSocketStream::connect_authenticated("/tmp/example.sock", |conn| {
+    ///     Ok(conn.peer_pid()? == expected_pid)
+    /// })?;
+    /// conn.write_all(b"secret")?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [`UntrustedServerError`]: struct.UntrustedServerError.html " "
+    /// [`peer_pid`]: #method.peer_pid " "
+    pub fn connect_authenticated<'a>(
+        name: impl ToLocalSocketName<'a>,
+        verify: impl FnOnce(&Self) -> io::Result<bool>,
+    ) -> io::Result<Self> {
+        let stream = Self::connect(name)?;
+        if verify(&stream)? {
+            Ok(stream)
+        } else {
+            Err(UntrustedServerError.into())
+        }
+    }
+    /// Retrieves the identifier of the process on the opposite end of the local
+    /// socket connection.
+    ///
+    /// # Platform-specific behavior
+    /// ## macOS and iOS
+    /// Not supported by the OS, will always generate an error at runtime.
+    ///
+    /// [`FromRawHandle`]: https://doc.rust-lang.org/std/os/windows/io/trait.FromRawHandle.html " "
+    pub fn peer_pid(&self) -> io::Result<u32> {
+        self.inner.peer_pid()
+    }
+    /// Returns the point in time at which the connection was established, as
+    /// measured by the monotonic clock: when the stream was returned by
+    /// [`accept`] on the server side or by one of the connection methods on the
+    /// client side.
+    ///
+    /// This is bookkeeping done by the stream itself rather than information
+    /// retrieved from the system, so streams created with `from_raw_handle` or
+    /// `from_raw_fd` report the time at which they were created that way.
+    ///
+    /// [`accept`]: struct.LocalSocketListener.html#method.accept " "
+    pub fn connected_at(&self) -> Instant {
+        self.connected_at
+    }
+    /// Returns the point in time at which the connection was established, as
+    /// measured by the system clock, for audit records which need wall-clock
+    /// time. See [`connected_at`] for details.
+    ///
+    /// Since the system clock can be adjusted, this is not suitable for
+    /// measuring how long the connection has been open — use [`age`] for that.
+    ///
+    /// [`connected_at`]: #method.connected_at " "
+    /// [`age`]: #method.age " "
+    pub fn connected_at_system_time(&self) -> SystemTime {
+        self.connected_at_system_time
+    }
+    /// Returns how much time has passed since the connection was established,
+    /// i.e. since [`connected_at`].
+    ///
+    /// [`connected_at`]: #method.connected_at " "
+    pub fn age(&self) -> Duration {
+        self.connected_at.elapsed()
+    }
+    /// Reads from the stream until end of file, passing the data to the
+    /// specified closure in chunks of at most `chunk_size` bytes and returning
+    /// the total amount of bytes read.
+    ///
+    /// Only one buffer of `chunk_size` bytes is allocated for the whole
+    /// operation, making this suitable for processing payloads of any size,
+    /// such as for hashing or forwarding them, without ever holding all of the
+    /// data in memory. Each chunk is whatever a single read returned, so chunks
+    /// may be smaller than `chunk_size` even before end of file is reached.
+    ///
+    /// If the closure returns an error, the operation stops and the error is
+    /// returned as-is. Interrupted reads are retried.
+    ///
+    /// # Panics
+    /// If `chunk_size` is zero.
+    ///
+    /// # Platform-specific behavior
+    /// ## Windows
+    /// The other side disconnecting is treated as end of file, even though
+    /// named pipes report it as a "broken pipe" error when reading.
+    pub fn read_chunks(
+        &mut self,
+        chunk_size: usize,
+        mut f: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> io::Result<u64> {
+        let (inner, cancel) = (&mut self.inner, &self.cancel);
+        // Every chunk counts as activity, so that a long transfer doesn't look idle.
+        cancel.guard(|| {
+            inner.read_chunks(chunk_size, |chunk| {
+                cancel.record_activity();
+                f(chunk)
+            })
+        })
+    }
+    /// Reads a frame made of a fixed-size header followed by a body whose
+    /// length is stated in the header, returning the header and the body.
+    ///
+    /// First, exactly `header_len` bytes are read as the header, which is then
+    /// passed to `body_len_from_header` to parse the length of the body out of
+    /// it. After that, exactly that many bytes are read as the body. The
+    /// closure is where the length should be validated: the buffer for the
+    /// body is allocated with the returned length before anything is read into
+    /// it, so a length taken from an untrusted peer as-is lets the peer make
+    /// the process allocate as much memory as it likes. An error returned by
+    /// the closure is returned as-is, with the body left unread.
+    ///
+    /// Fails with `ErrorKind::UnexpectedEof` if end of file is reached in the
+    /// middle of the frame. Interrupted reads are retried.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use interprocess::local_socket::LocalSocketStream;
+    /// use std::{convert::TryInto, io};
+    ///
+    /// let mut conn = LocalSocketStream::connect("/tmp/example.sock")?;
+    /// // A 4-byte little-endian length, followed by at most 64 KiB of data.
+    /// let (_, body) = conn.read_framed(4, |header| {
+    ///     let len = u32::from_le_bytes(header.try_into().unwrap()) as usize;
+    ///     if len > 64 * 1024 {
+    ///         return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too big"));
+    ///     }
+    ///     Ok(len)
+    /// })?;
+    /// # let _ = body;
+    /// # Ok(()) }
+    /// ```
+    pub fn read_framed(
+        &mut self,
+        header_len: usize,
+        body_len_from_header: impl FnOnce(&[u8]) -> io::Result<usize>,
+    ) -> io::Result<(Vec<u8>, Vec<u8>)> {
+        crate::read_framed(header_len, body_len_from_header, |buf| self.read(buf))
+    }
+    /// Moves up to `count` bytes read from the stream into the specified file,
+    /// stopping early at end of file. Returns the amount of bytes which were
+    /// written into the file, at its current offset.
+    ///
+    /// This is meant for efficiently capturing large amounts of incoming data,
+    /// e.g. for logging purposes. If the file cannot be written to after data
+    /// has already been read from the stream, that data is lost.
+    ///
+    /// # Platform-specific behavior
+    /// ## Linux and Android
+    /// The data is moved with `splice` without being copied into userspace,
+    /// using a kernel pipe created for the duration of the call as an
+    /// intermediary, since `splice` requires one of its ends to be a pipe. See
+    /// [`UdStream::splice_to_file`] for the details.
+    ///
+    /// ## Other platforms
+    /// The data is copied through a userspace buffer with a read-write loop. On
+    /// Windows, the other side disconnecting is treated as end of file.
+    ///
+    /// [`UdStream::splice_to_file`]: ../os/unix/udsocket/struct.UdStream.html#method.splice_to_file " "
+    pub fn splice_to_file(&mut self, file: &File, count: usize) -> io::Result<usize> {
+        let (inner, cancel) = (&mut self.inner, &self.cancel);
+        cancel.guard(|| inner.splice_to_file(file, count, &mut || cancel.record_activity()))
+    }
+    /// Retrieves the amount of connections which the server currently has open
+    /// or ready to be opened, as a measure of how busy it is. Useful for
+    /// picking the least loaded server out of several which provide the same
+    /// service.
+    ///
+    /// The value is only a snapshot: other clients may connect or disconnect
+    /// at any time. Since a connection is required to perform the query, the
+    /// count includes the connection through which it is made.
+    ///
+    /// # Platform-specific behavior
+    /// ## Windows
+    /// Returns the amount of instances of the named pipe which currently exist,
+    /// including the ones waiting for a client, as reported by
+    /// `GetNamedPipeHandleStateW`.
+    ///
+    /// ## Unix
+    /// There's no equivalent of this for Unix domain sockets, so an
+    /// `Unsupported` error is always returned.
+    pub fn server_instance_count(&self) -> io::Result<u32> {
+        self.inner.server_instance_count()
+    }
+    /// Enables or disables the nonblocking mode for the stream. By default, it
+    /// is disabled.
+    ///
+    /// In nonblocking mode, reading and writing will immediately return with
+    /// the [`WouldBlock`] error in situations when they would normally block
+    /// for an uncontrolled amount of time. The specific situations are:
+    /// - When reading is attempted and there is no new data available;
+    /// - When writing is attempted and the buffer is full due to the other side
+    ///   not yet having read previously sent data.
+    ///
+    /// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock " "
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.inner.set_nonblocking(nonblocking)
+    }
+    /// Returns a token which can be used to cancel all I/O on the stream from
+    /// another thread, e.g. by a supervisor shutting down the connections it
+    /// manages.
+    ///
+    /// Once [`cancel`] is called on the token (or on any of its clones), reads
+    /// and writes which are blocked on the stream return promptly, and so do
+    /// all subsequent ones, with a [`CancelledError`]. Cancellation cannot be
+    /// undone: the stream should be dropped afterwards, since it may have been
+    /// shut down in the process. The token does not keep the stream alive, and
+    /// cancelling after the stream has been dropped does nothing.
+    ///
+    /// [`cancel`]: struct.CancelToken.html#method.cancel " "
+    /// [`CancelledError`]: struct.CancelledError.html " "
+    pub fn cancel_token(&self) -> CancelToken {
+        CancelToken(Arc::clone(&self.cancel.0))
+    }
+    /// Borrows the file descriptor of the stream, without the ability to close
+    /// it, for diagnostics which query the state of the socket with system
+    /// calls such as `getsockopt`.
+    ///
+    /// Unlike [`as_raw_fd`], this ties the file descriptor to the lifetime of
+    /// the stream at compile time: the borrow cannot outlive the stream, so the
+    /// file descriptor cannot be closed, or reused for something else, while
+    /// it's being looked at. The same is available through the [`AsFd`] trait.
+    ///
+    /// [`as_raw_fd`]: https://doc.rust-lang.org/std/os/unix/io/trait.AsRawFd.html#tymethod.as_raw_fd " "
+    /// [`AsFd`]: https://doc.rust-lang.org/std/os/unix/io/trait.AsFd.html " "
+    #[cfg(all(unix, io_safety_stable))]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(unix)))]
+    pub fn as_borrowed_fd(&self) -> std::os::unix::io::BorrowedFd<'_> {
+        std::os::unix::io::AsFd::as_fd(self)
+    }
+    /// Borrows the handle of the stream, without the ability to close it, for
+    /// diagnostics which query the state of the named pipe with system calls
+    /// such as `GetNamedPipeInfo`.
+    ///
+    /// Unlike [`as_raw_handle`], this ties the handle to the lifetime of the
+    /// stream at compile time: the borrow cannot outlive the stream, so the
+    /// handle cannot be closed, or reused for something else, while it's being
+    /// looked at. The same is available through the [`AsHandle`] trait.
+    ///
+    /// [`as_raw_handle`]: https://doc.rust-lang.org/std/os/windows/io/trait.AsRawHandle.html#tymethod.as_raw_handle " "
+    /// [`AsHandle`]: https://doc.rust-lang.org/std/os/windows/io/trait.AsHandle.html " "
+    #[cfg(all(windows, io_safety_stable))]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(windows)))]
+    pub fn as_borrowed_handle(&self) -> std::os::windows::io::BorrowedHandle<'_> {
+        std::os::windows::io::AsHandle::as_handle(self)
+    }
+}
+impl Read for LocalSocketStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        self.cancel.guard(|| inner.read(buf))
+    }
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        self.cancel.guard(|| inner.read_vectored(bufs))
+    }
+}
+impl Write for LocalSocketStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        self.cancel.guard(|| inner.write(buf))
+    }
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        self.cancel.guard(|| inner.write_vectored(bufs))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        let inner = &mut self.inner;
+        self.cancel.guard(|| inner.flush())
+    }
+}
+impl Read for &LocalSocketStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut inner = &self.inner;
+        self.cancel.guard(|| inner.read(buf))
+    }
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let mut inner = &self.inner;
+        self.cancel.guard(|| inner.read_vectored(bufs))
+    }
+}
+impl Write for &LocalSocketStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut inner = &self.inner;
+        self.cancel.guard(|| inner.write(buf))
+    }
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let mut inner = &self.inner;
+        self.cancel.guard(|| inner.write_vectored(bufs))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        let mut inner = &self.inner;
+        self.cancel.guard(|| inner.flush())
+    }
+}
+impl Sealed for LocalSocketStream {
+}
+impl Connection for LocalSocketStream {
+    fn peer_process_id(&self) -> io::Result<u32> {
+        self.inner.peer_pid()
+    }
+    fn shutdown(&self) -> io::Result<()> {
+        self.inner.shutdown()
+    }
+    fn is_connected(&self) -> io::Result<bool> {
+        let inner = &self.inner;
+        self.cancel.guard_inspection(|| Ok(inner.input_state()? != InputState::Disconnected))
+    }
+    fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let inner = &self.inner;
+        self.cancel.guard_inspection(|| inner.peek(buf))
+    }
+}
+impl Debug for LocalSocketStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self.inner, f)
+    }
+}
+impl_as_raw_handle!(LocalSocketStream);
+impl_into_raw_handle!(LocalSocketStream);
+#[cfg(windows)]
+impl std::os::windows::io::FromRawHandle for LocalSocketStream {
+    unsafe fn from_raw_handle(handle: *mut std::ffi::c_void) -> Self {
+        Self::from_impl(unsafe { LocalSocketStreamImpl::from_raw_handle(handle) })
+    }
+}
+#[cfg(unix)]
+impl std::os::unix::io::FromRawFd for LocalSocketStream {
+    unsafe fn from_raw_fd(fd: libc::c_int) -> Self {
+        Self::from_impl(unsafe { LocalSocketStreamImpl::from_raw_fd(fd) })
+    }
+}
+
+/// A handle which cancels all I/O on a [`LocalSocketStream`], created with
+/// [`cancel_token`].
+///
+/// Tokens can be cloned and sent to other threads freely; all clones cancel
+/// the same stream.
+///
+/// [`LocalSocketStream`]: struct.LocalSocketStream.html " "
+/// [`cancel_token`]: struct.LocalSocketStream.html#method.cancel_token " "
+#[derive(Clone, Debug)]
+pub struct CancelToken(Arc<CancelState>);
+impl CancelToken {
+    /// Cancels all I/O on the stream: operations which are blocked on it return
+    /// with a [`CancelledError`], and so do all operations attempted
+    /// afterwards. Does nothing if the stream has already been cancelled or
+    /// dropped.
+    ///
+    /// # Platform-specific behavior
+    /// ## Unix
+    /// The socket is shut down in both directions with `shutdown`, which wakes
+    /// up blocked operations and is also seen by the other side as the
+    /// connection being closed. This is used instead of a self-pipe or an
+    /// `eventfd` polled alongside the socket, which would need every blocking
+    /// read and write to be turned into a `poll` followed by a nonblocking
+    /// call, and an extra file descriptor per stream, to achieve the same
+    /// effect; since a cancelled stream is meant to be dropped, the connection
+    /// being shut down along the way doesn't lose anything.
+    ///
+    /// ## Windows
+    /// Pending operations are cancelled with `CancelIoEx`. An operation which
+    /// is started concurrently with the call, after the stream has checked for
+    /// cancellation but before the system has started the operation, may not
+    /// be affected.
+    ///
+    /// [`CancelledError`]: struct.CancelledError.html " "
+    pub fn cancel(&self) -> io::Result<()> {
+        let target = self.0.target.lock().expect("unexpected lock poisoning");
+        if let Some(target) = &*target {
+            if !self.0.cancelled.swap(true, SeqCst) {
+                target.cancel()?;
+            }
+        }
+        Ok(())
+    }
+    /// Returns `true` if the stream has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(SeqCst)
+    }
+}
+
+#[derive(Debug)]
+struct CancelState {
+    cancelled:     AtomicBool,
+    /// `None` once the stream is dropped. Locked for the duration of the
+    /// cancellation, so that the stream cannot be dropped halfway through.
+    target:        Mutex<Option<CancelTarget>>,
+    /// When an I/O operation on the stream last succeeded, or when the stream
+    /// was created if none has yet. Kept here rather than in the stream so that
+    /// servers can tell idle connections apart from busy ones.
+    last_activity: Mutex<Instant>,
+}
+impl CancelState {
+    fn idle_for(&self) -> Duration {
+        self.last_activity.lock().expect("unexpected lock poisoning").elapsed()
+    }
+}
+
+/// The stream's own reference to the cancellation state.
+struct CancelGuard(Arc<CancelState>);
+impl CancelGuard {
+    fn new(stream: &LocalSocketStreamImpl) -> Self {
+        Self(Arc::new(CancelState {
+            cancelled:     AtomicBool::new(false),
+            target:        Mutex::new(Some(CancelTarget::new(stream))),
+            last_activity: Mutex::new(Instant::now()),
+        }))
+    }
+    /// Performs an I/O operation, replacing its outcome with a cancellation
+    /// error if the stream was cancelled before or during it, and records the
+    /// activity if it succeeds.
+    fn guard<T>(&self, op: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+        let result = self.guard_inspection(op);
+        if result.is_ok() {
+            self.record_activity();
+        }
+        result
+    }
+    /// Like `guard`, but for operations which only look at the state of the
+    /// stream without transferring any data, which don't count as activity:
+    /// otherwise, polling `is_connected` would keep an idle connection from
+    /// ever timing out.
+    fn guard_inspection<T>(&self, op: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+        if self.0.cancelled.load(SeqCst) {
+            return Err(CancelledError.into());
+        }
+        let result = op();
+        if self.0.cancelled.load(SeqCst) {
+            return Err(CancelledError.into());
+        }
+        result
+    }
+    /// Marks the stream as active as of now, for operations which make
+    /// progress in several steps.
+    fn record_activity(&self) {
+        if let Ok(mut last_activity) = self.0.last_activity.lock() {
+            *last_activity = Instant::now();
+        }
+    }
+}
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        if let Ok(mut target) = self.0.target.lock() {
+            *target = None;
+        }
+    }
+}
+
+/// Marker error indicating that [`accept`] was called on a
+/// [`LocalSocketListener`] which has been [closed].
+///
+/// Always emitted with the `ErrorKind::NotConnected` error type.
+///
+/// [`accept`]: struct.LocalSocketListener.html#method.accept " "
+/// [`LocalSocketListener`]: struct.LocalSocketListener.html " "
+/// [closed]: struct.LocalSocketListener.html#method.close " "
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ListenerClosedError;
+impl Display for ListenerClosedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("the listener has been closed")
+    }
+}
+impl Error for ListenerClosedError {
+}
+impl From<ListenerClosedError> for io::Error {
+    fn from(e: ListenerClosedError) -> Self {
+        io::Error::new(io::ErrorKind::NotConnected, e)
+    }
+}
+
+/// Marker error indicating that an operation on a [`LocalSocketStream`] failed
+/// because the stream has been cancelled using a [`CancelToken`].
+///
+/// Always emitted with the `ErrorKind::ConnectionAborted` error type.
+///
+/// [`LocalSocketStream`]: struct.LocalSocketStream.html " "
+/// [`CancelToken`]: struct.CancelToken.html " "
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CancelledError;
+impl Display for CancelledError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("the operation was cancelled")
+    }
+}
+impl Error for CancelledError {
+}
+impl From<CancelledError> for io::Error {
+    fn from(error: CancelledError) -> Self {
+        io::Error::new(io::ErrorKind::ConnectionAborted, error)
+    }
+}
+
+/// Error indicating that the server which [`connect_authenticated`] connected
+/// to is not the one which the client expected.
+///
+/// Always emitted with the `ErrorKind::PermissionDenied` error type.
+///
+/// [`connect_authenticated`]: struct.LocalSocketStream.html#method.connect_authenticated " "
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct UntrustedServerError;
+impl Display for UntrustedServerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("the server failed verification")
+    }
+}
+impl Error for UntrustedServerError {
+}
+impl From<UntrustedServerError> for io::Error {
+    fn from(error: UntrustedServerError) -> Self {
+        io::Error::new(io::ErrorKind::PermissionDenied, error)
+    }
+}
+
+// Servers routinely move accepted streams into worker threads and share the
+// listener between accepting threads, so make sure that doesn't silently
+// become impossible on any of the platforms.
+const _: fn() = || {
+    fn assert_send_static<T: Send + 'static>() {
+    }
+    fn assert_send_sync<T: Send + Sync>() {
+    }
+    assert_send_static::<LocalSocketStream>();
+    assert_send_sync::<CancelToken>();
+    assert_send_sync::<LocalSocketListener>();
+};
+
+/// A name for a local socket.
+///
+/// Due to vast differences between platforms in terms of how local sockets are
+/// named, there needs to be a way to store and process those in a unified way
+/// while also retaining platform-specific pecularities. `LocalSocketName` aims
+/// to bridge the gap between portability and platform-specific correctness.
+///
+/// # Creation
+/// A separate trait is used to create names from basic strings:
+/// [`ToLocalSocketName`]. Aside from being conveniently implemented on every
+/// single string type in the standard library, it also provides some special
+/// processing. Please read its documentation if you haven't already — the rest
+/// of this page assumes you did.
+///
+/// # Validity
+/// As mentioned in the [module-level documentation], not all platforms support
+/// all types of local socket names. A name pointing to a filesystem location is
+/// only supported on Unix-like systems, and names pointing to an abstract
+/// namespace reserved specifically for local sockets are only available on
+/// Linux and Windows. Due to the diversity of those differences,
+/// `LocalSocketName` does not provide any forced validation by itself — the
+/// [`is_supported`] and [`is_always_supported`] checks are not enforced to
+/// succeed. Instead, they are intended as helpers for the process of user input
+/// validation, if any local socket names are ever read from environment
+/// variables, configuration files or other methods of user input.
+///
+/// If an invalid local socket name is used to create a local socket or connect
+/// to it, the creation/connection method will fail.
+///
+/// [`to_local_socket_name`]: trait.ToLocalSocketName.html " "
+/// [module-level documentation]: index.html " "
+/// [`is_supported`]: #method.is_supported " "
+/// [`is_always_supported`]: #method.is_always_supported " "
+pub struct LocalSocketName<'a> {
+    inner:      Cow<'a, OsStr>,
+    namespaced: bool,
+}
+impl<'a> LocalSocketName<'a> {
+    /// Returns `true` if the type of the name is supported by the OS, `false`
+    /// otherwise.
+    ///
+    /// The check is performed at runtime. For a conservative compile-time
+    /// check, see [`is_always_supported`].
+    ///
+    /// [`is_always_supported`]: #method.is_always_supported " "
+    pub fn is_supported(&self) -> bool {
+        (NameTypeSupport::query().namespace_supported() && self.is_namespaced())
+            || (NameTypeSupport::query().paths_supported() && !self.is_path())
+    }
+    /// Returns `true` if the type of the name is supported by the OS, `false`
+    /// otherwise.
+    ///
+    /// The check is performed at compile-time. For a check which might return a
+    /// more permissive result on certain platforms by checking for support at
+    /// runtime, see [`is_supported`].
+    ///
+    /// [`is_supported`]: #method.is_supported " "
+    pub fn is_always_supported(&self) -> bool {
+        (NameTypeSupport::ALWAYS_AVAILABLE.namespace_supported() && self.is_namespaced())
+            || (NameTypeSupport::ALWAYS_AVAILABLE.paths_supported() && self.is_path())
+    }
+    /// Returns `true` if the value is a namespaced name, `false` otherwise.
+    pub const fn is_namespaced(&self) -> bool {
+        self.namespaced
+    }
+    /// Returns `true` if the value is a filesystem path, `false` otherwise.
+    pub const fn is_path(&self) -> bool {
+        !self.namespaced
+    }
+    /// Returns the name as an `OsStr`. The returned value does not retain the
+    /// type of the name (whether it was a filesystem path or a namespaced
+    /// name).
+    ///
+    /// If you need the value as an owned `OsString` instead, see
+    /// [`into_inner`].
+    ///
+    /// [`into_inner`]: #method.into_inner " "
+    pub fn inner(&'a self) -> &'a OsStr {
+        &self.inner
+    }
+    /// Returns the name as an `OsString`. The returned value does not retain
+    /// the type of the name (whether it was a filesystem path or a namespaced
+    /// name).
+    ///
+    /// If you need the value as a borrowed `OsStr` instead, see [`inner`].
+    ///
+    /// [`inner`]: #method.inner " "
+    pub fn into_inner(self) -> OsString {
+        self.inner.into_owned()
+    }
+    /// Returns the name as a *borrowed* `Cow<'_, OsStr>`. The returned value
+    /// does not retain the type of the name (whether it was a filesystem path
+    /// or a namespaced name).
+    ///
+    /// If you need the value as a borrowed `OsStr`, see [`inner`]; if you need
+    /// the value as an owned `OsString`, see [`into_inner`].  If you need to
+    /// take ownership of the `Cow`, see `into_inner_cow`.
+    ///
+    /// [`inner`]: #method.inner " "
+    /// [`into_inner`]: #method.into_inner " "
+    /// [`into_inner_cow`]: #method.into_inner_cow " "
+    pub const fn inner_cow(&'a self) -> &'a Cow<'a, OsStr> {
+        &self.inner
+    }
+    /// Returns the name as a `Cow<'_, OsStr>`. The returned value does not
+    /// retain the type of the name (whether it was a filesystem path or a
+    /// namespaced name).
+    ///
+    /// If you need the value as a borrowed `OsStr`, see [`inner`]; if you need
+    /// the value as an owned `OsString`, see [`into_inner`]. If you don't need
+    /// to take ownership of the `Cow`, see `inner_cow`.
+    ///
+    /// [`inner`]: #method.inner " "
+    /// [`into_inner`]: #method.into_inner " "
+    /// [`inner_cow`]: #method.inner_cow " "
+    pub fn into_inner_cow(self) -> Cow<'a, OsStr> {
+        self.inner
+    }
+    /// Checks whether a server can be started with this name, telling apart a
+    /// name which is taken by a server that's running from one which is only
+    /// blocked by a socket file left behind by a server that isn't.
+    ///
+    /// Meant for servers which want to give a friendly error at startup, such
+    /// as "another instance is already running", rather than fail to bind or
+    /// silently remove the file of a server which is still alive.
+    ///
+    /// # Probing
+    /// The check is performed by attempting to connect, the same way a client
+    /// would:
+    /// - **On Unix**, a connection is attempted without waiting for room in the
+    ///   listen backlog. If it succeeds or the server is too busy to take it,
+    ///   the name is in use by a live server; if the socket file doesn't exist,
+    ///   the name is free; if it exists but nobody is listening on it, it's a
+    ///   stale file, which has to be removed before binding. Namespaced names
+    ///   are never stale, since they disappear along with the server.
+    /// - **On Windows**, `WaitNamedPipe` is called with a minimal timeout. A
+    ///   named pipe stops existing once its last instance is closed, so the
+    ///   name is either free or in use by a live server, even if all of the
+    ///   server's instances are busy; it's never stale.
+    ///
+    /// A successful probe connection is closed right away, so a live server
+    /// sees a client which connects and disconnects without sending anything.
+    ///
+    /// The result is a snapshot and **inherently racy**: a server may start or
+    /// exit right after the check, so binding may still fail after `Free` is
+    /// returned, and a file reported as stale may belong to a server which has
+    /// started in the meantime by the time it's removed. The check is meant for
+    /// producing good error messages, not for mutual exclusion.
+    pub fn check_availability(&self) -> io::Result<Availability> {
+        check_availability_impl(self)
+    }
+    pub(crate) const fn from_raw_parts(inner: Cow<'a, OsStr>, namespaced: bool) -> Self {
+        Self { inner, namespaced }
+    }
+}
+
+/// Whether a server can be started with a local socket name, as reported by
+/// [`LocalSocketName::check_availability`].
+///
+/// [`LocalSocketName::check_availability`]: struct.LocalSocketName.html#method.check_availability " "
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Availability {
+    /// Nothing is using the name.
+    Free,
+    /// A server which is running is using the name.
+    InUseByLiveServer,
+    /// The path is taken by a socket file which no server is listening on,
+    /// usually left behind by a server which didn't exit cleanly. Only ever
+    /// reported on Unix, for names which are filesystem paths.
+    StaleFile,
+}
+
+/// Represents which kinds of identifiers can be used for a local socket's name
+/// on the current platform.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NameTypeSupport {
+    /// Only filesystem paths can be used.
+    ///
+    /// This is true for all Unix/POSIX and Unix-like systems other than Linux.
+    OnlyPaths,
+    /// Only names in an dedicated namespace can be used.
+    ///
+    /// This is true only for Windows.
+    OnlyNamespaced,
+    /// Both of the above options are available.
+    ///
+    /// This is true only for Linux.
+    Both,
+}
+impl NameTypeSupport {
+    /// The types of local socket names supported on the current platform
+    /// regardless of the environment and OS version.
+    ///
+    /// On most platforms, the value is known at compile time, i.e. the support
+    /// for paths wasn't introduced in a specific version of the OS or isn't
+    /// known to be supported at all. **Currently, this includes all supported
+    /// OSes.** If support is added for an OS which added this functionality in
+    /// a specific version, this constant will be the most restrictive value for
+    /// that platform, with [`query`] possibly returning the actual value
+    /// according to the current version of the OS.
+    ///
+    /// Simply put, you should probably just use this value for consistency
+    /// across platforms, unless you really need a specific name type to be
+    /// supported.
+    ///
+    /// [`query`]: #method.query " "
+    pub const ALWAYS_AVAILABLE: Self = NAME_TYPE_ALWAYS_SUPPORTED_REAL;
+    /// Returns the types of local socket names supported on the current
+    /// platform with the current environment.
+    ///
+    /// On most platforms, the value is known at compile time, i.e. the support
+    /// for one of the types wasn't introduced in an update to the OS or isn't
+    /// known to be supported at all. **Currently, this includes all supported
+    /// OSes.** For compatibility with OSes which might add the functionality in
+    /// the future starting with a specific version, this function isn't a
+    /// `const fn` — see [`ALWAYS_AVAILABLE`] if you need a constant expression.
+    ///
+    /// [`ALWAYS_AVAILABLE`]: #associatedconstant.ALWAYS_AVAILABLE " "
+    pub fn query() -> Self {
+        name_type_support_query_impl()
+    }
+
+    /// Returns `true` if, according to `self`, filesystem-based local sockets
+    /// are supported; `false` otherwise.
+    pub const fn paths_supported(self) -> bool {
+        matches!(self, Self::OnlyPaths | Self::Both)
+    }
+    /// Returns `true` if, according to `self`, namespaced local socket names
+    /// are supported; `false` otherwise.
+    pub const fn namespace_supported(self) -> bool {
+        matches!(self, Self::OnlyNamespaced | Self::Both)
+    }
+}
+/// Types which can be converted to a local socket name.
+///
+/// The difference between this trait and [`TryInto`]`<`[`LocalSocketName`]`>`
+/// is that the latter does not constrain the error type to be [`io::Error`] and
+/// thus is not compatible with many types from the standard library which are
+/// widely expected to be convertible to Unix domain socket paths. Additionally,
+/// this makes the special syntax for namespaced sockets possible (see below).
+///
+/// ## `@` syntax for namespaced paths
+/// As mentioned in the [`LocalSocketName` documentation][`LocalSocketName`],
+/// there are two types of which local socket names can be: filesystem paths and
+/// namespaced names. Those are isolated from each other — there's no portable
+/// way to represent one using another, though certain OSes might provide ways
+/// to do so — Windows does, for example. To be able to represent both in a
+/// platform-independent fashion, a special syntax was implemented in
+/// implementations of this trait on types from the standard library: "@
+/// syntax".
+///
+/// The feature, in its core, is extremely simple: if the first character in a
+/// string is the @ character, the value of the string is interpreted and stored
+/// as a namespaced name (otherwise, it's treated as a filesystem path); the @
+/// character is then removed from the string (by taking a subslice which
+/// dosen't include it if a string slice is being used; for owned strings, it's
+/// simply removed from the string by shifting the entire string towards the
+/// beginning). **[`Path`] and [`PathBuf`] are not affected at all — those have
+/// explicit path semantics and therefore cannot logically represent namespaced
+/// names.**
+///
+/// This feature is extremely useful both when using hardcoded literals and
+/// accepting user input for the path, but sometimes you might want to prevent
+/// this behavior. In such a case, you have the following possible approaches:
+/// - If the string is a [`OsStr`]/[`OsString`], it can be cheaply converted to
+///   a [`Path`]/[`PathBuf`], which do not support the @ syntax
+/// - If the string is a [`str`]/[`String`], it can be cheaply converted to
+///   [`OsStr`]/[`OsString`]; then the above method can be applied
+/// - If the string is a [`CStr`]/[`CString`], it can be converted to
+///   [`str`]/[`String`] using the following code:
+/// ```
+/// # use std::{
+/// #     str::Utf8Error,
+/// #     ffi::{CStr, CString},
+/// # };
+/// fn cstr_to_str(val: &CStr) -> Result<&str, Utf8Error> {
+///     std::str::from_utf8(val.to_bytes_with_nul())
+/// }
+/// fn cstring_to_string(val: CString) -> String {
+///     String::from_utf8_lossy(&val.into_bytes_with_nul()).into()
+/// }
+/// ```
+/// Then, the method for [`str`]/[`String`] can be applied.
+///
+/// None of the above conversions perform memory allocations — the only
+/// expensive one is [`CStr`]/[`CString`] which performs a check for valid
+/// UTF-8.
+///
+/// [`LocalSocketName`]: struct.LocalSocketName.html " "
+/// [`TryInto`]: https://doc.rust-lang.org/std/convert/trait.TryInto.html " "
+/// [`str`]: https://doc.rust-lang.org/std/primitive.str.html " "
+/// [`String`]: https://doc.rust-lang.org/std/string/struct.String.html " "
+/// [`OsStr`]: https://doc.rust-lang.org/std/ffi/struct.OsStr.html " "
+/// [`OsString`]: https://doc.rust-lang.org/std/ffi/struct.OsString.html " "
+/// [`CStr`]: https://doc.rust-lang.org/std/ffi/struct.CStr.html " "
+/// [`CString`]: https://doc.rust-lang.org/std/ffi/struct.CString.html " "
+/// [`Path`]: https://doc.rust-lang.org/std/path/struct.Path.html " "
+/// [`PathBuf`]: https://doc.rust-lang.org/std/path/struct.PathBuf.html " "
+pub trait ToLocalSocketName<'a> {
+    /// Performs the conversion to a local socket name.
+    #[allow(clippy::wrong_self_convention)] // shut the fuck up
+    fn to_local_socket_name(self) -> io::Result<LocalSocketName<'a>>;
+}
+// TODO document inpls for symmetry with ud-sockets
+impl<'a> ToLocalSocketName<'a> for LocalSocketName<'a> {
+    fn to_local_socket_name(self) -> io::Result<LocalSocketName<'a>> {
+        Ok(self)
+    }
+}
+impl<'a> ToLocalSocketName<'a> for &'a Path {
+    fn to_local_socket_name(self) -> io::Result<LocalSocketName<'a>> {
+        Ok(LocalSocketName::from_raw_parts(
+            Cow::Borrowed(self.as_os_str()),
+            false,
+        ))
+    }
+}
+impl ToLocalSocketName<'static> for PathBuf {
+    fn to_local_socket_name(self) -> io::Result<LocalSocketName<'static>> {
+        Ok(LocalSocketName::from_raw_parts(
+            Cow::Owned(self.into_os_string()),
+            false,
+        ))
+    }
+}
+impl<'a> ToLocalSocketName<'a> for &'a OsStr {
+    fn to_local_socket_name(self) -> io::Result<LocalSocketName<'a>> {
+        Ok(to_local_socket_name_osstr(self))
+    }
+}
+impl ToLocalSocketName<'static> for OsString {
+    fn to_local_socket_name(self) -> io::Result<LocalSocketName<'static>> {
+        Ok(to_local_socket_name_osstring(self))
+    }
+}
+impl<'a> ToLocalSocketName<'a> for &'a str {
+    fn to_local_socket_name(self) -> io::Result<LocalSocketName<'a>> {
+        OsStr::new(self).to_local_socket_name()
+    }
+}
+impl ToLocalSocketName<'static> for String {
+    fn to_local_socket_name(self) -> io::Result<LocalSocketName<'static>> {
+        // OsString docs misleadingly state that a conversion from String requires
+        // reallocating and copying, but, according to the std sources, that is
+        // not true on any platforms.
+        OsString::from(self).to_local_socket_name()
+    }
+}
+impl<'a> ToLocalSocketName<'a> for &'a CStr {
+    fn to_local_socket_name(self) -> io::Result<LocalSocketName<'a>> {
+        str::from_utf8(self.to_bytes_with_nul())
+            .map(|x| to_local_socket_name_osstr(OsStr::new(x)))
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+impl ToLocalSocketName<'static> for CString {
+    fn to_local_socket_name(self) -> io::Result<LocalSocketName<'static>> {
+        String::from_utf8(self.into_bytes_with_nul())
+            .map(|x| to_local_socket_name_osstring(OsString::from(x)))
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::local_socket::{LocalSocketListener, LocalSocketStream};
+    static SOCKET_NAME: &str = "/tmp/interprocess_local_soocket_test_basic.sock";
+    #[test]
+    fn basic() {
+        use std::{
+            fs::remove_file,
+            io::{self, prelude::*, BufReader},
+            sync::{Arc, Barrier},
+        };
+
+        // We're using a barrier here to avoid the client attempting to connect
+        // before the server creates the socket
+        let barrier = Arc::new(Barrier::new(2));
+        let server_barrier = Arc::clone(&barrier);
+
+        std::thread::spawn(move || {
+            fn handle_error(connection: io::Result<LocalSocketStream>) -> LocalSocketStream {
+                match connection {
+                    Ok(val) => val,
+                    Err(error) => {
+                        panic!("Incoming connection failed: {}", error);
+                    },
+                }
+            }
+
+            let _ = remove_file(SOCKET_NAME);
+            let listener = LocalSocketListener::bind(SOCKET_NAME).unwrap();
+            server_barrier.wait();
+
+            let mut conn = handle_error(listener.accept());
+            println!("Incoming connection!");
+            conn.write_all(b"Hello from server!\n").unwrap();
+            // Add buffering to the connection to read a line.
+            let mut conn = BufReader::new(conn);
+            let mut buffer = String::new();
+            conn.read_line(&mut buffer).unwrap();
+            println!("Client answered: {}", buffer);
+        });
+
+        barrier.wait();
+
+        let mut conn = LocalSocketStream::connect(SOCKET_NAME).unwrap();
+        conn.write_all(b"Hello from client!\n").unwrap();
+        let mut conn = BufReader::new(conn);
+        let mut buffer = String::new();
+        conn.read_line(&mut buffer).unwrap();
+        println!("Server answered: {}", buffer);
+    }
+
+    /// Returns a socket name in the temporary directory which no other test
+    /// uses, removing the socket file left behind under it by a previous run,
+    /// if any.
+    fn unique_name(test: &str) -> String {
+        let name = format!("interprocess_local_socket_test_{}.sock", test);
+        #[cfg(unix)]
+        {
+            let path = std::env::temp_dir().join(name);
+            let _ = std::fs::remove_file(&path);
+            path.into_os_string().into_string().unwrap()
+        }
+        // Pipe names live in their own namespace rather than in a directory.
+        #[cfg(windows)]
+        {
+            name
+        }
+    }
+    /// Binds a listener to a unique name, returning the name and the listener.
+    fn listen(test: &str) -> (String, LocalSocketListener) {
+        let name = unique_name(test);
+        let listener = LocalSocketListener::bind(&*name).unwrap();
+        (name, listener)
+    }
+    /// Binds a listener to a unique name and connects to it, returning the
+    /// listener, the client and the server end of the connection.
+    fn connected_pair(test: &str) -> (LocalSocketListener, LocalSocketStream, LocalSocketStream) {
+        let (name, listener) = listen(test);
+        let client = LocalSocketStream::connect(&*name).unwrap();
+        let server = listener.accept().unwrap();
+        (listener, client, server)
+    }
+
+    #[test]
+    fn cancel_token() {
+        use super::CancelledError;
+        use std::{io::prelude::*, thread, time::Duration};
+
+        // The server end is kept alive so that the read blocks instead of
+        // reaching end of file.
+        let (_listener, mut client, _server_conn) = connected_pair("cancel");
+
+        let token = client.cancel_token();
+        let reader = thread::spawn(move || {
+            let error = client.read(&mut [0; 16]).unwrap_err();
+            assert!(error.get_ref().unwrap().is::<CancelledError>());
+            // Everything fails from now on, not just the interrupted read.
+            let error = client.write(b"too late").unwrap_err();
+            assert!(error.get_ref().unwrap().is::<CancelledError>());
+        });
+        // Give the reader time to actually block.
+        thread::sleep(Duration::from_millis(100));
+        assert!(!token.is_cancelled());
+        token.cancel().unwrap();
+        assert!(token.is_cancelled());
+        reader.join().unwrap();
+        // The stream is gone, so nothing happens.
+        token.cancel().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn inheritable_fd() {
+        use std::io::prelude::*;
+
+        let (name, listener) = listen("inherit");
+        let fd = listener.into_inheritable_fd().unwrap();
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+        assert_eq!(flags & libc::FD_CLOEXEC, 0);
+        // Connecting in the middle of the handoff works.
+        let mut client = LocalSocketStream::connect(&*name).unwrap();
+        let listener = unsafe { LocalSocketListener::from_inherited_fd(fd) }.unwrap();
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+        assert_ne!(flags & libc::FD_CLOEXEC, 0);
+        let mut conn = listener.accept().unwrap();
+        client.write_all(b"still here").unwrap();
+        let mut buf = [0; 10];
+        conn.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"still here");
+    }
+
+    #[cfg(all(unix, io_safety_stable))]
+    #[test]
+    fn borrowed_fd() {
+        use std::{
+            mem::size_of_val,
+            os::unix::io::{AsRawFd, BorrowedFd},
+        };
+
+        fn socket_type(fd: BorrowedFd<'_>) -> libc::c_int {
+            let mut socket_type: libc::c_int = 0;
+            let mut len = size_of_val(&socket_type) as libc::socklen_t;
+            let success = unsafe {
+                libc::getsockopt(
+                    fd.as_raw_fd(),
+                    libc::SOL_SOCKET,
+                    libc::SO_TYPE,
+                    &mut socket_type as *mut _ as *mut _,
+                    &mut len as *mut _,
+                )
+            } != -1;
+            assert!(success);
+            socket_type
+        }
+
+        let (_listener, conn, _server_conn) = connected_pair("borrow");
+        let fd = conn.as_borrowed_fd();
+        assert_eq!(fd.as_raw_fd(), conn.as_raw_fd());
+        assert_eq!(socket_type(fd), libc::SOCK_STREAM);
+    }
+
+    #[test]
+    fn thread_pool() {
+        use std::{
+            io::{prelude::*, BufReader},
+            sync::{mpsc, Arc, Mutex},
+            thread,
+        };
+        const WORKERS: usize = 3;
+        const CLIENTS: usize = 8;
+
+        let (name, listener) = listen("pool");
+
+        let (sender, receiver) = mpsc::channel::<LocalSocketStream>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..WORKERS)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                thread::spawn(move || loop {
+                    let conn = match receiver.lock().unwrap().recv() {
+                        Ok(conn) => conn,
+                        Err(_) => break,
+                    };
+                    let mut conn = BufReader::new(conn);
+                    let mut buffer = String::new();
+                    conn.read_line(&mut buffer).unwrap();
+                    conn.get_mut().write_all(buffer.to_uppercase().as_bytes()).unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        let acceptor = thread::spawn(move || {
+            for conn in listener.incoming().take(CLIENTS) {
+                sender.send(conn.unwrap()).unwrap();
+            }
+        });
+
+        let clients = (0..CLIENTS)
+            .map(|i| {
+                let name = name.clone();
+                thread::spawn(move || {
+                    let mut conn = LocalSocketStream::connect(&*name).unwrap();
+                    writeln!(conn, "client number {}", i).unwrap();
+                    let mut conn = BufReader::new(conn);
+                    let mut buffer = String::new();
+                    conn.read_line(&mut buffer).unwrap();
+                    assert_eq!(buffer, format!("CLIENT NUMBER {}\n", i));
+                })
+            })
+            .collect::<Vec<_>>();
+        for client in clients {
+            client.join().unwrap();
+        }
+        acceptor.join().unwrap();
+        // The acceptor dropped the sender when it was done, which shuts the workers
+        // down.
+        for worker in workers {
+            worker.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn server_shutdown_and_join() {
+        use super::spawn_server;
+        use std::{
+            io::{prelude::*, BufReader},
+            time::Duration,
+        };
+
+        let (name, listener) = listen("server");
+        let server = spawn_server(listener, |conn| {
+            let mut conn = BufReader::new(conn);
+            let mut line = String::new();
+            if conn.read_line(&mut line).is_ok() {
+                let _ = conn.get_mut().write_all(line.as_bytes());
+            }
+        })
+        .unwrap();
+
+        // Never sends anything, so its handler can only be stopped by cancellation.
+        let mut stuck = LocalSocketStream::connect(&*name).unwrap();
+        for _ in 0..2 {
+            let mut conn = LocalSocketStream::connect(&*name).unwrap();
+            conn.write_all(b"echo\n").unwrap();
+            let mut reply = String::new();
+            BufReader::new(conn).read_line(&mut reply).unwrap();
+            assert_eq!(reply, "echo\n");
+        }
+        assert_eq!(server.shutdown_and_join(Duration::from_millis(200)), 1);
+        // The cancellation has closed the connection.
+        assert_eq!(stuck.read(&mut [0; 16]).unwrap(), 0);
+        // Nobody is accepting anymore.
+        assert!(LocalSocketStream::connect_immediate(&*name).is_err());
+    }
+
+    #[test]
+    fn server_idle_timeout() {
+        use super::{CancelledError, ServerOptions};
+        use std::{io::prelude::*, sync::mpsc, thread, time::Duration};
+
+        let (name, listener) = listen("idle");
+        let (results_sender, results) = mpsc::channel();
+        let results_sender = std::sync::Mutex::new(results_sender);
+        let server = ServerOptions::new()
+            .idle_timeout(Duration::from_millis(100))
+            .spawn(listener, move |mut conn| {
+                let mut bytes_read = 0;
+                let cancelled = loop {
+                    match conn.read(&mut [0; 16]) {
+                        Ok(0) => break false,
+                        Ok(n) => bytes_read += n,
+                        Err(e) => break matches!(e.get_ref(), Some(e) if e.is::<CancelledError>()),
+                    }
+                };
+                results_sender.lock().unwrap().send((bytes_read, cancelled)).unwrap();
+            })
+            .unwrap();
+
+        // Connects and then stalls.
+        let mut stalled = LocalSocketStream::connect(&*name).unwrap();
+        // Sends something more often than the timeout, for longer than the timeout.
+        let mut chatty = LocalSocketStream::connect(&*name).unwrap();
+        for _ in 0..10 {
+            chatty.write_all(b"x").unwrap();
+            thread::sleep(Duration::from_millis(20));
+        }
+        drop(chatty);
+        let mut results = results.iter().take(2).collect::<Vec<_>>();
+        results.sort();
+        assert_eq!(results, [(0, true), (10, false)]);
+        // The stalled client sees the connection being closed.
+        assert_eq!(stalled.read(&mut [0; 16]).unwrap(), 0);
+        server.join_all();
+    }
+
+    #[test]
+    fn server_idle_timeout_streaming() {
+        use super::ServerOptions;
+        use std::{
+            env,
+            fs::{remove_file, OpenOptions},
+            io::prelude::*,
+            process,
+            sync::mpsc,
+            thread,
+            time::Duration,
+        };
+
+        let scratch_path = env::temp_dir().join(format!(
+            "interprocess_local_socket_test_idle_streaming_{}",
+            process::id()
+        ));
+        let (name, listener) = listen("idle_streaming");
+        let (results_sender, results) = mpsc::channel();
+        let results_sender = std::sync::Mutex::new(results_sender);
+        let handler_scratch_path = scratch_path.clone();
+        let server = ServerOptions::new()
+            .idle_timeout(Duration::from_millis(100))
+            .spawn(listener, move |mut conn| {
+                // The first byte picks the operation, which then runs until the
+                // client disconnects, much later than the idle timeout.
+                let mut mode = [0];
+                conn.read_exact(&mut mode).unwrap();
+                let result = match &mode {
+                    b"c" => conn.read_chunks(16, |_| Ok(())).map(|total| total as usize),
+                    _ => {
+                        let file = OpenOptions::new()
+                            .write(true)
+                            .create(true)
+                            .truncate(true)
+                            .open(&handler_scratch_path)
+                            .unwrap();
+                        conn.splice_to_file(&file, usize::MAX)
+                    },
+                };
+                let result = result.map_err(|e| e.to_string());
+                results_sender.lock().unwrap().send((mode[0], result)).unwrap();
+            })
+            .unwrap();
+
+        let clients = [b'c', b's']
+            .iter()
+            .map(|&mode| {
+                let name = name.clone();
+                thread::spawn(move || {
+                    let mut conn = LocalSocketStream::connect(&*name).unwrap();
+                    conn.write_all(&[mode]).unwrap();
+                    for _ in 0..15 {
+                        thread::sleep(Duration::from_millis(20));
+                        conn.write_all(b"x").unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for client in clients {
+            client.join().unwrap();
+        }
+        let mut results = results.iter().take(2).collect::<Vec<_>>();
+        results.sort();
+        assert_eq!(results, [(b'c', Ok(15)), (b's', Ok(15))]);
+        server.join_all();
+        let _ = remove_file(scratch_path);
+    }
+
+    #[test]
+    fn server_connection_registry() {
+        use super::{spawn_server, CancelledError};
+        use std::{io::prelude::*, process, sync::mpsc, thread, time::Duration};
+
+        let (name, listener) = listen("registry");
+        let (cancelled_sender, cancelled) = mpsc::channel();
+        let cancelled_sender = std::sync::Mutex::new(cancelled_sender);
+        let server = spawn_server(listener, move |mut conn| {
+            let error = conn.read(&mut [0; 16]).err();
+            let was_cancelled = matches!(
+                error.as_ref().and_then(|e| e.get_ref()),
+                Some(e) if e.is::<CancelledError>()
+            );
+            cancelled_sender.lock().unwrap().send(was_cancelled).unwrap();
+        })
+        .unwrap();
+
+        let mut kicked = LocalSocketStream::connect(&*name).unwrap();
+        let kept = LocalSocketStream::connect(&*name).unwrap();
+        let mut connections = server.connections();
+        while connections.len() < 2 {
+            thread::sleep(Duration::from_millis(5));
+            connections = server.connections();
+        }
+        assert_eq!(connections.len(), 2);
+        assert!(connections[0].id < connections[1].id);
+        for info in &connections {
+            if let Some(pid) = info.peer_pid {
+                assert_eq!(pid, process::id());
+            }
+        }
+
+        let kicked_id = connections[0].id;
+        assert!(server.close_connection(kicked_id).unwrap());
+        assert!(cancelled.recv().unwrap());
+        assert_eq!(kicked.read(&mut [0; 16]).unwrap(), 0);
+        while server.connections().len() > 1 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(server.connections()[0].id, connections[1].id);
+        // Gone for good, and the id doesn't refer to anything else.
+        assert!(!server.close_connection(kicked_id).unwrap());
+
+        drop(kept);
+        assert!(!cancelled.recv().unwrap());
+        server.join_all();
+    }
+
+    #[test]
+    fn check_availability() {
+        use super::{Availability, ToLocalSocketName};
+
+        let socket_name = unique_name("availability");
+        let name = || socket_name.as_str().to_local_socket_name().unwrap();
+        assert_eq!(name().check_availability().unwrap(), Availability::Free);
+        let listener = LocalSocketListener::bind(&*socket_name).unwrap();
+        assert_eq!(
+            name().check_availability().unwrap(),
+            Availability::InUseByLiveServer
+        );
+        drop(listener);
+        // Unix domain sockets leave their file behind.
+        #[cfg(unix)]
+        assert_eq!(
+            name().check_availability().unwrap(),
+            Availability::StaleFile
+        );
+        #[cfg(windows)]
+        assert_eq!(name().check_availability().unwrap(), Availability::Free);
+    }
+
+    #[cfg(uds_peercred)]
+    #[test]
+    fn connect_authenticated() {
+        use super::UntrustedServerError;
+        use std::{io, process};
+
+        let (name, _listener) = listen("auth");
+        LocalSocketStream::connect_authenticated(&*name, |conn| {
+            Ok(conn.peer_pid()? == process::id())
+        })
+        .unwrap();
+        let error = LocalSocketStream::connect_authenticated(&*name, |conn| {
+            Ok(conn.peer_pid()? != process::id())
+        })
+        .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert!(error.get_ref().unwrap().is::<UntrustedServerError>());
+    }
+
+    #[test]
+    fn acceptor() {
+        use super::spawn_acceptor;
+        use std::{io::prelude::*, thread, time::Duration};
+
+        let (name, listener) = listen("acceptor");
+        let streams = spawn_acceptor(listener, 1).unwrap();
+        let mut clients = Vec::new();
+        for i in 0..3 {
+            let mut client = LocalSocketStream::connect(&*name).unwrap();
+            client.write_all(&[i]).unwrap();
+            clients.push(client);
+        }
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            let mut conn = streams.recv().unwrap().unwrap();
+            let mut buf = [0];
+            conn.read_exact(&mut buf).unwrap();
+            received.push(buf[0]);
+        }
+        assert_eq!(received, [0, 1, 2]);
+
+        drop(streams);
+        // The listener is closed once the accepting thread notices.
+        let mut refused = false;
+        for _ in 0..100 {
+            if LocalSocketStream::connect(&*name).is_err() {
+                refused = true;
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(refused);
+    }
+
+    #[test]
+    fn connected_at() {
+        use std::{
+            thread,
+            time::{Duration, Instant, SystemTime},
+        };
+
+        let before = Instant::now();
+        let before_system_time = SystemTime::now();
+        let (_listener, client, server) = connected_pair("connected_at");
+        assert!(client.connected_at() >= before);
+        assert!(server.connected_at() >= client.connected_at());
+        assert!(client.connected_at_system_time() >= before_system_time);
+        thread::sleep(Duration::from_millis(20));
+        assert!(client.age() >= Duration::from_millis(20));
+        assert!(client.age() <= before.elapsed());
+    }
+
+    #[test]
+    fn closed_listener() {
+        use super::ListenerClosedError;
+        use std::io;
+
+        let (name, listener) = listen("close");
+        let _client = LocalSocketStream::connect(&*name).unwrap();
+        assert!(!listener.is_closed());
+        listener.close();
+        assert!(listener.is_closed());
+        // Even with a client waiting, nothing is accepted anymore.
+        let error = listener.accept().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotConnected);
+        assert!(error.get_ref().unwrap().is::<ListenerClosedError>());
+        assert!(listener.incoming().next().is_none());
+        listener.close();
+        assert!(listener.accept().is_err());
+    }
+
+    // Other systems may ignore the shutdown which wakes up the accept.
+    #[cfg(any(windows, target_os = "linux"))]
+    #[test]
+    fn close_wakes_accept() {
+        use super::ListenerClosedError;
+        use std::{sync::Arc, thread, time::Duration};
+
+        let (_name, listener) = listen("close-wakes");
+        let listener = Arc::new(listener);
+        let accepting = {
+            let listener = Arc::clone(&listener);
+            thread::spawn(move || listener.accept().map(drop))
+        };
+        thread::sleep(Duration::from_millis(50));
+        listener.close();
+        let error = accepting.join().unwrap().unwrap_err();
+        assert!(error.get_ref().unwrap().is::<ListenerClosedError>());
+    }
+
+    #[test]
+    fn connection_pool() {
+        use super::{spawn_server, ConnectionPool, ReturnPolicy};
+        use std::{io::prelude::*, time::Duration};
+
+        // Asked for `k`, the server replies with `k` bytes of value `k`, and
+        // closes the connection when asked for zero.
+        fn ask(conn: &mut LocalSocketStream, k: u8, read: usize) -> Vec<u8> {
+            conn.write_all(&[k]).unwrap();
+            let mut reply = vec![0; read];
+            conn.read_exact(&mut reply).unwrap();
+            reply
+        }
+
+        let (name, listener) = listen("conn_pool");
+        let server = spawn_server(listener, |mut conn| {
+            let mut k = [0];
+            while conn.read_exact(&mut k).is_ok() && k[0] != 0 {
+                if conn.write_all(&vec![k[0]; k[0].into()]).is_err() {
+                    return;
+                }
+            }
+        })
+        .unwrap();
+
+        let pool = ConnectionPool::new(&*name, 2, ReturnPolicy::ValidateAndReuse).unwrap();
+        let mut conn = pool.get().unwrap();
+        assert_eq!(ask(&mut conn, 1, 1), [1]);
+        let connected_at = conn.connected_at();
+        drop(conn);
+        assert_eq!(pool.idle_connections(), 1);
+        let mut conn = pool.get().unwrap();
+        assert_eq!(conn.connected_at(), connected_at);
+        // Half of the reply is left unread.
+        assert_eq!(ask(&mut conn, 2, 1), [2]);
+        drop(conn);
+        assert_eq!(pool.idle_connections(), 0);
+        let mut conn = pool.get().unwrap();
+        conn.write_all(&[0]).unwrap();
+        // Either end of file or a broken pipe, depending on the platform.
+        let _ = conn.read(&mut [0]);
+        drop(conn);
+        assert_eq!(pool.idle_connections(), 0);
+        drop(pool);
+
+        let pool = ConnectionPool::new(&*name, 2, ReturnPolicy::DiscardInputAndReuse).unwrap();
+        let mut conn = pool.get().unwrap();
+        assert_eq!(ask(&mut conn, 3, 1), [3]);
+        drop(conn);
+        assert_eq!(pool.idle_connections(), 1);
+        let mut conn = pool.get().unwrap();
+        assert_eq!(ask(&mut conn, 1, 1), [1]);
+        drop(conn);
+        drop(pool);
+
+        let pool = ConnectionPool::new(&*name, 2, ReturnPolicy::AlwaysDiscard).unwrap();
+        let mut conn = pool.get().unwrap();
+        assert_eq!(ask(&mut conn, 1, 1), [1]);
+        drop(conn);
+        assert_eq!(pool.idle_connections(), 0);
+
+        server.shutdown_and_join(Duration::from_secs(5));
+    }
+
+    #[test]
+    fn connection_trait() {
+        use super::CancelledError;
+        use crate::Connection;
+        use std::{
+            io::{self, prelude::*},
+            process,
+        };
+
+        /// Written once against the trait, as protocol code would be.
+        fn take_greeting(conn: &mut impl Connection) -> io::Result<[u8; 5]> {
+            let mut peeked = [0; 5];
+            assert_eq!(conn.peek(&mut peeked)?, 5);
+            let mut read = [0; 5];
+            conn.read_exact(&mut read)?;
+            assert_eq!(peeked, read);
+            Ok(read)
+        }
+
+        let (_listener, mut client, mut server) = connected_pair("connection_trait");
+
+        let error = client.peek(&mut [0; 5]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+        assert!(client.is_connected().unwrap());
+        if let Ok(pid) = client.peer_process_id() {
+            assert_eq!(pid, process::id());
+        }
+
+        server.write_all(b"hello").unwrap();
+        while client.peek(&mut [0]).is_err() {}
+        assert_eq!(&take_greeting(&mut client).unwrap(), b"hello");
+
+        // Data sent before the peer went away is still there to be read.
+        server.write_all(b"bye").unwrap();
+        #[cfg(unix)]
+        Connection::shutdown(&server).unwrap();
+        #[cfg(windows)]
+        drop(server);
+        assert!(client.is_connected().unwrap());
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"bye");
+        assert!(!client.is_connected().unwrap());
+        assert_eq!(client.peek(&mut [0]).unwrap(), 0);
+
+        // Like reads and writes, they fail once the stream is cancelled.
+        client.cancel_token().cancel().unwrap();
+        let error = client.is_connected().unwrap_err();
+        assert!(error.get_ref().unwrap().is::<CancelledError>());
+        let error = client.peek(&mut [0]).unwrap_err();
+        assert!(error.get_ref().unwrap().is::<CancelledError>());
+    }
+
+    #[test]
+    fn server_idle_timeout_ignores_polling() {
+        use super::{CancelledError, ServerOptions};
+        use crate::Connection;
+        use std::{sync::mpsc, thread, time::Duration};
+
+        let (name, listener) = listen("idle_polling");
+        let (results_sender, results) = mpsc::channel();
+        let results_sender = std::sync::Mutex::new(results_sender);
+        let server = ServerOptions::new()
+            .idle_timeout(Duration::from_millis(100))
+            .spawn(listener, move |conn| {
+                // Checking on the connection isn't activity, so this has to be
+                // cancelled for being idle.
+                let cancelled = loop {
+                    match conn.is_connected() {
+                        Ok(true) => thread::sleep(Duration::from_millis(10)),
+                        Ok(false) => break false,
+                        Err(e) => break matches!(e.get_ref(), Some(e) if e.is::<CancelledError>()),
+                    }
+                };
+                results_sender.lock().unwrap().send(cancelled).unwrap();
+            })
+            .unwrap();
+
+        let _client = LocalSocketStream::connect(&*name).unwrap();
+        assert!(results.recv_timeout(Duration::from_secs(5)).unwrap());
+        server.join_all();
+    }
+
+    #[cfg(feature = "nonblocking")]
+    #[allow(deprecated)]
+    #[test]
+    fn async_connection_trait() {
+        use crate::{nonblocking::local_socket as nonblocking, AsyncConnection};
+        use futures::io::{AsyncReadExt, AsyncWriteExt};
+        use std::io;
+
+        let name = unique_name("async_connection_trait");
+        let runtime = ::tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let listener = nonblocking::LocalSocketListener::bind(name.clone()).await.unwrap();
+            let mut client = nonblocking::LocalSocketStream::connect(name).await.unwrap();
+            let mut server = listener.accept().await.unwrap();
+
+            let error = client.peek(&mut [0; 5]).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+            assert!(client.is_connected().unwrap());
+
+            server.write_all(b"hello").await.unwrap();
+            server.flush().await.unwrap();
+            while client.peek(&mut [0]).is_err() {}
+            let mut peeked = [0; 5];
+            while client.peek(&mut peeked).unwrap() < 5 {}
+            let mut read = [0; 5];
+            client.read_exact(&mut read).await.unwrap();
+            assert_eq!(&peeked, b"hello");
+            assert_eq!(&read, b"hello");
+
+            drop(server);
+            let mut rest = Vec::new();
+            client.read_to_end(&mut rest).await.unwrap();
+            assert!(rest.is_empty());
+            assert!(!client.is_connected().unwrap());
+        });
+    }
+}
@@ -0,0 +1,234 @@
+use super::{InputState, LocalSocketName, LocalSocketStream, ToLocalSocketName};
+use std::{
+    borrow::Cow,
+    fmt::{self, Debug, Formatter},
+    io::{self, Read},
+    ops::{Deref, DerefMut},
+    sync::{atomic::Ordering::SeqCst, Mutex},
+};
+
+static LPE: &str = "unexpected lock poisoning";
+
+/// How much leftover input [`ReturnPolicy::DiscardInputAndReuse`] throws away
+/// before giving up on a connection, so that a peer which keeps sending cannot
+/// keep the returning thread busy forever.
+///
+/// [`ReturnPolicy::DiscardInputAndReuse`]: enum.ReturnPolicy.html#variant.DiscardInputAndReuse " "
+const MAX_DISCARDED_INPUT: usize = 64 * 1024;
+
+/// What a [`ConnectionPool`] does with a connection which is handed back to it.
+///
+/// A connection which was in the middle of an exchange when its user stopped
+/// using it, e.g. because of an error or a panic, may still have part of a
+/// reply waiting to be read. Handing such a connection to the next user would
+/// make them read the tail end of someone else's reply as the start of their
+/// own, so returned connections are checked before being reused.
+///
+/// [`ConnectionPool`]: struct.ConnectionPool.html " "
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ReturnPolicy {
+    /// The connection is reused only if it's still connected and has no input
+    /// waiting to be read; otherwise, it's closed. This is the default.
+    ValidateAndReuse,
+    /// Leftover input is read and thrown away, after which the connection is
+    /// reused if it's still connected. Only suitable for protocols in which
+    /// the peer never sends anything unprompted, so that everything which
+    /// arrives after the returning user stopped reading belongs to that user.
+    DiscardInputAndReuse,
+    /// The connection is always closed, which makes the pool hand out a fresh
+    /// connection every time.
+    AlwaysDiscard,
+}
+impl Default for ReturnPolicy {
+    fn default() -> Self {
+        Self::ValidateAndReuse
+    }
+}
+
+/// A pool of client connections to one local socket server, reusing them
+/// instead of connecting anew for every exchange.
+///
+/// [`get`] hands out an idle connection if there is one and connects to the
+/// server otherwise. Dropping the returned [`PooledConnection`] gives the
+/// connection back to the pool, which then decides whether it can be reused
+/// according to the [`ReturnPolicy`]. Connections which have been cancelled
+/// via their [`CancelToken`], ones which the server has closed and ones which
+/// the pool already holds `max_idle` of are always closed instead.
+///
+/// Whether there's input waiting is checked without consuming it, using
+/// `recv` with `MSG_PEEK` on Unix and `PeekNamedPipe` on Windows. The check is
+/// a snapshot: a reply which is still in flight when the connection is
+/// returned cannot be detected, so users should read replies in full or
+/// [`discard`] the connection if they bail out midway.
+///
+/// The pool can be shared between threads; every connection is only ever used
+/// by one of them at a time.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use interprocess::local_socket::{ConnectionPool, ReturnPolicy};
+/// use std::io::prelude::*;
+///
+/// let pool = ConnectionPool::new("/tmp/example.sock", 4, ReturnPolicy::ValidateAndReuse)?;
+/// for _ in 0..10 {
+///     let mut conn = pool.get()?;
+///     conn.write_all(b"ping\n")?;
+///     let mut reply = [0; 5];
+///     conn.read_exact(&mut reply)?;
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// [`get`]: #method.get " "
+/// [`PooledConnection`]: struct.PooledConnection.html " "
+/// [`ReturnPolicy`]: enum.ReturnPolicy.html " "
+/// [`CancelToken`]: struct.CancelToken.html " "
+/// [`discard`]: struct.PooledConnection.html#method.discard " "
+pub struct ConnectionPool {
+    name:     LocalSocketName<'static>,
+    max_idle: usize,
+    policy:   ReturnPolicy,
+    idle:     Mutex<Vec<LocalSocketStream>>,
+}
+impl ConnectionPool {
+    /// Creates a pool of connections to the server with the specified name,
+    /// keeping up to `max_idle` connections around while they aren't in use.
+    ///
+    /// No connections are made until they're needed.
+    pub fn new<'a>(
+        name: impl ToLocalSocketName<'a>,
+        max_idle: usize,
+        policy: ReturnPolicy,
+    ) -> io::Result<Self> {
+        let name = name.to_local_socket_name()?;
+        Ok(Self {
+            name: LocalSocketName {
+                inner:      Cow::Owned(name.inner.into_owned()),
+                namespaced: name.namespaced,
+            },
+            max_idle,
+            policy,
+            idle: Mutex::new(Vec::new()),
+        })
+    }
+    /// Hands out an idle connection, or connects to the server if there are
+    /// none.
+    pub fn get(&self) -> io::Result<PooledConnection<'_>> {
+        let idle = self.idle.lock().expect(LPE).pop();
+        let conn = match idle {
+            Some(conn) => conn,
+            None => LocalSocketStream::connect(LocalSocketName {
+                inner:      Cow::Borrowed(&*self.name.inner),
+                namespaced: self.name.namespaced,
+            })?,
+        };
+        Ok(PooledConnection {
+            pool: self,
+            conn: Some(conn),
+        })
+    }
+    /// Returns the amount of connections which are currently idle in the pool.
+    pub fn idle_connections(&self) -> usize {
+        self.idle.lock().expect(LPE).len()
+    }
+    /// Returns the policy which the pool applies to returned connections.
+    pub fn policy(&self) -> ReturnPolicy {
+        self.policy
+    }
+    fn put_back(&self, mut conn: LocalSocketStream) {
+        if self.policy == ReturnPolicy::AlwaysDiscard
+            || conn.cancel.0.cancelled.load(SeqCst)
+            || !self.is_reusable(&mut conn)
+        {
+            return;
+        }
+        let mut idle = self.idle.lock().expect(LPE);
+        if idle.len() < self.max_idle {
+            idle.push(conn);
+        }
+    }
+    fn is_reusable(&self, conn: &mut LocalSocketStream) -> bool {
+        // The user may have left the stream in nonblocking mode, which the next
+        // one wouldn't expect.
+        if conn.set_nonblocking(false).is_err() {
+            return false;
+        }
+        let mut discarded = 0;
+        loop {
+            match conn.inner.input_state() {
+                Ok(InputState::Empty) => return true,
+                Ok(InputState::Pending)
+                    if self.policy == ReturnPolicy::DiscardInputAndReuse
+                        && discarded < MAX_DISCARDED_INPUT =>
+                {
+                    // There is input, so this doesn't block.
+                    let mut buf = [0; 512];
+                    match conn.read(&mut buf) {
+                        Ok(0) | Err(..) => return false,
+                        Ok(bytes_read) => discarded += bytes_read,
+                    }
+                },
+                _ => return false,
+            }
+        }
+    }
+}
+impl Debug for ConnectionPool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionPool")
+            .field("name", &self.name.inner)
+            .field("max_idle", &self.max_idle)
+            .field("policy", &self.policy)
+            .field("idle_connections", &self.idle_connections())
+            .finish()
+    }
+}
+
+/// A connection handed out by a [`ConnectionPool`], given back to it when
+/// dropped.
+///
+/// Dereferences to the [`LocalSocketStream`], which is how it's read from and
+/// written to.
+///
+/// [`ConnectionPool`]: struct.ConnectionPool.html " "
+/// [`LocalSocketStream`]: struct.LocalSocketStream.html " "
+pub struct PooledConnection<'a> {
+    pool: &'a ConnectionPool,
+    /// Only `None` while being taken out of the wrapper.
+    conn: Option<LocalSocketStream>,
+}
+impl PooledConnection<'_> {
+    /// Closes the connection instead of giving it back to the pool, for users
+    /// who know that it's not in a state in which it could be reused.
+    pub fn discard(mut self) {
+        self.conn = None;
+    }
+    /// Takes the connection out of the pool for good.
+    pub fn detach(mut self) -> LocalSocketStream {
+        self.conn.take().expect("connection taken out twice")
+    }
+}
+impl Deref for PooledConnection<'_> {
+    type Target = LocalSocketStream;
+    fn deref(&self) -> &Self::Target {
+        self.conn.as_ref().expect("connection taken out")
+    }
+}
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.conn.as_mut().expect("connection taken out")
+    }
+}
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.put_back(conn);
+        }
+    }
+}
+impl Debug for PooledConnection<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PooledConnection").field(&self.conn).finish()
+    }
+}
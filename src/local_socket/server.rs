@@ -0,0 +1,537 @@
+use super::{CancelToken, ListenerClosedError, LocalSocketListener, LocalSocketStream};
+use std::{
+    fmt::{self, Debug, Formatter},
+    io,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        mpsc::{self, Receiver, SyncSender},
+        Arc,
+        Condvar,
+        Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// How long the accepting thread sleeps when there are no clients, before
+/// checking whether it should stop.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+static LPE: &str = "unexpected lock poisoning";
+
+/// Returns whether accepting failed because the listener has been closed,
+/// which means that it never succeeds again.
+fn is_listener_closed(error: &io::Error) -> bool {
+    matches!(error.get_ref(), Some(e) if e.is::<ListenerClosedError>())
+}
+
+/// Starts serving connections from the listener in the background, calling
+/// `handler` for each of them on a thread of its own.
+///
+/// The listener is moved to a thread which accepts clients until the server is
+/// shut down via the returned [`ServerHandle`], which keeps track of the
+/// threads handling the connections, so that shutting down can wait for them
+/// instead of leaving them detached. The listener is switched to nonblocking
+/// mode to let the accepting thread notice the shutdown; accepted streams are
+/// switched back to blocking mode before being handed to `handler`. Clients
+/// which fail to be accepted are skipped; once the listener has been
+/// [closed], the accepting thread exits instead.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use interprocess::local_socket::{spawn_server, LocalSocketListener};
+/// use std::{
+///     io::{self, prelude::*, BufReader},
+///     time::Duration,
+/// };
+///
+/// let listener = LocalSocketListener::bind("/tmp/example.sock")?;
+/// let server = spawn_server(listener, |conn| {
+///     let mut conn = BufReader::new(conn);
+///     let mut line = String::new();
+///     if conn.read_line(&mut line).is_ok() {
+///         let _ = conn.get_mut().write_all(line.as_bytes());
+///     }
+/// })?;
+/// // Serve until the user presses Enter.
+/// io::stdin().read_line(&mut String::new())?;
+/// let cut_off = server.shutdown_and_join(Duration::from_secs(5));
+/// println!("{} connections were still busy and had to be closed", cut_off);
+/// # Ok(()) }
+/// ```
+///
+/// [`ServerHandle`]: struct.ServerHandle.html " "
+/// [closed]: struct.LocalSocketListener.html#method.close " "
+pub fn spawn_server<F>(listener: LocalSocketListener, handler: F) -> io::Result<ServerHandle>
+where
+    F: Fn(LocalSocketStream) + Send + Sync + 'static,
+{
+    ServerOptions::new().spawn(listener, handler)
+}
+
+/// Configuration for servers started in the background, for when the defaults
+/// used by [`spawn_server`] don't fit.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use interprocess::local_socket::{LocalSocketListener, ServerOptions};
+/// use std::{io::prelude::*, time::Duration};
+///
+/// let listener = LocalSocketListener::bind("/tmp/example.sock")?;
+/// let server = ServerOptions::new()
+///     .idle_timeout(Duration::from_secs(30))
+///     .spawn(listener, |mut conn| {
+///         let mut request = [0; 4];
+///         if conn.read_exact(&mut request).is_ok() {
+///             let _ = conn.write_all(&request);
+///         }
+///     })?;
+/// server.join_all();
+/// # Ok(()) }
+/// ```
+///
+/// [`spawn_server`]: fn.spawn_server.html " "
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ServerOptions {
+    /// How long a connection may go without a single successful read or write
+    /// before the server closes it, or `None` to never close connections for
+    /// being idle, which is the default.
+    ///
+    /// This protects the server from clients which connect and then send
+    /// nothing, tying up a thread each for as long as they like. Idle
+    /// connections are closed with their [`CancelToken`], which **interrupts
+    /// reads and writes which are in progress** on them, making those and all
+    /// further I/O fail with a [`CancelledError`]; the handler is then expected
+    /// to return. Time which the handler spends on something other than I/O on
+    /// the connection counts as idle time too, so handlers which take long to
+    /// process a request should be given a timeout long enough to cover that.
+    /// Operations which receive data in several steps, such as `read_chunks`
+    /// and `splice_to_file`, count every step as activity, so a transfer which
+    /// keeps making progress is never considered idle, while `peek` and
+    /// `is_connected` don't transfer anything and thus don't count as activity
+    /// at all. Connections are checked
+    /// every few milliseconds, so they may stay open slightly longer than
+    /// the timeout.
+    ///
+    /// [`CancelToken`]: struct.CancelToken.html " "
+    /// [`CancelledError`]: struct.CancelledError.html " "
+    pub idle_timeout: Option<Duration>,
+}
+impl ServerOptions {
+    /// Creates a new builder with default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets the [`idle_timeout`](#structfield.idle_timeout) parameter to the
+    /// specified value.
+    #[must_use = "builder setters take the entire structure and return the result"]
+    pub fn idle_timeout(mut self, idle_timeout: impl Into<Option<Duration>>) -> Self {
+        self.idle_timeout = idle_timeout.into();
+        self
+    }
+    /// Starts serving connections from the listener in the background with the
+    /// options, the same way as [`spawn_server`].
+    ///
+    /// [`spawn_server`]: fn.spawn_server.html " "
+    pub fn spawn<F>(self, listener: LocalSocketListener, handler: F) -> io::Result<ServerHandle>
+    where
+        F: Fn(LocalSocketStream) + Send + Sync + 'static,
+    {
+        listener.set_nonblocking(true)?;
+        let shared = Arc::new(Shared {
+            stopping:     AtomicBool::new(false),
+            connections:  Mutex::new(Connections::default()),
+            finished:     Condvar::new(),
+            idle_timeout: self.idle_timeout,
+        });
+        let acceptor = {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new().spawn(move || accept_loop(listener, handler, &shared))?
+        };
+        Ok(ServerHandle {
+            shared,
+            acceptor: Some(acceptor),
+        })
+    }
+}
+
+/// A handle to a server started by [`spawn_server`], used to shut it down.
+///
+/// Shutting down always stops accepting new clients first. What happens to the
+/// connections which are being handled at that point depends on the method:
+/// - [`join_all`] waits for all of them to be handled, for however long that
+///   takes;
+/// - [`shutdown_and_join`] waits for them for up to the specified amount of
+///   time, and then cancels the ones which are still being handled using their
+///   [`CancelToken`], which closes them and makes all I/O on them fail, and
+///   waits for their handlers to return.
+///
+/// Cancellation only interrupts I/O on the connection: a handler which is busy
+/// with something else still has to return on its own, so handlers which run
+/// for long stretches without touching the connection delay the shutdown.
+/// Handlers which panic are treated the same as ones which return.
+///
+/// Dropping the handle stops accepting new clients, but leaves the threads
+/// handling the existing connections running, detached.
+///
+/// [`spawn_server`]: fn.spawn_server.html " "
+/// [`join_all`]: #method.join_all " "
+/// [`shutdown_and_join`]: #method.shutdown_and_join " "
+/// [`CancelToken`]: struct.CancelToken.html " "
+pub struct ServerHandle {
+    shared:   Arc<Shared>,
+    acceptor: Option<JoinHandle<()>>,
+}
+impl ServerHandle {
+    /// Returns the amount of connections which are currently being handled.
+    pub fn active_connections(&self) -> usize {
+        self.shared.connections.lock().expect(LPE).active
+    }
+    /// Lists the connections which are currently being handled, in the order
+    /// in which they were accepted.
+    ///
+    /// The list is a snapshot: connections may be accepted or finish being
+    /// handled right after it's taken.
+    pub fn connections(&self) -> Vec<ConnectionInfo> {
+        let connections = self.shared.connections.lock().expect(LPE);
+        connections
+            .list
+            .iter()
+            .filter(|connection| !connection.done.load(SeqCst))
+            .map(|connection| ConnectionInfo {
+                id:           connection.id,
+                peer_pid:     connection.peer_pid,
+                connected_at: connection.connected_at,
+                idle_for:     connection.cancel.0.idle_for(),
+            })
+            .collect()
+    }
+    /// Forcibly closes the connection with the specified id, e.g. to kick out
+    /// a misbehaving client. Returns `false` if there's no such connection,
+    /// which includes connections whose handler has already returned.
+    ///
+    /// The connection is closed with its [`CancelToken`], which interrupts
+    /// reads and writes which are in progress on it and makes all further I/O
+    /// fail with a [`CancelledError`]; the handler is expected to return
+    /// afterwards, at which point the stream is dropped and the connection
+    /// disappears from [`connections`]. Like with [`shutdown_and_join`], a
+    /// handler which doesn't touch the connection keeps running until it
+    /// returns on its own.
+    ///
+    /// [`CancelToken`]: struct.CancelToken.html " "
+    /// [`CancelledError`]: struct.CancelledError.html " "
+    /// [`connections`]: #method.connections " "
+    /// [`shutdown_and_join`]: #method.shutdown_and_join " "
+    pub fn close_connection(&self, id: u64) -> io::Result<bool> {
+        let connections = self.shared.connections.lock().expect(LPE);
+        let connection = connections
+            .list
+            .iter()
+            .find(|connection| connection.id == id && !connection.done.load(SeqCst));
+        match connection {
+            Some(connection) => connection.cancel.cancel().map(|()| true),
+            None => Ok(false),
+        }
+    }
+    /// Stops accepting new clients and waits for the handlers of all existing
+    /// connections to return.
+    pub fn join_all(mut self) {
+        self.stop_accepting();
+        self.join_handlers();
+    }
+    /// Stops accepting new clients and waits for the handlers of the existing
+    /// connections to return for up to the specified amount of time, after
+    /// which the remaining connections are cancelled. Returns once all
+    /// handlers have returned, along with the amount of connections which had
+    /// to be cancelled.
+    pub fn shutdown_and_join(mut self, timeout: Duration) -> usize {
+        self.stop_accepting();
+        let deadline = Instant::now() + timeout;
+        let mut connections = self.shared.connections.lock().expect(LPE);
+        while connections.active != 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            connections =
+                self.shared.finished.wait_timeout(connections, deadline - now).expect(LPE).0;
+        }
+        let mut cancelled = 0;
+        for connection in &connections.list {
+            if !connection.done.load(SeqCst) {
+                // An error means that the connection is already beyond saving anyway.
+                let _ = connection.cancel.cancel();
+                cancelled += 1;
+            }
+        }
+        drop(connections);
+        self.join_handlers();
+        cancelled
+    }
+    fn stop_accepting(&mut self) {
+        self.shared.stopping.store(true, SeqCst);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+    }
+    fn join_handlers(&self) {
+        let list = std::mem::take(&mut self.shared.connections.lock().expect(LPE).list);
+        for connection in list {
+            let _ = connection.thread.join();
+        }
+    }
+}
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        self.stop_accepting();
+    }
+}
+impl Debug for ServerHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerHandle")
+            .field("active_connections", &self.active_connections())
+            .finish()
+    }
+}
+
+/// Information about a connection being handled by a server started by
+/// [`spawn_server`], as listed by [`ServerHandle::connections`].
+///
+/// [`spawn_server`]: fn.spawn_server.html " "
+/// [`ServerHandle::connections`]: struct.ServerHandle.html#method.connections " "
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ConnectionInfo {
+    /// Identifies the connection for [`ServerHandle::close_connection`].
+    ///
+    /// Ids are assigned in the order in which connections are accepted,
+    /// starting from zero, and are never reused during the lifetime of the
+    /// server, so an id which refers to a connection that has since gone away
+    /// never ends up closing a different one. An id stays valid for as long as
+    /// the handler of the connection is running.
+    ///
+    /// [`ServerHandle::close_connection`]: struct.ServerHandle.html#method.close_connection " "
+    pub id:           u64,
+    /// The process ID of the client, or `None` if the platform cannot tell.
+    pub peer_pid:     Option<u32>,
+    /// When the connection was accepted.
+    pub connected_at: Instant,
+    /// How long it has been since the last successful read from or write to
+    /// the connection, or since it was accepted if there has been none.
+    pub idle_for:     Duration,
+}
+
+struct Shared {
+    stopping:     AtomicBool,
+    connections:  Mutex<Connections>,
+    /// Signalled whenever a handler returns.
+    finished:     Condvar,
+    idle_timeout: Option<Duration>,
+}
+#[derive(Default)]
+struct Connections {
+    list:    Vec<Connection>,
+    /// The amount of handlers which haven't returned yet.
+    active:  usize,
+    /// Only ever incremented, so that ids aren't reused.
+    next_id: u64,
+}
+struct Connection {
+    id:           u64,
+    peer_pid:     Option<u32>,
+    connected_at: Instant,
+    thread:       JoinHandle<()>,
+    cancel:       CancelToken,
+    done:         Arc<AtomicBool>,
+}
+
+/// Marks a connection as handled when dropped, which also happens if the
+/// handler panics.
+struct FinishGuard {
+    shared: Arc<Shared>,
+    done:   Arc<AtomicBool>,
+}
+impl Drop for FinishGuard {
+    fn drop(&mut self) {
+        if let Ok(mut connections) = self.shared.connections.lock() {
+            self.done.store(true, SeqCst);
+            connections.active -= 1;
+        }
+        self.shared.finished.notify_all();
+    }
+}
+
+fn accept_loop<F>(listener: LocalSocketListener, handler: F, shared: &Arc<Shared>)
+where
+    F: Fn(LocalSocketStream) + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+    let mut last_idle_check = Instant::now();
+    while !shared.stopping.load(SeqCst) {
+        if let Some(idle_timeout) = shared.idle_timeout {
+            if last_idle_check.elapsed() >= ACCEPT_POLL_INTERVAL {
+                close_idle_connections(shared, idle_timeout);
+                last_idle_check = Instant::now();
+            }
+        }
+        let conn = match listener.accept() {
+            Ok(conn) => conn,
+            Err(e) if is_listener_closed(&e) => break,
+            Err(..) => {
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+                continue;
+            },
+        };
+        if conn.set_nonblocking(false).is_err() {
+            continue;
+        }
+        let cancel = conn.cancel_token();
+        let peer_pid = conn.peer_pid().ok();
+        let connected_at = conn.connected_at();
+        let done = Arc::new(AtomicBool::new(false));
+        let (id, finished) = {
+            let mut connections = shared.connections.lock().expect(LPE);
+            let id = connections.next_id;
+            connections.next_id += 1;
+            // Threads of connections which have been handled are joined right away
+            // rather than piling up until shutdown.
+            let (finished, list) =
+                connections.list.drain(..).partition::<Vec<_>, _>(|c| c.done.load(SeqCst));
+            connections.list = list;
+            // Counted before the thread starts, since the guard uncounts it when the
+            // handler returns, or right away if the thread fails to spawn.
+            connections.active += 1;
+            (id, finished)
+        };
+        let guard = FinishGuard {
+            shared: Arc::clone(shared),
+            done:   Arc::clone(&done),
+        };
+        let handler = Arc::clone(&handler);
+        let spawned = thread::Builder::new().spawn(move || {
+            let _guard = guard;
+            handler(conn);
+        });
+        if let Ok(thread) = spawned {
+            shared.connections.lock().expect(LPE).list.push(Connection {
+                id,
+                peer_pid,
+                connected_at,
+                thread,
+                cancel,
+                done,
+            });
+        }
+        for connection in finished {
+            let _ = connection.thread.join();
+        }
+    }
+}
+
+fn close_idle_connections(shared: &Shared, idle_timeout: Duration) {
+    let connections = shared.connections.lock().expect(LPE);
+    for connection in &connections.list {
+        if !connection.done.load(SeqCst) && connection.cancel.0.idle_for() >= idle_timeout {
+            // An error means that the connection is already beyond saving anyway.
+            let _ = connection.cancel.cancel();
+        }
+    }
+}
+
+/// Starts accepting connections from the listener in the background, passing
+/// the accepted streams, as well as the errors from accepting, to the returned
+/// receiver through a channel which holds up to `capacity` of them.
+///
+/// This separates accepting from handling the connections, for servers which
+/// hand them out to workers or multiplex them in an event loop of their own.
+/// The channel provides backpressure: once it's full, the accepting thread
+/// waits for the receiving end to take a stream out of it before accepting the
+/// next client, leaving further clients waiting in the listen backlog or for a
+/// free pipe instance. A `capacity` of zero makes every accepted stream wait
+/// until it's received.
+///
+/// Dropping the receiver shuts the accepting thread down and closes the
+/// listener, even if it's waiting for a client or for room in the channel at
+/// that moment. As with [`spawn_server`], the listener is switched to
+/// nonblocking mode so that the thread can notice the shutdown, and accepted
+/// streams are switched back to blocking mode. Once the listener has been
+/// [closed], the thread exits, which disconnects the channel.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use interprocess::local_socket::{spawn_acceptor, LocalSocketListener};
+/// use std::io::prelude::*;
+///
+/// let listener = LocalSocketListener::bind("/tmp/example.sock")?;
+/// let streams = spawn_acceptor(listener, 16)?;
+/// for conn in streams.iter() {
+///     conn?.write_all(b"Hello from server!\n")?;
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// [`spawn_server`]: fn.spawn_server.html " "
+/// [closed]: struct.LocalSocketListener.html#method.close " "
+pub fn spawn_acceptor(listener: LocalSocketListener, capacity: usize) -> io::Result<Acceptor> {
+    listener.set_nonblocking(true)?;
+    let stopping = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::sync_channel(capacity);
+    {
+        let stopping = Arc::clone(&stopping);
+        thread::Builder::new().spawn(move || acceptor_loop(listener, &sender, &stopping))?;
+    }
+    Ok(Acceptor { receiver, stopping })
+}
+
+/// The receiving end of the channel created by [`spawn_acceptor`].
+///
+/// Dereferences to the standard library's [`Receiver`], with all of its ways to
+/// receive streams. Dropping it stops the accepting thread.
+///
+/// [`spawn_acceptor`]: fn.spawn_acceptor.html " "
+/// [`Receiver`]: https://doc.rust-lang.org/std/sync/mpsc/struct.Receiver.html " "
+#[derive(Debug)]
+pub struct Acceptor {
+    receiver: Receiver<io::Result<LocalSocketStream>>,
+    stopping: Arc<AtomicBool>,
+}
+impl Deref for Acceptor {
+    type Target = Receiver<io::Result<LocalSocketStream>>;
+    fn deref(&self) -> &Self::Target {
+        &self.receiver
+    }
+}
+impl Drop for Acceptor {
+    fn drop(&mut self) {
+        // A thread waiting for room in the channel is woken up by the receiver
+        // being dropped right after this; one waiting for a client checks the
+        // flag.
+        self.stopping.store(true, SeqCst);
+    }
+}
+
+fn acceptor_loop(
+    listener: LocalSocketListener,
+    sender: &SyncSender<io::Result<LocalSocketStream>>,
+    stopping: &AtomicBool,
+) {
+    while !stopping.load(SeqCst) {
+        let accepted = match listener.accept() {
+            Ok(conn) => conn.set_nonblocking(false).map(|()| conn),
+            Err(e) if is_listener_closed(&e) => return,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+                continue;
+            },
+            Err(e) => Err(e),
+        };
+        if sender.send(accepted).is_err() {
+            // The receiver is gone.
+            return;
+        }
+    }
+}
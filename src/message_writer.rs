@@ -0,0 +1,178 @@
+use crate::PartialMsgWriteError;
+use std::io::{self, Write};
+
+/// Builds a message out of any amount of `write` calls and sends it as a
+/// single message when finished.
+///
+/// Message-oriented IPC primitives, such as message-mode named pipes, treat
+/// every call to `write` as a separate message. This becomes a problem for
+/// code which produces a message piece by piece — formatting with `write!`,
+/// serializers which write field by field, and anything else that expects a
+/// byte stream will end up sending a batch of fragments instead of the message
+/// that was meant to be sent. `MessageWriter` collects everything written into
+/// it in a buffer and hands the whole buffer to the underlying writer with one
+/// `write` call in [`finish`], so that exactly one message is sent.
+///
+/// The writer can be reused for any number of messages: after `finish`, the
+/// next `write` starts a new message, which can be checked for with
+/// [`is_empty`]. Dropping the `MessageWriter` discards the message which is
+/// being built, if any, without sending it.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # #[cfg(windows)] {
+/// use interprocess::{
+///     os::windows::named_pipe::DuplexMsgPipeStream,
+///     MessageWriter,
+/// };
+/// use std::io::prelude::*;
+///
+/// let conn = DuplexMsgPipeStream::connect("Example")?;
+/// let mut writer = MessageWriter::new(conn);
+/// // Three writes, one message.
+/// write!(writer, "Hello")?;
+/// writer.write_all(b", ")?;
+/// write!(writer, "server!")?;
+/// writer.finish()?;
+/// # }
+/// # Ok(()) }
+/// ```
+///
+/// [`finish`]: #method.finish " "
+/// [`is_empty`]: #method.is_empty " "
+#[derive(Debug)]
+pub struct MessageWriter<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+}
+impl<W: Write> MessageWriter<W> {
+    /// Creates a message writer which sends messages into the specified writer.
+    pub fn new(writer: W) -> Self {
+        Self::with_capacity(writer, 0)
+    }
+    /// Creates a message writer which sends messages into the specified writer,
+    /// with enough space preallocated for a message of the specified size.
+    ///
+    /// The buffer is retained between messages, so this only serves to avoid
+    /// reallocations while the first one is being built.
+    pub fn with_capacity(writer: W, capacity: usize) -> Self {
+        Self {
+            writer,
+            buffer: Vec::with_capacity(capacity),
+        }
+    }
+    /// Sends the message which has been built so far as a single message and
+    /// starts a new one. A message is sent even if nothing has been written
+    /// into it, in which case it's empty.
+    ///
+    /// If the underlying writer fails, the message is kept intact, allowing
+    /// to retry the operation. If it writes only a part of the message, the
+    /// rest of it is discarded and a [`PartialMsgWriteError`] is returned with
+    /// the `ErrorKind::Other` error type.
+    ///
+    /// [`PartialMsgWriteError`]: struct.PartialMsgWriteError.html " "
+    pub fn finish(&mut self) -> io::Result<()> {
+        let bytes_written = self.writer.write(&self.buffer)?;
+        let whole_message = bytes_written == self.buffer.len();
+        self.buffer.clear();
+        if whole_message {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::Other, PartialMsgWriteError))
+        }
+    }
+    /// Discards the message which has been built so far without sending it.
+    pub fn discard(&mut self) {
+        self.buffer.clear();
+    }
+    /// Returns `true` if nothing has been written into the message yet, meaning
+    /// that the next `write` call starts a new message.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+    /// Returns the size of the message which has been built so far, in bytes.
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+    /// Borrows the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+    /// Mutably borrows the underlying writer. Writing into it directly sends a
+    /// message separately from the one being built.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+    /// Returns the underlying writer, discarding the message which has been
+    /// built so far, if any.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+/// Appends to the message being built instead of writing anything into the
+/// underlying writer. Flushing is a no-op which does not finish the message.
+impl<W: Write> Write for MessageWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Records every `write` call as a separate message, like a message-mode
+    /// pipe would, optionally accepting only a limited amount of bytes.
+    #[derive(Default)]
+    struct MsgSink {
+        messages: Vec<Vec<u8>>,
+        max_len:  Option<usize>,
+    }
+    impl Write for MsgSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let len = self.max_len.map_or(buf.len(), |max| buf.len().min(max));
+            self.messages.push(buf[..len].to_vec());
+            Ok(len)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn one_message_per_finish() {
+        let mut writer = MessageWriter::new(MsgSink::default());
+        assert!(writer.is_empty());
+        write!(writer, "Hello, world").unwrap();
+        writer.write_all(b"!").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(writer.len(), 13);
+        assert!(writer.get_ref().messages.is_empty());
+        writer.finish().unwrap();
+        assert!(writer.is_empty());
+        writer.write_all(b"second").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            writer.into_inner().messages,
+            [&b"Hello, world!"[..], &b"second"[..]]
+        );
+    }
+
+    #[test]
+    fn partial_write() {
+        let sink = MsgSink {
+            max_len: Some(4),
+            ..MsgSink::default()
+        };
+        let mut writer = MessageWriter::new(sink);
+        writer.write_all(b"too long").unwrap();
+        let error = writer.finish().unwrap_err();
+        assert!(error.get_ref().unwrap().is::<PartialMsgWriteError>());
+        assert!(writer.is_empty());
+    }
+}
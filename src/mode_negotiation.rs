@@ -0,0 +1,260 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io::{self, Read, Write},
+};
+
+/// A transfer mode which the two ends of a connection can agree on using
+/// [`negotiate_mode_client`] and [`negotiate_mode_server`].
+///
+/// [`negotiate_mode_client`]: fn.negotiate_mode_client.html " "
+/// [`negotiate_mode_server`]: fn.negotiate_mode_server.html " "
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TransferMode {
+    /// Data is a stream of bytes, with no boundaries between separate writes.
+    Bytes,
+    /// Data is sent in messages, each of which is received as a whole.
+    Messages,
+}
+impl TransferMode {
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Bytes => 0,
+            Self::Messages => 1,
+        }
+    }
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Bytes),
+            1 => Some(Self::Messages),
+            _ => None,
+        }
+    }
+}
+#[cfg(windows)]
+impl From<TransferMode> for crate::os::windows::named_pipe::PipeMode {
+    fn from(mode: TransferMode) -> Self {
+        match mode {
+            TransferMode::Bytes => Self::Bytes,
+            TransferMode::Messages => Self::Messages,
+        }
+    }
+}
+
+/// The first byte of both the request and the reply, which lets either side
+/// tell a peer which doesn't perform the handshake apart from one which does.
+const HANDSHAKE_VERSION: u8 = 0xA1;
+/// Stands in for a missing mode in the request and for the lack of a common
+/// mode in the reply.
+const NO_MODE: u8 = 0xFF;
+
+/// Performs the client side of the transfer mode handshake, proposing the
+/// modes which the client supports in order of preference, and returns the
+/// mode which the server picked.
+///
+/// The handshake is tiny and fixed-size: the client sends a 3-byte request and
+/// the server answers with a 2-byte reply, each side using a single `write`
+/// call. This means that it works the same way over byte streams and over
+/// message-mode named pipes, where each side of the handshake is one message.
+/// Once it's done, both sides switch to the agreed mode.
+///
+/// # Switching modes
+/// How the switch happens depends on the platform:
+/// - **On Windows**, the read mode of a named pipe stream can be changed on a
+///   live connection with [`set_read_mode`], which is what the agreed mode is
+///   meant for. The type of the pipe is fixed when the server creates it,
+///   though: a pipe created in byte mode cannot be read in message mode, so a
+///   server which wants to support both has to create message-mode pipes and
+///   only offer [`Messages`] if it does.
+/// - **On Unix**, the type of a socket is chosen when it's created, and a
+///   connected socket cannot be switched between stream and sequenced-packet
+///   semantics at all. The handshake can still be used to pick the mode, but
+///   switching to it requires closing the connection and connecting again with
+///   the socket type which corresponds to it, or layering message framing of
+///   one's own on top of the byte stream.
+///
+/// # Errors
+/// Fails with a [`NoCommonModeError`] if the server supports none of the
+/// proposed modes, and with `ErrorKind::InvalidData` if the reply is not a
+/// valid handshake reply, which usually means that the server doesn't perform
+/// the handshake. Also fails if `preferred` is empty or contains more than two
+/// modes, since there are no more than two to begin with.
+///
+/// [`set_read_mode`]: os/windows/named_pipe/struct.DuplexMsgPipeStream.html#method.set_read_mode " "
+/// [`Messages`]: enum.TransferMode.html#variant.Messages " "
+/// [`NoCommonModeError`]: struct.NoCommonModeError.html " "
+pub fn negotiate_mode_client(
+    conn: &mut (impl Read + Write),
+    preferred: &[TransferMode],
+) -> io::Result<TransferMode> {
+    let (first, second) = match *preferred {
+        [first] => (first.to_byte(), NO_MODE),
+        [first, second] => (first.to_byte(), second.to_byte()),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "one or two transfer modes must be proposed",
+            ))
+        },
+    };
+    conn.write_all(&[HANDSHAKE_VERSION, first, second])?;
+    conn.flush()?;
+    let mut reply = [0; 2];
+    conn.read_exact(&mut reply)?;
+    if reply[0] != HANDSHAKE_VERSION {
+        return Err(invalid_handshake());
+    }
+    if reply[1] == NO_MODE {
+        return Err(NoCommonModeError.into());
+    }
+    match TransferMode::from_byte(reply[1]) {
+        Some(mode) if preferred.contains(&mode) => Ok(mode),
+        _ => Err(invalid_handshake()),
+    }
+}
+
+/// Performs the server side of the transfer mode handshake started by
+/// [`negotiate_mode_client`], picking the first of the modes proposed by the
+/// client which is also in `supported`, and returns the picked mode.
+///
+/// If there is no such mode, the client is told so before a
+/// [`NoCommonModeError`] is returned, so that both sides fail the same way.
+/// Malformed requests fail with `ErrorKind::InvalidData` without a reply. See
+/// [`negotiate_mode_client`] for what to do with the result.
+///
+/// [`negotiate_mode_client`]: fn.negotiate_mode_client.html " "
+/// [`NoCommonModeError`]: struct.NoCommonModeError.html " "
+pub fn negotiate_mode_server(
+    conn: &mut (impl Read + Write),
+    supported: &[TransferMode],
+) -> io::Result<TransferMode> {
+    let mut request = [0; 3];
+    conn.read_exact(&mut request)?;
+    if request[0] != HANDSHAKE_VERSION {
+        return Err(invalid_handshake());
+    }
+    let mut picked = None;
+    for &byte in request[1..].iter().filter(|&&byte| byte != NO_MODE) {
+        let mode = TransferMode::from_byte(byte).ok_or_else(invalid_handshake)?;
+        if picked.is_none() && supported.contains(&mode) {
+            picked = Some(mode);
+        }
+    }
+    conn.write_all(&[
+        HANDSHAKE_VERSION,
+        picked.map_or(NO_MODE, TransferMode::to_byte),
+    ])?;
+    conn.flush()?;
+    picked.ok_or_else(|| NoCommonModeError.into())
+}
+
+fn invalid_handshake() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "invalid transfer mode handshake",
+    )
+}
+
+/// Marker error indicating that the transfer mode handshake failed because the
+/// client and the server have no supported mode in common.
+///
+/// Always emitted with the `ErrorKind::Other` error type.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct NoCommonModeError;
+impl Display for NoCommonModeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("the client and the server have no transfer mode in common")
+    }
+}
+impl Error for NoCommonModeError {
+}
+impl From<NoCommonModeError> for io::Error {
+    fn from(e: NoCommonModeError) -> Self {
+        io::Error::new(io::ErrorKind::Other, e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    /// One side of a connection, reading what the other side has sent from a
+    /// buffer filled out in advance and recording what it sends.
+    struct Scripted {
+        input:  Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+    impl Scripted {
+        fn new(input: Vec<u8>) -> Self {
+            Self {
+                input:  Cursor::new(input),
+                output: Vec::new(),
+            }
+        }
+    }
+    impl Read for Scripted {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+    impl Write for Scripted {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs the server side against the request sent by the client side,
+    /// then feeds the reply back into the client side.
+    fn handshake(
+        preferred: &[TransferMode],
+        supported: &[TransferMode],
+    ) -> (io::Result<TransferMode>, io::Result<TransferMode>) {
+        // The client needs the reply before it can finish, so its request is
+        // produced by a run which fails at end of file and then replayed.
+        let mut client = Scripted::new(Vec::new());
+        let _ = negotiate_mode_client(&mut client, preferred);
+        let mut server = Scripted::new(client.output);
+        let server_result = negotiate_mode_server(&mut server, supported);
+        let mut client = Scripted::new(server.output);
+        let client_result = negotiate_mode_client(&mut client, preferred);
+        (client_result, server_result)
+    }
+
+    #[test]
+    fn client_preference_wins() {
+        use TransferMode::*;
+        let (client, server) = handshake(&[Messages, Bytes], &[Bytes, Messages]);
+        assert_eq!(client.unwrap(), Messages);
+        assert_eq!(server.unwrap(), Messages);
+        let (client, server) = handshake(&[Messages, Bytes], &[Bytes]);
+        assert_eq!(client.unwrap(), Bytes);
+        assert_eq!(server.unwrap(), Bytes);
+    }
+
+    #[test]
+    fn no_common_mode() {
+        use TransferMode::*;
+        let (client, server) = handshake(&[Messages], &[Bytes]);
+        for result in [client, server].iter() {
+            let error = result.as_ref().unwrap_err();
+            assert!(error.get_ref().unwrap().is::<NoCommonModeError>());
+        }
+    }
+
+    #[test]
+    fn invalid_peer() {
+        let mut server = Scripted::new(b"GET / HTTP/1.1".to_vec());
+        let error = negotiate_mode_server(&mut server, &[TransferMode::Bytes]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(server.output.is_empty());
+
+        let mut client = Scripted::new(vec![HANDSHAKE_VERSION, TransferMode::Bytes.to_byte()]);
+        // The server picked a mode which wasn't proposed.
+        let error = negotiate_mode_client(&mut client, &[TransferMode::Messages]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
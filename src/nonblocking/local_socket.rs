@@ -5,9 +5,14 @@
 //! [blocking version of this module]: ../../local_socket/index.html " "
 
 use super::imports::*;
-use crate::local_socket::{self as sync, ToLocalSocketName};
+use crate::{
+    local_socket::{self as sync, ToLocalSocketName},
+    AsyncConnection,
+    Connection,
+    Sealed,
+};
 use std::{
-    io,
+    io::{self, prelude::*},
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
@@ -65,9 +70,9 @@ impl LocalSocketListener {
     /// [`incoming`]: #method.incoming " "
     pub async fn accept(&self) -> io::Result<LocalSocketStream> {
         let s = self.inner.clone();
-        Ok(LocalSocketStream {
-            inner: Unblock::new(unblock(move || s.accept()).await?),
-        })
+        Ok(LocalSocketStream::from_sync(
+            unblock(move || s.accept()).await?,
+        ))
     }
     /// Creates an infinite asynchronous stream which calls `accept()` with each
     /// iteration. Used together with [`for_each`]/[`try_for_each`] stream
@@ -108,9 +113,7 @@ impl Stream for Incoming {
         match poll {
             Poll::Ready(val) => {
                 let val = val.map(|val| match val {
-                    Ok(inner) => Ok(LocalSocketStream {
-                        inner: Unblock::new(inner),
-                    }),
+                    Ok(inner) => Ok(LocalSocketStream::from_sync(inner)),
                     Err(error) => Err(error),
                 });
                 Poll::Ready(val)
@@ -165,16 +168,59 @@ impl Iterator for SyncArcIncoming {
 /// [`LocalSocketListener`]: struct.LocalSocketListener.html " "
 #[derive(Debug)]
 pub struct LocalSocketStream {
-    inner: Unblock<sync::LocalSocketStream>,
+    inner:  Unblock<SharedStream>,
+    /// The same stream as the one in `inner`, for the operations which don't
+    /// block and thus don't have to wait for `inner` to give it back.
+    shared: Arc<sync::LocalSocketStream>,
 }
 impl LocalSocketStream {
     /// Connects to a remote local socket server.
     pub async fn connect<'a>(
         name: impl ToLocalSocketName<'a> + Send + 'static,
     ) -> io::Result<Self> {
-        Ok(Self {
-            inner: Unblock::new(unblock(move || sync::LocalSocketStream::connect(name)).await?),
-        })
+        let stream = unblock(move || sync::LocalSocketStream::connect(name)).await?;
+        Ok(Self::from_sync(stream))
+    }
+    fn from_sync(stream: sync::LocalSocketStream) -> Self {
+        let shared = Arc::new(stream);
+        Self {
+            inner: Unblock::new(SharedStream(Arc::clone(&shared))),
+            shared,
+        }
+    }
+}
+impl Sealed for LocalSocketStream {
+}
+impl AsyncConnection for LocalSocketStream {
+    fn peer_process_id(&self) -> io::Result<u32> {
+        self.shared.peer_process_id()
+    }
+    fn shutdown(&self) -> io::Result<()> {
+        self.shared.shutdown()
+    }
+    fn is_connected(&self) -> io::Result<bool> {
+        self.shared.is_connected()
+    }
+    fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.shared.peek(buf)
+    }
+}
+
+/// A stream which the blocking thread pool can read from and write to while
+/// the `LocalSocketStream` keeps using it as well.
+#[derive(Debug)]
+struct SharedStream(Arc<sync::LocalSocketStream>);
+impl Read for SharedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self.0).read(buf)
+    }
+}
+impl Write for SharedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self.0).write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self.0).flush()
     }
 }
 
@@ -41,6 +41,8 @@ cfg_if! {
             AF_UNIX, SOCK_STREAM, SOCK_DGRAM, SOL_SOCKET,
             O_NONBLOCK, F_GETFL, F_SETFL,
             SHUT_RD, SHUT_WR, SHUT_RDWR,
+            MSG_TRUNC, MSG_CTRUNC,
+            SO_RCVTIMEO, SO_SNDTIMEO, SO_ERROR, timeval,
         };
     } else {
         pub struct sockaddr_un {}
@@ -1,10 +1,17 @@
 use super::udsocket::{UdSocketPath, UdStream, UdStreamListener};
-use crate::local_socket::{LocalSocketName, NameTypeSupport, ToLocalSocketName};
+use crate::{
+    local_socket::{Availability, InputState, LocalSocketName, NameTypeSupport, ToLocalSocketName},
+    unsupported,
+    OverflowPolicy,
+};
 use std::{
     borrow::Cow,
     ffi::{CStr, CString, OsStr, OsString},
     fmt::{self, Debug, Formatter},
+    fs::File,
     io::{self, prelude::*, IoSlice, IoSliceMut},
+    net::Shutdown,
+    num::NonZeroUsize,
     os::unix::{
         ffi::{OsStrExt, OsStringExt},
         io::{AsRawFd, FromRawFd, IntoRawFd},
@@ -20,6 +27,15 @@ impl LocalSocketListener {
         let inner = UdStreamListener::bind(path)?;
         Ok(Self { inner })
     }
+    pub fn bind_with_limit<'a>(
+        name: impl ToLocalSocketName<'a>,
+        max_connections: NonZeroUsize,
+        overflow_policy: OverflowPolicy,
+    ) -> io::Result<Self> {
+        let path = local_socket_name_to_ud_socket_path(name.to_local_socket_name()?)?;
+        let inner = UdStreamListener::bind_with_limit(path, max_connections, overflow_policy)?;
+        Ok(Self { inner })
+    }
     pub fn accept(&self) -> io::Result<LocalSocketStream> {
         let inner = self.inner.accept()?;
         Ok(LocalSocketStream { inner })
@@ -27,6 +43,20 @@ impl LocalSocketListener {
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         self.inner.set_nonblocking(nonblocking)
     }
+    /// Makes the calls to `accept` which are waiting for a client fail, by
+    /// shutting down the receiving half of the socket.
+    pub fn wake_accepts(&self) {
+        // Nothing more can be done about it failing, which some systems do for
+        // listening sockets.
+        let _ = unsafe { libc::shutdown(self.inner.as_raw_fd(), libc::SHUT_RD) };
+    }
+    pub fn into_inheritable_fd(self) -> io::Result<i32> {
+        self.inner.into_inheritable_fd()
+    }
+    pub unsafe fn from_inherited_fd(fd: i32) -> io::Result<Self> {
+        let inner = unsafe { UdStreamListener::from_inherited_fd(fd) }?;
+        Ok(Self { inner })
+    }
 }
 impl Debug for LocalSocketListener {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -62,6 +92,11 @@ impl LocalSocketStream {
         let inner = UdStream::connect(path)?;
         Ok(Self { inner })
     }
+    pub fn connect_immediate<'a>(name: impl ToLocalSocketName<'a>) -> io::Result<Self> {
+        let path = local_socket_name_to_ud_socket_path(name.to_local_socket_name()?)?;
+        let inner = UdStream::connect_immediate(path)?;
+        Ok(Self { inner })
+    }
     pub fn peer_pid(&self) -> io::Result<u32> {
         #[cfg(uds_peercred)]
         {
@@ -69,12 +104,60 @@ impl LocalSocketStream {
         }
         #[cfg(not(uds_peercred))]
         {
-            Err(io::Error::new(io::ErrorKind::Other, "not supported"))
+            Err(unsupported(
+                "the system does not report the credentials of the peer",
+            ))
         }
     }
+    pub fn read_chunks(
+        &mut self,
+        chunk_size: usize,
+        f: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> io::Result<u64> {
+        self.inner.read_chunks(chunk_size, f)
+    }
+    pub fn splice_to_file(
+        &mut self,
+        file: &File,
+        count: usize,
+        progress: &mut dyn FnMut(),
+    ) -> io::Result<usize> {
+        self.inner.splice_to_file_with_progress(file, count, progress)
+    }
+    pub fn server_instance_count(&self) -> io::Result<u32> {
+        Err(unsupported(
+            "Unix domain sockets do not expose the load of the server",
+        ))
+    }
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         self.inner.set_nonblocking(nonblocking)
     }
+    pub fn input_state(&self) -> io::Result<InputState> {
+        match self.peek(&mut [0]) {
+            Ok(0) => Ok(InputState::Disconnected),
+            Ok(..) => Ok(InputState::Pending),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(InputState::Empty),
+            Err(e) => Err(e),
+        }
+    }
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let received = unsafe {
+            libc::recv(
+                self.as_raw_fd(),
+                buf.as_mut_ptr() as *mut _,
+                buf.len(),
+                libc::MSG_PEEK | libc::MSG_DONTWAIT,
+            )
+        };
+        if received == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(received as usize)
+        }
+    }
+    pub fn shutdown(&self) -> io::Result<()> {
+        self.inner.shutdown(Shutdown::Both)
+    }
 }
 impl Read for LocalSocketStream {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
@@ -95,6 +178,25 @@ impl Write for LocalSocketStream {
         self.inner.flush()
     }
 }
+impl Read for &LocalSocketStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.recv(buf)
+    }
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        self.inner.recv_vectored(bufs)
+    }
+}
+impl Write for &LocalSocketStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.send(buf)
+    }
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.inner.send_vectored(bufs)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
 impl Debug for LocalSocketStream {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("LocalSocketStream")
@@ -120,6 +222,28 @@ impl FromRawFd for LocalSocketStream {
     }
 }
 
+/// What a `CancelToken` shuts down to cancel I/O on a stream.
+#[derive(Debug)]
+pub(crate) struct CancelTarget(i32);
+impl CancelTarget {
+    pub fn new(stream: &LocalSocketStream) -> Self {
+        Self(stream.as_raw_fd())
+    }
+    /// Shuts the socket down in both directions, which makes blocked reads
+    /// return end of file and blocked writes fail.
+    pub fn cancel(&self) -> io::Result<()> {
+        let success = unsafe { libc::shutdown(self.0, libc::SHUT_RDWR) } != -1;
+        let error = io::Error::last_os_error();
+        // The other side may have already closed the connection, leaving nothing
+        // to shut down.
+        if success || error.raw_os_error() == Some(libc::ENOTCONN) {
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+}
+
 fn local_socket_name_to_ud_socket_path(name: LocalSocketName<'_>) -> io::Result<UdSocketPath<'_>> {
     fn cow_osstr_to_cstr(osstr: Cow<'_, OsStr>) -> io::Result<Cow<'_, CStr>> {
         match osstr {
@@ -148,6 +272,27 @@ fn local_socket_name_to_ud_socket_path(name: LocalSocketName<'_>) -> io::Result<
     )?))
 }
 
+pub fn check_availability(name: &LocalSocketName<'_>) -> io::Result<Availability> {
+    let namespaced = name.is_namespaced();
+    let path = local_socket_name_to_ud_socket_path(LocalSocketName::from_raw_parts(
+        Cow::Borrowed(name.inner()),
+        namespaced,
+    ))?;
+    let error = match UdStream::connect_immediate(path) {
+        Ok(..) => return Ok(Availability::InUseByLiveServer),
+        Err(e) => e,
+    };
+    match error.kind() {
+        // The backlog is full.
+        io::ErrorKind::AddrInUse => Ok(Availability::InUseByLiveServer),
+        io::ErrorKind::NotFound => Ok(Availability::Free),
+        // Unbound namespaced names are refused rather than not found.
+        io::ErrorKind::ConnectionRefused if namespaced => Ok(Availability::Free),
+        io::ErrorKind::ConnectionRefused => Ok(Availability::StaleFile),
+        _ => Err(error),
+    }
+}
+
 pub fn name_type_support_query() -> NameTypeSupport {
     NAME_TYPE_ALWAYS_SUPPORTED
 }
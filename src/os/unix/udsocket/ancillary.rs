@@ -2,8 +2,11 @@ use super::imports::*;
 use cfg_if::cfg_if;
 use std::{
     borrow::Cow,
+    fmt::{self, Debug, Formatter},
     iter::{FromIterator, FusedIterator},
-    mem::size_of,
+    mem::{size_of, zeroed},
+    ptr,
+    slice,
 };
 
 /// Ancillary data to be sent through a Unix domain socket or read from an input
@@ -293,6 +296,177 @@ impl<'a> AsMut<[u8]> for AncillaryDataBuf<'a> {
     }
 }
 
+/// A reusable, correctly aligned buffer for receiving ancillary data with
+/// [`recv_with_ancillary_into`].
+///
+/// Control messages consist of headers which the system accesses as
+/// `cmsghdr` structures, so the buffer they are received into has to be
+/// aligned like `cmsghdr` — usually to the size of a pointer — and every
+/// message within it is padded to that alignment as well. Byte buffers like
+/// the ones used by [`AncillaryDataBuf`] only have the alignment of `u8`, which
+/// is why this buffer allocates its storage as an array of `cmsghdr` instead,
+/// once, and reuses it for every call. The capacity should be calculated with
+/// [`for_file_descriptors`] or [`CMSG_SPACE`] rather than by adding up the
+/// sizes of the payloads, since those do not account for padding.
+///
+/// [`recv_with_ancillary_into`]: struct.UdStream.html#method.recv_with_ancillary_into " "
+/// [`AncillaryDataBuf`]: enum.AncillaryDataBuf.html " "
+/// [`for_file_descriptors`]: #method.for_file_descriptors " "
+/// [`CMSG_SPACE`]: https://man7.org/linux/man-pages/man3/cmsg.3.html " "
+pub struct AncillaryBuffer {
+    storage:   Vec<cmsghdr>,
+    len:       usize,
+    truncated: bool,
+}
+impl AncillaryBuffer {
+    /// Creates a buffer which can hold at least the specified amount of bytes
+    /// of ancillary data. The capacity is rounded up to a multiple of the size
+    /// of `cmsghdr`.
+    pub fn with_capacity(capacity: usize) -> Self {
+        // div_ceil is too new for the supported compilers.
+        #[allow(clippy::manual_div_ceil)]
+        let elements = (capacity + size_of::<cmsghdr>() - 1) / size_of::<cmsghdr>();
+        let storage = (0..elements)
+            .map(|_| unsafe {
+                // SAFETY: cmsghdr is plain old data, i.e. an all-zero pattern is allowed
+                zeroed()
+            })
+            .collect();
+        Self {
+            storage,
+            len: 0,
+            truncated: false,
+        }
+    }
+    /// Creates a buffer which can hold one control message with the specified
+    /// amount of file descriptors, including all padding.
+    ///
+    /// On platforms where sockets created by this crate have credential
+    /// passing enabled, such as Linux, the system attaches the credentials of
+    /// the sender to every message, so room for those is included too.
+    pub fn for_file_descriptors(num_descriptors: usize) -> Self {
+        let payload_size = num_descriptors * size_of::<c_int>();
+        let mut capacity = unsafe { libc::CMSG_SPACE(payload_size as _) } as usize;
+        #[cfg(uds_scm_credentials)]
+        {
+            capacity += unsafe { libc::CMSG_SPACE(size_of::<ucred>() as _) } as usize;
+        }
+        Self::with_capacity(capacity)
+    }
+    /// Returns the amount of bytes of ancillary data which the buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.storage.len() * size_of::<cmsghdr>()
+    }
+    /// Returns the ancillary data which was received by the last call, in the
+    /// Unix ancillary data format.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            // SAFETY: the length never exceeds the capacity, and the storage is plain
+            // old data
+            slice::from_raw_parts(self.storage.as_ptr() as *const u8, self.len)
+        }
+    }
+    /// Returns `true` if the ancillary data received by the last call did not
+    /// fit into the buffer and was cut off. File descriptors which were cut
+    /// off are closed by the system and are lost.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+    /// Creates a decoder which decodes the received ancillary data, like
+    /// [`AncillaryDataBuf::decode`]. Unlike receiving itself, decoding
+    /// allocates for every file descriptor message.
+    ///
+    /// [`AncillaryDataBuf::decode`]: enum.AncillaryDataBuf.html#method.decode " "
+    pub fn decode(&self) -> AncillaryDataDecoder<'_> {
+        AncillaryDataDecoder {
+            buffer: self.as_bytes(),
+            i:      0,
+        }
+    }
+    /// Forgets the received ancillary data, without deallocating the buffer.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.truncated = false;
+    }
+
+    pub(super) fn storage_mut(&mut self) -> &mut [u8] {
+        let capacity = self.capacity();
+        unsafe {
+            // SAFETY: as above, and the storage is borrowed mutably
+            slice::from_raw_parts_mut(self.storage.as_mut_ptr() as *mut u8, capacity)
+        }
+    }
+    pub(super) fn set_received(&mut self, len: usize, truncated: bool) {
+        self.len = len.min(self.capacity());
+        self.truncated = truncated;
+    }
+    /// Appends the file descriptors from all `SCM_RIGHTS` messages in the
+    /// received data to the vector.
+    pub(super) fn extract_file_descriptors(&mut self, fds_out: &mut Vec<c_int>) {
+        self.for_each_message(|level, ty, data, data_len| {
+            if level == SOL_SOCKET && ty == SCM_RIGHTS {
+                let data = data as *const c_int;
+                let amount = data_len / size_of::<c_int>();
+                fds_out.reserve(amount);
+                for i in 0..amount {
+                    // SAFETY: the data is within the received part of the buffer, but is
+                    // only guaranteed to be aligned for cmsghdr, not for c_int
+                    fds_out.push(unsafe { ptr::read_unaligned(data.add(i)) });
+                }
+            }
+        });
+    }
+    /// Returns the credentials from the last `SCM_CREDENTIALS` message in the
+    /// received data, if there is one.
+    #[cfg(uds_scm_credentials)]
+    pub(super) fn extract_credentials(&mut self) -> Option<ucred> {
+        let mut credentials = None;
+        self.for_each_message(|level, ty, data, data_len| {
+            if level == SOL_SOCKET && ty == SCM_CREDENTIALS && data_len >= size_of::<ucred>() {
+                // SAFETY: as above
+                credentials = Some(unsafe { ptr::read_unaligned(data as *const ucred) });
+            }
+        });
+        credentials
+    }
+    /// Calls the closure with the level, type, data pointer and data length of
+    /// every message in the received data, walking the messages with the
+    /// `CMSG_*` macros to account for padding.
+    fn for_each_message(&mut self, mut f: impl FnMut(c_int, c_int, *const u8, usize)) {
+        let mut hdr: msghdr = unsafe {
+            // SAFETY: msghdr is plain old data, i.e. an all-zero pattern is allowed
+            zeroed()
+        };
+        hdr.msg_control = self.storage.as_mut_ptr() as *mut _;
+        hdr.msg_controllen = self.len as _;
+        let data_offset = unsafe { libc::CMSG_LEN(0) } as usize;
+        let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&hdr) };
+        while !cmsg.is_null() {
+            let (level, ty, len) = unsafe {
+                (
+                    (*cmsg).cmsg_level,
+                    (*cmsg).cmsg_type,
+                    (*cmsg).cmsg_len as usize,
+                )
+            };
+            if len > data_offset {
+                let data = unsafe { libc::CMSG_DATA(cmsg) } as *const u8;
+                f(level, ty, data, len - data_offset);
+            }
+            cmsg = unsafe { libc::CMSG_NXTHDR(&hdr, cmsg) };
+        }
+    }
+}
+impl Debug for AncillaryBuffer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AncillaryBuffer")
+            .field("capacity", &self.capacity())
+            .field("len", &self.len)
+            .field("truncated", &self.truncated)
+            .finish()
+    }
+}
+
 /// An iterator which decodes ancillary data from an ancillary data buffer.
 ///
 /// This iterator is created by the [`decode`] method on [`AncillaryDataBuf`] —
@@ -1,15 +1,23 @@
 use super::{
     super::{close_by_error, handle_fd_error},
     imports::*,
-    util::{enable_passcred, raw_get_nonblocking, raw_set_nonblocking},
+    util::{enable_passcred, raw_get_nonblocking, raw_set_cloexec, raw_set_nonblocking},
     ToUdSocketPath,
     UdStream,
 };
+use crate::OverflowPolicy;
 use std::{
     fmt::{self, Debug, Formatter},
     io,
     iter::FusedIterator,
     mem::{size_of, zeroed},
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc,
+        Condvar,
+        Mutex,
+    },
 };
 use to_method::To;
 
@@ -143,7 +151,9 @@ use to_method::To;
 /// # Ok(()) }
 /// ```
 pub struct UdStreamListener {
-    fd: FdOps,
+    fd:                  FdOps,
+    limit:               Option<Arc<ConnectionLimit>>,
+    inherit_nonblocking: AtomicBool,
 }
 impl UdStreamListener {
     /// Creates a new listener socket at the specified address.
@@ -154,6 +164,10 @@ impl UdStreamListener {
     /// errors should always be handled regardless of whether the path is known
     /// to be short enough or not.
     ///
+    /// If the system refuses to bind the socket, the error it reported is
+    /// returned unchanged, so its `raw_os_error` can be matched against codes
+    /// such as `EADDRINUSE`; it does not include the attempted path.
+    ///
     /// # Example
     /// See [`ToUdSocketPath`].
     ///
@@ -209,6 +223,86 @@ impl UdStreamListener {
             Self::from_raw_fd(socket)
         })
     }
+    /// Creates a new listener socket at the specified address, labeled with
+    /// the specified SELinux security context.
+    ///
+    /// Under an enforcing SELinux policy, whether clients are allowed to
+    /// connect to a socket can depend on its label, which is normally derived
+    /// from the label of the server process. This sets the socket creation
+    /// context of the current thread (`setsockcreatecon`) to `context`, such
+    /// as `system_u:object_r:my_service_socket_t:s0`, for the duration of the
+    /// call, and then restores the previous one. The previous context is
+    /// restored even if binding fails, in which case the binding error is
+    /// returned; if restoring the context fails, the listener is closed, its
+    /// socket file is removed, and the error of restoring is returned instead,
+    /// since the thread would otherwise keep labeling every socket which it
+    /// creates afterwards.
+    ///
+    /// Only available with the `selinux` feature, which links to libselinux.
+    ///
+    /// # System calls
+    /// - `getsockcreatecon` (libselinux)
+    /// - `setsockcreatecon` (libselinux)
+    /// - `socket`
+    /// - `bind`
+    /// - `unlink`, if restoring the context fails
+    #[cfg(any(doc, all(target_os = "linux", feature = "selinux")))]
+    #[cfg_attr(
+        feature = "doc_cfg",
+        doc(cfg(all(target_os = "linux", feature = "selinux")))
+    )]
+    pub fn bind_with_label<'a>(
+        path: impl ToUdSocketPath<'a>,
+        context: &std::ffi::CStr,
+    ) -> io::Result<Self> {
+        let path = path.to_socket_path()?;
+        // Namespaced sockets disappear along with the listener.
+        let file = match &path {
+            super::UdSocketPath::File(file) => Some(file.clone().into_owned()),
+            _ => None,
+        };
+        super::selinux::with_socket_creation_context(
+            context,
+            || Self::bind(path),
+            |listener| {
+                drop(listener);
+                if let Some(file) = file {
+                    unsafe { libc::unlink(file.as_ptr()) };
+                }
+            },
+        )
+    }
+    /// Creates a new listener socket at the specified address which serves at
+    /// most `max_connections` clients at a time.
+    ///
+    /// Every stream produced by the listener occupies a place until it's
+    /// dropped or converted into a raw file descriptor. Clients which arrive
+    /// while all places are occupied are handled according to the specified
+    /// [overflow policy] — see its documentation for what those clients
+    /// observe in each case.
+    ///
+    /// Apart from that, this behaves exactly like [`bind`].
+    ///
+    /// # System calls
+    /// - `socket`
+    /// - `bind`
+    ///
+    /// [overflow policy]: ../../../enum.OverflowPolicy.html " "
+    /// [`bind`]: #method.bind " "
+    pub fn bind_with_limit<'a>(
+        path: impl ToUdSocketPath<'a>,
+        max_connections: NonZeroUsize,
+        overflow_policy: OverflowPolicy,
+    ) -> io::Result<Self> {
+        let mut listener = Self::bind(path)?;
+        listener.limit = Some(Arc::new(ConnectionLimit {
+            max:     max_connections.get(),
+            policy:  overflow_policy,
+            active:  Mutex::new(0),
+            vacated: Condvar::new(),
+        }));
+        Ok(listener)
+    }
 
     /// Listens for incoming connections to the socket, blocking until a client
     /// is connected.
@@ -216,6 +310,18 @@ impl UdStreamListener {
     /// See [`incoming`] for a convenient way to create a main loop for a
     /// server.
     ///
+    /// If the listener was created with a connection limit using
+    /// [`bind_with_limit`], clients which do not fit into the limit are handled
+    /// according to its overflow policy: with `Queue`, the call waits until a
+    /// place frees up before accepting (or fails with [`WouldBlock`] in
+    /// nonblocking mode), and with `Reject`, excess clients are closed right
+    /// away and the call keeps waiting for one which fits.
+    ///
+    /// Accepting doesn't allocate: the address of the client is not retrieved,
+    /// since it's of no use for Unix domain sockets, so there is no buffer to
+    /// fill out, let alone one which concurrent calls from several threads
+    /// would have to share.
+    ///
     /// # Example
     /// ```no_run
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -238,20 +344,191 @@ impl UdStreamListener {
     /// ```
     ///
     /// # System calls
-    /// - `accept`
+    /// - `accept4` on Linux, Android, FreeBSD, DragonflyBSD, NetBSD and
+    ///   OpenBSD, `accept` elsewhere
+    /// - `fcntl`, to query and set nonblocking mode where necessary
     ///
     /// [`incoming`]: #method.incoming " "
+    /// [`bind_with_limit`]: #method.bind_with_limit " "
+    /// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock " "
     pub fn accept(&self) -> io::Result<UdStream> {
+        let limit = match &self.limit {
+            Some(limit) => limit,
+            None => return self.accept_unlimited(),
+        };
+        match limit.policy {
+            OverflowPolicy::Queue => {
+                let slot = match ConnectionLimit::try_occupy(limit) {
+                    Some(slot) => slot,
+                    None if self.is_nonblocking()? => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::WouldBlock,
+                            "the connection limit has been reached",
+                        ))
+                    },
+                    None => ConnectionLimit::occupy(limit),
+                };
+                // If accepting fails, the slot is dropped and thus vacated again.
+                let mut stream = self.accept_unlimited()?;
+                stream.slot = Some(slot);
+                Ok(stream)
+            },
+            OverflowPolicy::Reject => loop {
+                let mut stream = self.accept_unlimited()?;
+                if let Some(slot) = ConnectionLimit::try_occupy(limit) {
+                    stream.slot = Some(slot);
+                    break Ok(stream);
+                }
+                // Dropping the stream closes the connection, which is the
+                // signal for the client to go elsewhere.
+            },
+        }
+    }
+    fn accept_unlimited(&self) -> io::Result<UdStream> {
+        let nonblocking = self.inherit_nonblocking.load(Relaxed) && self.is_nonblocking()?;
+        let fd = self.raw_accept(nonblocking)?;
+        Ok(unsafe {
+            // SAFETY: we just created the file descriptor, meaning that it's guaranteeed
+            // not to be used elsewhere
+            UdStream::from_raw_fd(fd)
+        })
+    }
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd",
+    ))]
+    fn raw_accept(&self, nonblocking: bool) -> io::Result<c_int> {
+        let flags = if nonblocking { libc::SOCK_NONBLOCK } else { 0 };
+        let (success, fd) = unsafe {
+            let result = libc::accept4(self.as_raw_fd(), zeroed(), zeroed(), flags);
+            (result != -1, result)
+        };
+        if success {
+            Ok(fd)
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd",
+    )))]
+    fn raw_accept(&self, nonblocking: bool) -> io::Result<c_int> {
         let (success, fd) = unsafe {
             let result = libc::accept(self.as_raw_fd(), zeroed(), zeroed());
             (result != -1, result)
         };
+        if !success {
+            return Err(io::Error::last_os_error());
+        }
+        // Without accept4, whether the new socket inherits the nonblocking mode of the
+        // listener differs between platforms, so it's set explicitly either way.
+        unsafe { raw_set_nonblocking(fd, nonblocking).map_err(close_by_error(fd))? };
+        Ok(fd)
+    }
+
+    /// Accepts up to `max` connections in one call: blocks until a client is
+    /// connected, like [`accept`], and then accepts the connections which are
+    /// already waiting in the backlog without blocking again, returning however
+    /// many were ready.
+    ///
+    /// There is no system call which accepts several connections at once, so
+    /// this does not reduce the amount of system calls, but it does let servers
+    /// which face bursts of connections drain the backlog in a tight loop and
+    /// hand the whole batch off at once, amortizing the per-connection overhead
+    /// of whatever happens after accepting. In nonblocking mode, the first
+    /// connection isn't waited for either: a [`WouldBlock`] error is returned
+    /// if there are no connections waiting at all.
+    ///
+    /// Connection limits are respected: with the `Queue` policy, the batch ends
+    /// once the limit is reached (after waiting for a place for the first
+    /// connection, if needed), and with `Reject`, excess clients are closed as
+    /// usual. If accepting fails after the first connection, the batch ends
+    /// early and the connections accepted so far are returned, leaving the
+    /// error for the next call to run into. Returns an empty batch right away
+    /// if `max` is zero.
+    ///
+    /// Whether a connection is waiting is checked right before accepting it, so
+    /// if other threads accept on the same listener at the same time and take
+    /// the connection first, the call may end up waiting for the next client in
+    /// blocking mode.
+    ///
+    /// # System calls
+    /// - `accept4` on Linux, Android, FreeBSD, DragonflyBSD, NetBSD and
+    ///   OpenBSD, `accept` elsewhere
+    /// - `poll`
+    /// - `fcntl`, to query and set nonblocking mode where necessary
+    ///
+    /// [`accept`]: #method.accept " "
+    /// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock " "
+    pub fn accept_batch(&self, max: usize) -> io::Result<Vec<UdStream>> {
+        let mut batch = Vec::new();
+        if max == 0 {
+            return Ok(batch);
+        }
+        batch.push(self.accept()?);
+        while batch.len() < max {
+            match self.accept_pending() {
+                Ok(Some(stream)) => batch.push(stream),
+                Ok(None) | Err(..) => break,
+            }
+        }
+        Ok(batch)
+    }
+    /// Accepts a connection if one is already waiting in the backlog and there
+    /// is room for it in the connection limit, without blocking.
+    fn accept_pending(&self) -> io::Result<Option<UdStream>> {
+        loop {
+            if !self.has_pending_connection()? {
+                return Ok(None);
+            }
+            let queue_slot = match &self.limit {
+                Some(limit) if limit.policy == OverflowPolicy::Queue => {
+                    match ConnectionLimit::try_occupy(limit) {
+                        Some(slot) => Some(slot),
+                        None => return Ok(None),
+                    }
+                },
+                _ => None,
+            };
+            let mut stream = match self.accept_unlimited() {
+                Ok(stream) => stream,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            stream.slot = match &self.limit {
+                Some(limit) if limit.policy == OverflowPolicy::Reject => {
+                    match ConnectionLimit::try_occupy(limit) {
+                        Some(slot) => Some(slot),
+                        // Closed by dropping, like in accept.
+                        None => continue,
+                    }
+                },
+                _ => queue_slot,
+            };
+            return Ok(Some(stream));
+        }
+    }
+    fn has_pending_connection(&self) -> io::Result<bool> {
+        let mut fd = libc::pollfd {
+            fd:      self.as_raw_fd(),
+            events:  libc::POLLIN,
+            revents: 0,
+        };
+        let (success, ready) = unsafe {
+            let result = libc::poll(&mut fd as *mut _, 1, 0);
+            (result != -1, result)
+        };
         if success {
-            Ok(unsafe {
-                // SAFETY: we just created the file descriptor, meaning that it's guaranteeed
-                // not to be used elsewhere
-                UdStream::from_raw_fd(fd)
-            })
+            Ok(ready > 0 && fd.revents & libc::POLLIN != 0)
         } else {
             Err(io::Error::last_os_error())
         }
@@ -291,9 +568,14 @@ impl UdStreamListener {
     /// allowing the thread to perform other useful operations while there are
     /// no new client connections to accept.
     ///
+    /// The streams produced by the listener are not affected by this: they are
+    /// in blocking mode regardless of the mode of the listener, unless
+    /// [`set_inherit_nonblocking`] is used to change that.
+    ///
     /// [`accept`]: #method.accept " "
     /// [`incoming`]: #method.incoming " "
     /// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock " "
+    /// [`set_inherit_nonblocking`]: #method.set_inherit_nonblocking " "
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         unsafe { raw_set_nonblocking(self.fd.0, nonblocking) }
     }
@@ -301,6 +583,81 @@ impl UdStreamListener {
     pub fn is_nonblocking(&self) -> io::Result<bool> {
         unsafe { raw_get_nonblocking(self.fd.0) }
     }
+    /// Sets whether streams produced by [`accept`] and [`incoming`] are put in
+    /// the same nonblocking mode as the listener is in at the time of
+    /// accepting. By default, this is disabled, and accepted streams are always
+    /// in blocking mode.
+    ///
+    /// The operating systems themselves disagree on this matter — on Linux,
+    /// accepted sockets never inherit the nonblocking mode, while on BSD-like
+    /// systems, including macOS, they always do. The listener hides this
+    /// difference by creating accepted streams with the requested mode
+    /// explicitly, using the `SOCK_NONBLOCK` flag of `accept4` where it's
+    /// available and `fcntl` elsewhere, so that the behavior is the same on all
+    /// platforms.
+    ///
+    /// [`accept`]: #method.accept " "
+    /// [`incoming`]: #method.incoming " "
+    pub fn set_inherit_nonblocking(&self, inherit: bool) {
+        self.inherit_nonblocking.store(inherit, Relaxed);
+    }
+    /// Checks whether streams produced by the listener inherit its nonblocking
+    /// mode. See [`set_inherit_nonblocking`] for more.
+    ///
+    /// [`set_inherit_nonblocking`]: #method.set_inherit_nonblocking " "
+    pub fn inherits_nonblocking(&self) -> bool {
+        self.inherit_nonblocking.load(Relaxed)
+    }
+    /// Converts the listener into its file descriptor with the close-on-exec
+    /// flag cleared, so that it survives an `exec` and the new program image
+    /// can keep accepting connections on it using [`from_inherited_fd`].
+    ///
+    /// This is the foundation of hitless reloads: the server execs the new
+    /// version of itself, telling it the number of the file descriptor, for
+    /// example via an environment variable, and the new image picks up right
+    /// where the old one left off. The socket stays bound and listening for
+    /// the whole time, so clients which connect while the new image is
+    /// starting up simply wait in the backlog instead of being refused.
+    ///
+    /// The connection limit, if any, is not carried over, and neither is the
+    /// [nonblocking inheritance setting].
+    ///
+    /// # System calls
+    /// - `fcntl`
+    ///
+    /// [`from_inherited_fd`]: #method.from_inherited_fd " "
+    /// [nonblocking inheritance setting]: #method.set_inherit_nonblocking " "
+    pub fn into_inheritable_fd(self) -> io::Result<c_int> {
+        unsafe { raw_set_cloexec(self.fd.0, false) }?;
+        Ok(self.into_raw_fd())
+    }
+    /// Creates a listener from a file descriptor inherited across `exec`, as
+    /// produced by [`into_inheritable_fd`]. The close-on-exec flag is set
+    /// again, so that the socket does not leak into programs executed later.
+    ///
+    /// # Safety
+    /// The file descriptor must be a listening Unix domain socket of the
+    /// stream type, and must not be owned by anything else.
+    ///
+    /// # System calls
+    /// - `fcntl`
+    ///
+    /// [`into_inheritable_fd`]: #method.into_inheritable_fd " "
+    pub unsafe fn from_inherited_fd(fd: c_int) -> io::Result<Self> {
+        // Taking ownership first, so that the descriptor is closed on failure.
+        let listener = unsafe { Self::from_raw_fd(fd) };
+        unsafe { raw_set_cloexec(fd, true) }?;
+        Ok(listener)
+    }
+    /// Consumes the listener and returns its file descriptor without closing
+    /// it, the same way as `into_raw_fd` does. Closing the file descriptor
+    /// becomes the caller's responsibility — see [`UdStream::forget`] for
+    /// details.
+    ///
+    /// [`UdStream::forget`]: struct.UdStream.html#method.forget " "
+    pub fn forget(self) -> c_int {
+        self.into_raw_fd()
+    }
 }
 impl Debug for UdStreamListener {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -313,6 +670,7 @@ impl AsRawFd for UdStreamListener {
         self.fd.as_raw_fd()
     }
 }
+impl_as_borrowed_handle!(UdStreamListener);
 #[cfg(unix)]
 impl IntoRawFd for UdStreamListener {
     fn into_raw_fd(self) -> c_int {
@@ -322,7 +680,48 @@ impl IntoRawFd for UdStreamListener {
 #[cfg(unix)]
 impl FromRawFd for UdStreamListener {
     unsafe fn from_raw_fd(fd: c_int) -> Self {
-        Self { fd: FdOps::new(fd) }
+        Self {
+            fd:                  FdOps::new(fd),
+            limit:               None,
+            inherit_nonblocking: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Bookkeeping for a listener created with `bind_with_limit`.
+struct ConnectionLimit {
+    max:     usize,
+    policy:  OverflowPolicy,
+    active:  Mutex<usize>,
+    vacated: Condvar,
+}
+impl ConnectionLimit {
+    fn try_occupy(self_: &Arc<Self>) -> Option<ConnectionSlot> {
+        let mut active = self_.active.lock().expect("unexpected lock poison");
+        if *active < self_.max {
+            *active += 1;
+            Some(ConnectionSlot(Arc::clone(self_)))
+        } else {
+            None
+        }
+    }
+    fn occupy(self_: &Arc<Self>) -> ConnectionSlot {
+        let mut active = self_.active.lock().expect("unexpected lock poison");
+        while *active >= self_.max {
+            active = self_.vacated.wait(active).expect("unexpected lock poison");
+        }
+        *active += 1;
+        ConnectionSlot(Arc::clone(self_))
+    }
+}
+
+/// A place taken up by a stream in the connection limit of the listener which
+/// produced it, vacated when dropped.
+pub(super) struct ConnectionSlot(Arc<ConnectionLimit>);
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        *self.0.active.lock().expect("unexpected lock poison") -= 1;
+        self.0.vacated.notify_one();
     }
 }
 
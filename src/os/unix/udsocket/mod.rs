@@ -27,6 +27,8 @@
 mod ancillary;
 mod listener;
 mod path;
+#[cfg(any(doc, all(target_os = "linux", feature = "selinux")))]
+mod selinux;
 mod socket;
 mod stream;
 mod util;
@@ -93,3 +95,43 @@ platform if you wish to enable Unix domain socket support for it"
 // separately. This way, the user-faced constant has only one definition and one
 // documentation comment block.
 pub const MAX_UDSOCKET_PATH_LEN: usize = _MAX_UDSOCKET_PATH_LEN;
+
+/// Returns the credentials of the current process in the same form as the
+/// credentials of the peer returned by `get_peer_credentials` on [`UdStream`]
+/// and [`UdSocket`], allowing the two to be compared directly.
+///
+/// The effective user and group identifiers are used, since those are the ones
+/// which the system records for the peer when a connection is established.
+///
+/// # System calls
+/// - `getpid`
+/// - `geteuid`
+/// - `getegid`
+///
+/// [`UdStream`]: struct.UdStream.html " "
+/// [`UdSocket`]: struct.UdSocket.html " "
+#[cfg(any(doc, uds_peercred))]
+#[cfg_attr( // uds_peercred template
+    feature = "doc_cfg",
+    doc(cfg(any(
+        all(
+            target_os = "linux",
+            any(
+                target_env = "gnu",
+                target_env = "musl",
+                target_env = "musleabi",
+                target_env = "musleabihf"
+            )
+        ),
+        target_os = "emscripten",
+        target_os = "redox",
+        target_os = "haiku"
+    )))
+)]
+pub fn own_credentials() -> imports::ucred {
+    imports::ucred {
+        pid: unsafe { libc::getpid() },
+        uid: unsafe { libc::geteuid() },
+        gid: unsafe { libc::getegid() },
+    }
+}
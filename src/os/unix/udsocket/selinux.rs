@@ -0,0 +1,93 @@
+use libc::{c_char, c_int};
+use std::{
+    ffi::{CStr, CString},
+    io,
+    ptr,
+};
+
+#[link(name = "selinux")]
+extern "C" {
+    fn getsockcreatecon(context: *mut *mut c_char) -> c_int;
+    fn setsockcreatecon(context: *const c_char) -> c_int;
+    fn freecon(context: *mut c_char);
+    #[cfg(test)]
+    fn is_selinux_enabled() -> c_int;
+    #[cfg(test)]
+    fn getcon(context: *mut *mut c_char) -> c_int;
+}
+
+/// Takes ownership of a context which libselinux has allocated.
+///
+/// # Safety
+/// The pointer must be a non-null context returned by libselinux.
+unsafe fn take_context(context: *mut c_char) -> CString {
+    let owned = unsafe {
+        // SAFETY: libselinux has allocated a nul-terminated string for us
+        CStr::from_ptr(context)
+    }
+    .to_owned();
+    unsafe { freecon(context) };
+    owned
+}
+
+/// Returns the security context which sockets created by the current thread
+/// are labeled with, or `None` if it's the default one.
+pub(super) fn get_socket_creation_context() -> io::Result<Option<CString>> {
+    let mut context: *mut c_char = ptr::null_mut();
+    let success = unsafe { getsockcreatecon(&mut context as *mut _) } != -1;
+    if !success {
+        return Err(io::Error::last_os_error());
+    }
+    if context.is_null() {
+        return Ok(None);
+    }
+    Ok(Some(unsafe { take_context(context) }))
+}
+/// Sets the security context which sockets created by the current thread are
+/// labeled with, `None` meaning the default one.
+fn set_socket_creation_context(context: Option<&CStr>) -> io::Result<()> {
+    let context = context.map_or(ptr::null(), CStr::as_ptr);
+    let success = unsafe { setsockcreatecon(context) } != -1;
+    if success {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Runs `f` with the socket creation context of the current thread set to the
+/// specified one, restoring the previous context afterwards, regardless of
+/// whether `f` succeeded. If the context cannot be restored, the failure to do
+/// so is returned instead of the result of `f`, which is passed to `undo` to be
+/// disposed of if `f` succeeded.
+pub(super) fn with_socket_creation_context<T>(
+    context: &CStr,
+    f: impl FnOnce() -> io::Result<T>,
+    undo: impl FnOnce(T),
+) -> io::Result<T> {
+    let previous = get_socket_creation_context()?;
+    set_socket_creation_context(Some(context))?;
+    let result = f();
+    if let Err(e) = set_socket_creation_context(previous.as_deref()) {
+        if let Ok(created) = result {
+            undo(created);
+        }
+        return Err(e);
+    }
+    result
+}
+
+/// Returns the security context of the current process, or `None` if SELinux
+/// is disabled.
+#[cfg(test)]
+pub(super) fn current_context() -> io::Result<Option<CString>> {
+    if unsafe { is_selinux_enabled() } != 1 {
+        return Ok(None);
+    }
+    let mut context: *mut c_char = ptr::null_mut();
+    let success = unsafe { getcon(&mut context as *mut _) } != -1;
+    if !success {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Some(unsafe { take_context(context) }))
+}
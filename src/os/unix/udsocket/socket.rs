@@ -1,5 +1,7 @@
 #[cfg(uds_peercred)]
 use super::util::get_peer_ucred;
+#[cfg(uds_scm_credentials)]
+use super::AncillaryBuffer;
 use super::{
     super::{close_by_error, handle_fd_error},
     imports::*,
@@ -22,7 +24,6 @@ use crate::{ReliableReadMsg, Sealed};
 use std::{
     fmt::{self, Debug, Formatter},
     io::{self, IoSlice, IoSliceMut},
-    iter,
     mem::{size_of, size_of_val, zeroed},
 };
 use to_method::To;
@@ -135,64 +136,54 @@ impl UdSocket {
         Ok(unsafe { Self::from_raw_fd(socket) })
     }
 
-    fn add_fake_trunc_flag(x: usize) -> (usize, bool) {
-        (x, false)
-    }
-
-    /// Receives a single datagram from the socket, returning the size of the
-    /// received datagram.
+    /// Receives a single datagram from the socket, returning how many bytes of
+    /// it were copied into the buffer and whether a part of it was discarded
+    /// because the buffer was too small.
     ///
-    /// *Note: there is an additional meaningless boolean return value which is
-    /// always `false`. It used to signify whether the datagram was truncated or
-    /// not, but the functionality was implemented incorrectly and only on
-    /// Linux, leading to its removal in version 1.2.0. In the next breaking
-    /// release, 2.0.0, the return value will be changed to just
-    /// `io::Result<usize>`.*
+    /// Every call receives exactly one datagram, even if several are queued up,
+    /// and if the datagram doesn't fit into the buffer, the remainder is
+    /// discarded rather than kept for the next call. In nonblocking mode, if no
+    /// datagram is available, a [`WouldBlock`] error is returned instead. The
+    /// returned length never exceeds the size of the buffer; on Linux,
+    /// [`recv_with_size`] also reports the size which a truncated datagram had.
     ///
     /// # System calls
-    /// - `read`
+    /// - `recvmsg`
     ///
-    /// [scatter input]: https://en.wikipedia.org/wiki/Vectored_I/O " "
+    /// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock " "
+    /// [`recv_with_size`]: #method.recv_with_size " "
     pub fn recv(&self, buf: &mut [u8]) -> io::Result<(usize, bool)> {
-        self.fd.read(buf).map(Self::add_fake_trunc_flag)
+        self.recv_vectored(&mut [IoSliceMut::new(buf)])
     }
 
     /// Receives a single datagram from the socket, making use of [scatter
-    /// input] and returning the size of the received datagram.
+    /// input] and returning the size of the received datagram and whether a
+    /// part of it was discarded because the buffers were too small.
     ///
-    /// *Note: there is an additional meaningless boolean return value which is
-    /// always `false`. It used to signify whether the datagram was truncated or
-    /// not, but the functionality was implemented incorrectly and only on
-    /// Linux, leading to its removal in version 1.2.0. In the next breaking
-    /// release, 2.0.0, the return value will be changed to just
-    /// `io::Result<usize>`.*
+    /// The same rules as with [`recv`] apply.
     ///
     /// # System calls
-    /// - `readv`
+    /// - `recvmsg`
     ///
     /// [scatter input]: https://en.wikipedia.org/wiki/Vectored_I/O " "
+    /// [`recv`]: #method.recv " "
     pub fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<(usize, bool)> {
-        self.fd.read_vectored(bufs).map(Self::add_fake_trunc_flag)
+        let mut hdr = mk_msghdr_r(bufs, &mut [])?;
+        let bytes_read = self.recvmsg(&mut hdr)?;
+        Ok((bytes_read, hdr.msg_flags & MSG_TRUNC != 0))
     }
 
     /// Receives a single datagram and ancillary data from the socket. The
     /// return value is in the following order:
     /// - How many bytes of the datagram were received
-    /// - *Deprecated `bool` field (always `false`), see note*
+    /// - Whether a part of the datagram was discarded because the buffer was
+    ///   too small
     /// - How many bytes of ancillary data were received
-    /// - *Another deprecated `bool` field (always `false`), see note*
-    ///
-    /// *Note: there are two additional meaningless boolean return values which
-    /// are always `false`. They used to signify whether the datagram, and the
-    /// ancillary data respectively, were truncated or not, but the
-    /// functionality was implemented incorrectly and only on Linux, leading to
-    /// its removal in version 1.2.0. In the next breaking release, 2.0.0, the
-    /// return value will be changed to just `io::Result<usize>`.*
+    /// - Whether some ancillary data was discarded because the buffer was too
+    ///   small
     ///
     /// # System calls
     /// - `recvmsg`
-    ///
-    /// [scatter input]: https://en.wikipedia.org/wiki/Vectored_I/O " "
     pub fn recv_ancillary<'a: 'b, 'b>(
         &self,
         buf: &mut [u8],
@@ -205,16 +196,11 @@ impl UdSocket {
     /// Receives a single datagram and ancillary data from the socket, making
     /// use of [scatter input]. The return value is in the following order:
     /// - How many bytes of the datagram were received
-    /// - *Deprecated `bool` field (always `false`), see note*
+    /// - Whether a part of the datagram was discarded because the buffers were
+    ///   too small
     /// - How many bytes of ancillary data were received
-    /// - *Another deprecated `bool` field (always `false`), see note*
-    ///
-    /// *Note: there are two additional meaningless boolean return values which
-    /// are always `false`. They used to signify whether the datagram, and the
-    /// ancillary data respectively, were truncated or not, but the
-    /// functionality was implemented incorrectly and only on Linux, leading to
-    /// its removal in version 1.2.0. In the next breaking release, 2.0.0, the
-    /// return value will be changed to just `io::Result<(usize, usize)>`.*
+    /// - Whether some ancillary data was discarded because the buffer was too
+    ///   small
     ///
     /// # System calls
     /// - `recvmsg`
@@ -228,15 +214,13 @@ impl UdSocket {
     ) -> io::Result<(usize, bool, usize, bool)> {
         check_ancillary_unsound()?;
         let mut hdr = mk_msghdr_r(bufs, abuf.as_mut())?;
-        let (success, bytes_read) = unsafe {
-            let result = libc::recvmsg(self.as_raw_fd(), &mut hdr as *mut _, 0);
-            (result != -1, result as usize)
-        };
-        if success {
-            Ok((bytes_read, false, hdr.msg_controllen as _, false))
-        } else {
-            Err(io::Error::last_os_error())
-        }
+        let bytes_read = self.recvmsg(&mut hdr)?;
+        Ok((
+            bytes_read,
+            hdr.msg_flags & MSG_TRUNC != 0,
+            hdr.msg_controllen as _,
+            hdr.msg_flags & MSG_CTRUNC != 0,
+        ))
     }
 
     /// Receives a single datagram and the source address from the socket,
@@ -272,23 +256,17 @@ impl UdSocket {
         bufs: &mut [IoSliceMut<'_>],
         addr_buf: &'b mut UdSocketPath<'a>,
     ) -> io::Result<(usize, bool)> {
-        self.recv_from_ancillary_vectored(bufs, &mut AncillaryDataBuf::Owned(Vec::new()), addr_buf)
-            .map(|x| (x.0, x.1))
+        self._recv_from_ancillary_vectored(bufs, &mut [], addr_buf).map(|x| (x.0, x.1))
     }
 
     /// Receives a single datagram, ancillary data and the source address from
     /// the socket. The return value is in the following order:
     /// - How many bytes of the datagram were received
-    /// - *Deprecated `bool` field (always `false`), see note*
+    /// - Whether a part of the datagram was discarded because the buffer was
+    ///   too small
     /// - How many bytes of ancillary data were received
-    /// - *Another deprecated `bool` field (always `false`), see note*
-    ///
-    /// *Note: there are two additional meaningless boolean return values which
-    /// are always `false`. They used to signify whether the datagram, and the
-    /// ancillary data respectively, were truncated or not, but the
-    /// functionality was implemented incorrectly and only on Linux, leading to
-    /// its removal in version 1.2.0. In the next breaking release, 2.0.0, the
-    /// return value will be changed to just `io::Result<(usize, usize)>`.*
+    /// - Whether some ancillary data was discarded because the buffer was too
+    ///   small
     ///
     /// # System calls
     /// - `recvmsg`
@@ -327,6 +305,69 @@ impl UdSocket {
         addr_buf: &'d mut UdSocketPath<'c>,
     ) -> io::Result<(usize, bool, usize, bool)> {
         check_ancillary_unsound()?;
+        self._recv_from_ancillary_vectored(bufs, abuf.as_mut(), addr_buf)
+    }
+
+    /// Receives a single datagram together with the source address and the
+    /// credentials of the process which sent it, returning how much of the
+    /// buffer was filled out, whether a part of the datagram was discarded
+    /// because the buffer was too small, and the credentials.
+    ///
+    /// Datagrams from different senders arrive interleaved on the same socket,
+    /// so checking the credentials of each one is the only way to
+    /// authenticate senders reliably; unlike the address, which can be left
+    /// unnamed, the credentials are filled out by the system and cannot be
+    /// forged by unprivileged senders. Sockets created by this crate have
+    /// credential passing enabled, so the system attaches credentials to every
+    /// datagram whether or not the sender sent any explicitly. `None` is
+    /// returned if the system did not attach any, such as for sockets which
+    /// were created outside of this crate and adopted with [`from_raw_fd`]
+    /// without having credential passing enabled.
+    ///
+    /// # System calls
+    /// - `recvmsg`
+    ///
+    /// [`from_raw_fd`]: https://doc.rust-lang.org/std/os/unix/io/trait.FromRawFd.html#tymethod.from_raw_fd " "
+    #[cfg(any(doc, uds_scm_credentials))]
+    #[cfg_attr(
+        feature = "doc_cfg",
+        doc(cfg(any(
+            all(
+                target_os = "linux",
+                any(
+                    target_env = "gnu",
+                    target_env = "musl",
+                    target_env = "musleabi",
+                    target_env = "musleabihf"
+                )
+            ),
+            target_os = "android",
+            target_os = "fuchsia",
+            target_os = "redox"
+        )))
+    )]
+    pub fn recv_from_with_credentials<'a: 'b, 'b>(
+        &self,
+        buf: &mut [u8],
+        addr_buf: &'b mut UdSocketPath<'a>,
+    ) -> io::Result<(usize, bool, Option<ucred>)> {
+        let capacity = unsafe { libc::CMSG_SPACE(size_of::<ucred>() as _) } as usize;
+        let mut cmsg_buf = AncillaryBuffer::with_capacity(capacity);
+        let (bytes_read, truncated, cmsg_len, cmsg_truncated) = self
+            ._recv_from_ancillary_vectored(
+                &mut [IoSliceMut::new(buf)],
+                cmsg_buf.storage_mut(),
+                addr_buf,
+            )?;
+        cmsg_buf.set_received(cmsg_len, cmsg_truncated);
+        Ok((bytes_read, truncated, cmsg_buf.extract_credentials()))
+    }
+    fn _recv_from_ancillary_vectored(
+        &self,
+        bufs: &mut [IoSliceMut<'_>],
+        abuf: &mut [u8],
+        addr_buf: &mut UdSocketPath<'_>,
+    ) -> io::Result<(usize, bool, usize, bool)> {
         // SAFETY: msghdr consists of integers and pointers, all of which are nullable
         let mut hdr = unsafe { zeroed::<msghdr>() };
         // Same goes for sockaddr_un
@@ -334,20 +375,58 @@ impl UdSocket {
         // It's a void* so the doublecast is mandatory
         hdr.msg_name = &mut addr_buf_staging as *mut _ as *mut _;
         hdr.msg_namelen = size_of_val(&addr_buf_staging).try_to::<u32>().unwrap();
-        fill_out_msghdr_r(&mut hdr, bufs, abuf.as_mut())?;
+        fill_out_msghdr_r(&mut hdr, bufs, abuf)?;
+        let bytes_read = self.recvmsg(&mut hdr)?;
+        let path_length = hdr.msg_namelen as usize;
+        addr_buf.write_sockaddr_un_to_self(&addr_buf_staging, path_length);
+        Ok((
+            bytes_read,
+            hdr.msg_flags & MSG_TRUNC != 0,
+            hdr.msg_controllen as _,
+            hdr.msg_flags & MSG_CTRUNC != 0,
+        ))
+    }
+    /// Performs a single `recvmsg` call with the specified header, which then
+    /// contains the message flags reported by the kernel.
+    fn recvmsg(&self, hdr: &mut msghdr) -> io::Result<usize> {
+        self.recvmsg_with_flags(hdr, 0)
+    }
+    fn recvmsg_with_flags(&self, hdr: &mut msghdr, flags: c_int) -> io::Result<usize> {
         let (success, bytes_read) = unsafe {
-            let result = libc::recvmsg(self.as_raw_fd(), &mut hdr as *mut _, 0);
+            let result = libc::recvmsg(self.as_raw_fd(), hdr as *mut _, flags);
             (result != -1, result as usize)
         };
-        let path_length = hdr.msg_namelen as usize;
         if success {
-            addr_buf.write_sockaddr_un_to_self(&addr_buf_staging, path_length);
-            Ok((bytes_read, false, hdr.msg_controllen as _, false))
+            Ok(bytes_read)
         } else {
             Err(io::Error::last_os_error())
         }
     }
 
+    /// Receives a single datagram from the socket like [`recv`], returning how
+    /// many bytes of it were copied into the buffer and the size of the whole
+    /// datagram, which is bigger than the former if the datagram was truncated
+    /// to fit into the buffer.
+    ///
+    /// This method is only available on Linux; on other platforms, it's absent
+    /// and thus any usage of it will result in a compile-time error.
+    ///
+    /// # System calls
+    /// - `recvmsg`
+    ///
+    /// [`recv`]: #method.recv " "
+    #[cfg(any(doc, target_os = "linux"))]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(target_os = "linux")))]
+    pub fn recv_with_size(&self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
+        let buf_len = buf.len();
+        let mut bufs = [IoSliceMut::new(buf)];
+        let mut hdr = mk_msghdr_r(&mut bufs, &mut [])?;
+        // With MSG_TRUNC, Linux returns the size of the datagram even if it
+        // doesn't fit into the buffer.
+        let size = self.recvmsg_with_flags(&mut hdr, MSG_TRUNC)?;
+        Ok((size.min(buf_len), size))
+    }
+
     /// Returns the size of the next datagram available on the socket without
     /// discarding it.
     ///
@@ -379,6 +458,9 @@ impl UdSocket {
 
     /// Sends a datagram into the socket.
     ///
+    /// The datagram is sent with exactly one system call straight from the
+    /// provided buffer, without allocating or copying anything, which makes
+    /// this suitable for sending small messages at a high rate.
     ///
     /// # System calls
     /// - `sendmsg`
@@ -392,16 +474,18 @@ impl UdSocket {
     /// Sends a datagram into the socket, making use of [gather output] for the
     /// main data.
     ///
+    /// Like [`send`], never allocates.
     ///
     /// # System calls
     /// - `sendmsg`
-    ///     - Future versions of `interprocess` may use `writev` instead; for
-    ///       now, this method is a wrapper around [`send_ancillary_vectored`].
+    ///     - Future versions of `interprocess` may use `writev` instead.
     ///
     /// [gather output]: https://en.wikipedia.org/wiki/Vectored_I/O " "
-    /// [`send_ancillary_vectored`]: #method.send_ancillary_vectored " "
+    /// [`send`]: #method.send " "
     pub fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
-        self.send_ancillary_vectored(bufs, iter::empty()).map(|x| x.0)
+        // Bypasses the ancillary data encoder, since there is nothing to encode.
+        let hdr = mk_msghdr_w(bufs, &[])?;
+        self.sendmsg(&hdr)
     }
     /// Sends a datagram and ancillary data into the socket.
     ///
@@ -438,12 +522,16 @@ impl UdSocket {
         check_ancillary_unsound()?;
         let abuf = ancillary_data.into_iter().collect::<EncodedAncillaryData<'_>>();
         let hdr = mk_msghdr_w(bufs, abuf.as_ref())?;
+        let bytes_written = self.sendmsg(&hdr)?;
+        Ok((bytes_written, hdr.msg_controllen as _))
+    }
+    fn sendmsg(&self, hdr: &msghdr) -> io::Result<usize> {
         let (success, bytes_written) = unsafe {
-            let result = libc::sendmsg(self.as_raw_fd(), &hdr as *const _, 0);
+            let result = libc::sendmsg(self.as_raw_fd(), hdr as *const _, 0);
             (result != -1, result as usize)
         };
         if success {
-            Ok((bytes_written, hdr.msg_controllen as _))
+            Ok(bytes_written)
         } else {
             Err(io::Error::last_os_error())
         }
@@ -495,6 +583,14 @@ impl UdSocket {
     pub fn get_peer_credentials(&self) -> io::Result<ucred> {
         unsafe { get_peer_ucred(self.fd.0) }
     }
+    /// Consumes the socket and returns its file descriptor without closing it,
+    /// the same way as `into_raw_fd` does. Closing the file descriptor becomes
+    /// the caller's responsibility — see [`UdStream::forget`] for details.
+    ///
+    /// [`UdStream::forget`]: struct.UdStream.html#method.forget " "
+    pub fn forget(self) -> c_int {
+        self.into_raw_fd()
+    }
 }
 impl Debug for UdSocket {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -533,6 +629,7 @@ impl AsRawFd for UdSocket {
         self.fd.as_raw_fd()
     }
 }
+impl_as_borrowed_handle!(UdSocket);
 #[cfg(unix)]
 impl IntoRawFd for UdSocket {
     fn into_raw_fd(self) -> c_int {
@@ -1,5 +1,7 @@
 #[cfg(uds_peercred)]
 use super::util::get_peer_ucred;
+#[cfg(target_os = "linux")]
+use super::util::{raw_get_mark, raw_get_send_queue_len, raw_set_mark};
 use super::{
     super::{close_by_error, handle_fd_error},
     imports::*,
@@ -9,21 +11,35 @@ use super::{
         mk_msghdr_r,
         mk_msghdr_w,
         raw_get_nonblocking,
+        raw_get_recv_queue_len,
+        raw_get_timeout,
         raw_set_nonblocking,
+        raw_set_timeout,
         raw_shutdown,
+        raw_take_error,
     },
+    AncillaryBuffer,
     AncillaryData,
     AncillaryDataBuf,
+    ConnectionSlot,
     EncodedAncillaryData,
     ToUdSocketPath,
+    UdSocketPath,
 };
+#[cfg(not(target_os = "linux"))]
+use crate::unsupported;
+use crate::{copy_to_writer, read_chunks, read_framed};
 use std::{
     fmt::{self, Debug, Formatter},
+    fs::File,
     io::{self, IoSlice, IoSliceMut, Read, Write},
     iter,
     mem::size_of,
     net::Shutdown,
+    time::Duration,
 };
+#[cfg(target_os = "linux")]
+use std::{thread, time::Instant};
 use to_method::To;
 
 /// A Unix domain socket byte stream, obtained either from [`UdStreamListener`]
@@ -120,7 +136,9 @@ use to_method::To;
 ///
 /// [`UdStreamListener`]: struct.UdStreamListener.html " "
 pub struct UdStream {
-    fd: FdOps,
+    fd:              FdOps,
+    // Only set for streams accepted by a listener with a connection limit.
+    pub(super) slot: Option<ConnectionSlot>,
 }
 impl UdStream {
     /// Connect to a Unix domain socket server at the specified path.
@@ -139,13 +157,50 @@ impl UdStream {
     /// See [`ToUdSocketPath`] for an example of using various string types to
     /// specify socket paths.
     ///
+    /// # Errors
+    /// Two errors tell apart the ways in which there can be no server to
+    /// connect to:
+    /// - [`ConnectionRefused`] (`ECONNREFUSED`) means that the socket file
+    ///   exists, but nobody is listening on it — typically a stale file left
+    ///   behind by a server which exited without removing it, which can be
+    ///   removed before binding a new listener to the same path;
+    /// - [`NotFound`] (`ENOENT`) means that there is no file at the path at
+    ///   all, i.e. the server has never been started or has cleaned up after
+    ///   itself.
+    ///
     /// # System calls
     /// - `socket`
     /// - `connect`
     ///
     /// [`ToUdSocketPath`]: trait.ToUdSocketPath.html " "
+    /// [`ConnectionRefused`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.ConnectionRefused " "
+    /// [`NotFound`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.NotFound " "
     pub fn connect<'a>(path: impl ToUdSocketPath<'a>) -> io::Result<Self> {
-        let addr = path.to_socket_path()?.try_to::<sockaddr_un>()?;
+        Self::_connect(path.to_socket_path()?, false)
+    }
+    /// Connects to a Unix domain socket server at the specified path without
+    /// waiting if the server is busy.
+    ///
+    /// If the listen backlog of the server is full, which happens when the
+    /// server does not accept clients as fast as they arrive, [`connect`] waits
+    /// until there's room in it. This method instead fails right away with an
+    /// error of kind [`AddrInUse`], which allows tools that probe whether a
+    /// server is available to do so without ever blocking. Other than that, it
+    /// behaves exactly like `connect`, including in that the resulting stream
+    /// is in blocking mode and in the errors reported when there's no server.
+    ///
+    /// # System calls
+    /// - `socket`
+    /// - `fcntl`
+    /// - `connect`
+    ///
+    /// [`connect`]: #method.connect " "
+    /// [`AddrInUse`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.AddrInUse " "
+    pub fn connect_immediate<'a>(path: impl ToUdSocketPath<'a>) -> io::Result<Self> {
+        Self::_connect(path.to_socket_path()?, true)
+    }
+    fn _connect(path: UdSocketPath<'_>, immediate: bool) -> io::Result<Self> {
+        let addr = path.try_to::<sockaddr_un>()?;
         let socket = {
             let (success, fd) = unsafe {
                 let result = libc::socket(AF_UNIX, SOCK_STREAM, 0);
@@ -157,15 +212,28 @@ impl UdStream {
                 return Err(io::Error::last_os_error());
             }
         };
+        if immediate {
+            // A nonblocking connect fails with EAGAIN instead of waiting for room in
+            // the backlog.
+            unsafe { raw_set_nonblocking(socket, true).map_err(close_by_error(socket))? };
+        }
         let success = unsafe {
             libc::connect(
                 socket,
                 &addr as *const _ as *const _,
                 size_of::<sockaddr_un>() as u32,
             )
-        } != 1;
+        } != -1;
         if !success {
-            unsafe { return Err(handle_fd_error(socket)) };
+            let error = unsafe { handle_fd_error(socket) };
+            return Err(if immediate && error.kind() == io::ErrorKind::WouldBlock {
+                io::Error::new(io::ErrorKind::AddrInUse, "the server is busy")
+            } else {
+                error
+            });
+        }
+        if immediate {
+            unsafe { raw_set_nonblocking(socket, false).map_err(close_by_error(socket))? };
         }
         unsafe { enable_passcred(socket).map_err(close_by_error(socket))? };
         Ok(unsafe { Self::from_raw_fd(socket) })
@@ -188,6 +256,182 @@ impl UdStream {
     pub fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
         self.fd.read_vectored(bufs)
     }
+    /// Receives bytes from the socket stream until end of file, passing them to
+    /// the specified closure in chunks of at most `chunk_size` bytes and
+    /// returning the total amount of bytes received.
+    ///
+    /// Only one buffer of `chunk_size` bytes is allocated for the whole
+    /// operation, making this suitable for processing payloads of any size,
+    /// such as for hashing or forwarding them, without ever holding all of the
+    /// data in memory. Each chunk is whatever a single `read` call returned, so
+    /// chunks may be smaller than `chunk_size` even before end of file is
+    /// reached.
+    ///
+    /// If the closure returns an error, the operation stops and the error is
+    /// returned as-is. Interrupted reads are retried.
+    ///
+    /// # Panics
+    /// If `chunk_size` is zero.
+    ///
+    /// # System calls
+    /// - `read`
+    pub fn read_chunks(
+        &self,
+        chunk_size: usize,
+        f: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> io::Result<u64> {
+        read_chunks(chunk_size, |buf| self.fd.read(buf), f)
+    }
+    /// Receives a frame made of a fixed-size header followed by a body whose
+    /// length is stated in the header, returning the header and the body.
+    ///
+    /// First, exactly `header_len` bytes are received as the header, which is
+    /// then passed to `body_len_from_header` to parse the length of the body
+    /// out of it. After that, exactly that many bytes are received as the
+    /// body. The closure is where the length should be validated: the buffer
+    /// for the body is allocated with the returned length before anything is
+    /// received into it, so a length taken from an untrusted peer as-is lets
+    /// the peer make the process allocate as much memory as it likes. An error
+    /// returned by the closure is returned as-is, with the body left unread.
+    ///
+    /// Fails with `ErrorKind::UnexpectedEof` if end of file is reached in the
+    /// middle of the frame. Interrupted reads are retried.
+    ///
+    /// # System calls
+    /// - `read`
+    pub fn read_framed(
+        &self,
+        header_len: usize,
+        body_len_from_header: impl FnOnce(&[u8]) -> io::Result<usize>,
+    ) -> io::Result<(Vec<u8>, Vec<u8>)> {
+        read_framed(header_len, body_len_from_header, |buf| self.fd.read(buf))
+    }
+    /// Moves up to `count` bytes received from the socket stream into the
+    /// specified file, stopping early at end of file. Returns the amount of
+    /// bytes which were written into the file, at its current offset.
+    ///
+    /// On Linux and Android, the data is moved with `splice`, never being
+    /// copied into userspace. Since `splice` requires one side of the transfer
+    /// to be a pipe, neither of which is the case when moving data from a
+    /// socket into a file, this creates a kernel pipe as an intermediary for
+    /// the duration of the call: the data is spliced from the socket into the
+    /// pipe and from the pipe into the file, costing two extra file
+    /// descriptors while the call lasts. If the file system the file is on does
+    /// not support `splice`, the rest of the data is copied through a userspace
+    /// buffer instead, which is also what's done on all other platforms.
+    ///
+    /// If the file cannot be written to after data has already been received
+    /// from the socket, that data is lost.
+    ///
+    /// # System calls
+    /// - `pipe2`, `splice` and `close` on Linux and Android
+    /// - `read`
+    /// - `write`
+    pub fn splice_to_file(&self, file: &File, count: usize) -> io::Result<usize> {
+        self.splice_to_file_with_progress(file, count, &mut || {})
+    }
+    /// `splice_to_file` which calls `progress` whenever data has been received,
+    /// for callers who keep track of activity on the stream.
+    pub(crate) fn splice_to_file_with_progress(
+        &self,
+        file: &File,
+        count: usize,
+        progress: &mut dyn FnMut(),
+    ) -> io::Result<usize> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let (spliced, done) = self.splice_via_pipe(file, count, progress)?;
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let (spliced, done) = (0, false);
+        if done {
+            return Ok(spliced);
+        }
+        let read = |buf: &mut [u8]| {
+            let result = self.fd.read(buf);
+            if matches!(result, Ok(bytes_read) if bytes_read > 0) {
+                progress();
+            }
+            result
+        };
+        let copied = copy_to_writer(count - spliced, read, &mut &*file)?;
+        Ok(spliced + copied)
+    }
+    /// The `splice` part of `splice_to_file`. Returns the amount of bytes moved
+    /// and whether the operation is done, which is not the case if it has to be
+    /// finished with a userspace copy because `splice` is not supported for the
+    /// file or the socket.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn splice_via_pipe(
+        &self,
+        file: &File,
+        count: usize,
+        progress: &mut dyn FnMut(),
+    ) -> io::Result<(usize, bool)> {
+        // The default capacity of a pipe, i.e. the biggest amount of data which is
+        // guaranteed to fit into it at once.
+        const PIPE_CAPACITY: usize = 65536;
+        let (pipe_reader, pipe_writer) = {
+            let mut fds = [0; 2];
+            let success = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != -1;
+            if !success {
+                return Err(io::Error::last_os_error());
+            }
+            (FdOps::new(fds[0]), FdOps::new(fds[1]))
+        };
+        let splice = |from: c_int, to: c_int, len: usize| {
+            let (success, bytes_moved) = unsafe {
+                let result = libc::splice(
+                    from,
+                    std::ptr::null_mut(),
+                    to,
+                    std::ptr::null_mut(),
+                    len,
+                    libc::SPLICE_F_MOVE,
+                );
+                (result != -1, result as usize)
+            };
+            if success {
+                Ok(bytes_moved)
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        };
+        let is_unsupported = |e: &io::Error| e.raw_os_error() == Some(libc::EINVAL);
+
+        let mut total = 0;
+        while total < count {
+            let in_pipe = match splice(
+                self.as_raw_fd(),
+                pipe_writer.0,
+                (count - total).min(PIPE_CAPACITY),
+            ) {
+                Ok(0) => return Ok((total, true)),
+                Ok(in_pipe) => {
+                    progress();
+                    in_pipe
+                },
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                // The pipe is always empty at this point, so there's nothing to salvage.
+                Err(e) if is_unsupported(&e) => return Ok((total, false)),
+                Err(e) => return Err(e),
+            };
+            let mut left = in_pipe;
+            while left > 0 {
+                match splice(pipe_reader.0, file.as_raw_fd(), left) {
+                    Ok(bytes_moved) => left -= bytes_moved,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+                    Err(e) if is_unsupported(&e) => {
+                        // Drain the pipe into the file by hand before giving up on splicing.
+                        let drained =
+                            copy_to_writer(left, |buf| pipe_reader.read(buf), &mut &*file)?;
+                        return Ok((total + in_pipe - left + drained, false));
+                    },
+                    Err(e) => return Err(e),
+                }
+            }
+            total += in_pipe;
+        }
+        Ok((total, true))
+    }
     /// Receives both bytes and ancillary data from the socket stream.
     ///
     /// The ancillary data buffer is automatically converted from the supplied
@@ -233,6 +477,54 @@ impl UdStream {
             Err(io::Error::last_os_error())
         }
     }
+    /// Receives bytes and file descriptors from the socket stream into buffers
+    /// owned by the caller, making use of [scatter input] for the main data and
+    /// returning the amount of bytes read.
+    ///
+    /// This is the allocation-free counterpart of [`recv_ancillary_vectored`]
+    /// for servers which receive file descriptors at a high rate: the
+    /// ancillary data is received into the [`AncillaryBuffer`], which is
+    /// reused across calls, and the file descriptors from it are appended to
+    /// `fds_out`, which only allocates if it has to grow beyond its capacity.
+    /// Clearing `fds_out` between calls is left to the caller. The buffer
+    /// retains the full ancillary data until the next call, so that other
+    /// control messages, such as credentials, can still be decoded from it.
+    ///
+    /// If the ancillary data does not fit into the buffer, it is cut off, which
+    /// [`AncillaryBuffer::is_truncated`] reports after the call. The file
+    /// descriptors which were cut off are closed by the system.
+    ///
+    /// Since the buffer is aligned for control message headers and the
+    /// messages are walked with the `CMSG_*` macros, this also works on the
+    /// platforms where the other ancillary data methods are disabled due to
+    /// alignment issues.
+    ///
+    /// # System calls
+    /// - `recvmsg`
+    ///
+    /// [scatter input]: https://en.wikipedia.org/wiki/Vectored_I/O " "
+    /// [`recv_ancillary_vectored`]: #method.recv_ancillary_vectored " "
+    /// [`AncillaryBuffer`]: struct.AncillaryBuffer.html " "
+    /// [`AncillaryBuffer::is_truncated`]: struct.AncillaryBuffer.html#method.is_truncated " "
+    pub fn recv_with_ancillary_into(
+        &self,
+        bufs: &mut [IoSliceMut<'_>],
+        cmsg_buf: &mut AncillaryBuffer,
+        fds_out: &mut Vec<c_int>,
+    ) -> io::Result<usize> {
+        cmsg_buf.clear();
+        let mut hdr = mk_msghdr_r(bufs, cmsg_buf.storage_mut())?;
+        let (success, bytes_read) = unsafe {
+            let result = libc::recvmsg(self.as_raw_fd(), &mut hdr as *mut _, 0);
+            (result != -1, result as usize)
+        };
+        if !success {
+            return Err(io::Error::last_os_error());
+        }
+        cmsg_buf.set_received(hdr.msg_controllen as _, hdr.msg_flags & MSG_CTRUNC != 0);
+        cmsg_buf.extract_file_descriptors(fds_out);
+        Ok(bytes_read)
+    }
 
     /// Sends bytes into the socket stream.
     ///
@@ -332,6 +624,222 @@ impl UdStream {
         unsafe { raw_get_nonblocking(self.fd.0) }
     }
 
+    /// Sets the read timeout to the specified value. If `None` is specified,
+    /// reads block indefinitely, which is the default.
+    ///
+    /// Reads which time out fail with a [`WouldBlock`] or [`TimedOut`] error,
+    /// depending on the platform.
+    ///
+    /// # Errors
+    /// An [`InvalidInput`] error is returned if the duration is zero.
+    ///
+    /// # System calls
+    /// - `setsockopt`
+    ///
+    /// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock " "
+    /// [`TimedOut`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.TimedOut " "
+    /// [`InvalidInput`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.InvalidInput " "
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        unsafe { raw_set_timeout(self.fd.0, SO_RCVTIMEO, timeout) }
+    }
+    /// Sets the write timeout to the specified value. If `None` is specified,
+    /// writes block indefinitely, which is the default.
+    ///
+    /// See [`set_read_timeout`] for how timeouts and errors are reported.
+    ///
+    /// # System calls
+    /// - `setsockopt`
+    ///
+    /// [`set_read_timeout`]: #method.set_read_timeout " "
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        unsafe { raw_set_timeout(self.fd.0, SO_SNDTIMEO, timeout) }
+    }
+    /// Sets both the read and the write timeout, leaving the stream with
+    /// neither of them changed if either one cannot be applied.
+    ///
+    /// The read timeout is set first; if setting the write timeout fails
+    /// afterwards, the previous read timeout is restored before the error is
+    /// returned. This keeps streams which are shared between threads from ever
+    /// being observed with only one of the two timeouts updated due to a
+    /// failure, although another thread can still observe the short moment
+    /// between the two being set.
+    ///
+    /// # System calls
+    /// - `getsockopt`
+    /// - `setsockopt`
+    pub fn set_timeouts(&self, read: Option<Duration>, write: Option<Duration>) -> io::Result<()> {
+        let old_read = self.read_timeout()?;
+        self.set_read_timeout(read)?;
+        if let Err(e) = self.set_write_timeout(write) {
+            // If even this fails, the original error is still the one worth reporting.
+            let _ = self.set_read_timeout(old_read);
+            return Err(e);
+        }
+        Ok(())
+    }
+    /// Returns the read timeout of the stream, or `None` if reads block
+    /// indefinitely.
+    ///
+    /// # System calls
+    /// - `getsockopt`
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        unsafe { raw_get_timeout(self.fd.0, SO_RCVTIMEO) }
+    }
+    /// Returns the write timeout of the stream, or `None` if writes block
+    /// indefinitely.
+    ///
+    /// # System calls
+    /// - `getsockopt`
+    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        unsafe { raw_get_timeout(self.fd.0, SO_SNDTIMEO) }
+    }
+    /// Retrieves the pending error of the socket (`SO_ERROR`), if any, and
+    /// clears it, like the method of the same name on `TcpStream`.
+    ///
+    /// The system records asynchronous errors on the socket, such as the other
+    /// end resetting the connection, without reporting them until the next
+    /// operation fails with them. Reactors which are told by `poll` that the
+    /// socket is in an error state (`POLLERR`) can use this to find out what
+    /// the error is without attempting a read or write. Since the error is
+    /// cleared, it's not reported again by the next operation on the socket.
+    ///
+    /// Unlike with TCP, connecting a Unix domain socket in nonblocking mode
+    /// doesn't leave the outcome to be retrieved with this method: the
+    /// connection is either established or refused by the `connect` call
+    /// itself, which reports the error directly and leaves none pending.
+    ///
+    /// # System calls
+    /// - `getsockopt`
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        unsafe { raw_take_error(self.fd.0) }
+    }
+
+    /// Sets the mark of the socket (`SO_MARK`), which tags it for firewall and
+    /// routing policy.
+    ///
+    /// Unix domain sockets are never routed, but some security modules and
+    /// cgroup-based policies inspect the mark of a socket to decide what is
+    /// allowed to happen with it.
+    ///
+    /// Changing the mark requires the `CAP_NET_ADMIN` capability. Unprivileged
+    /// processes get a [`PermissionDenied`] error, which leaves the socket
+    /// unchanged and can safely be handled by carrying on without a mark.
+    ///
+    /// # System calls
+    /// - `setsockopt`
+    ///
+    /// [`PermissionDenied`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.PermissionDenied " "
+    #[cfg(any(doc, target_os = "linux"))]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(target_os = "linux")))]
+    pub fn set_mark(&self, mark: u32) -> io::Result<()> {
+        unsafe { raw_set_mark(self.fd.0, mark) }
+    }
+    /// Retrieves the mark of the socket (`SO_MARK`), which is zero unless it
+    /// has been set with [`set_mark`]. Does not require any privileges.
+    ///
+    /// # System calls
+    /// - `getsockopt`
+    ///
+    /// [`set_mark`]: #method.set_mark " "
+    #[cfg(any(doc, target_os = "linux"))]
+    #[cfg_attr(feature = "doc_cfg", doc(cfg(target_os = "linux")))]
+    pub fn mark(&self) -> io::Result<u32> {
+        unsafe { raw_get_mark(self.fd.0) }
+    }
+    /// Returns the amount of bytes which have been received and are waiting to
+    /// be read from the socket.
+    ///
+    /// Together with [`send_queue_len`], this lets servers make flow control
+    /// decisions, such as pausing a producer whose consumer is falling behind,
+    /// without peeking at the data. The value is a snapshot which may already
+    /// be out of date by the time it's returned.
+    ///
+    /// # System calls
+    /// - `ioctl` (`FIONREAD`)
+    ///
+    /// [`send_queue_len`]: #method.send_queue_len " "
+    pub fn recv_queue_len(&self) -> io::Result<usize> {
+        unsafe { raw_get_recv_queue_len(self.fd.0) }
+    }
+    /// Returns the amount of bytes which have been written into the socket but
+    /// not yet read by the other end.
+    ///
+    /// Like with [`recv_queue_len`], the value is a snapshot which may already
+    /// be out of date by the time it's returned. [`wait_flushed`] can be used
+    /// to wait for this to reach zero.
+    ///
+    /// # Platform-specific behavior
+    /// Only Linux can report the size of the send queue (`SIOCOUTQ`). For Unix
+    /// domain sockets, it reports the amount of memory taken up by the queued
+    /// data, including bookkeeping, which is bigger than the data itself — the
+    /// value is only zero if everything has been read, and is otherwise best
+    /// treated as a measure of how much is backed up. On other platforms, an
+    /// `Unsupported` error is always returned.
+    ///
+    /// # System calls
+    /// - `ioctl` (Linux only)
+    ///
+    /// [`recv_queue_len`]: #method.recv_queue_len " "
+    /// [`wait_flushed`]: #method.wait_flushed " "
+    pub fn send_queue_len(&self) -> io::Result<usize> {
+        #[cfg(target_os = "linux")]
+        {
+            unsafe { raw_get_send_queue_len(self.fd.0) }
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            Err(unsupported(
+                "the size of the send queue of a Unix domain socket is only available on Linux",
+            ))
+        }
+    }
+    /// Waits until everything written into the socket has been read by the
+    /// other end, or until the timeout expires, in which case an error of kind
+    /// [`TimedOut`] is returned. With no timeout, the wait is unbounded.
+    ///
+    /// Writes into a Unix domain socket return as soon as the data is queued
+    /// in the kernel, and there is no equivalent of flushing a named pipe,
+    /// which waits until the client has read everything. This fills that gap
+    /// for graceful shutdown code which needs to make sure that a final
+    /// message got through before closing the connection. The size of the send
+    /// queue is polled with a short, growing interval, since the system does
+    /// not provide a way to be notified when it drains.
+    ///
+    /// # Platform-specific behavior
+    /// Only Linux can report the size of the send queue (`SIOCOUTQ`). On other
+    /// platforms, this returns `Ok(())` immediately without waiting.
+    ///
+    /// # System calls
+    /// - `ioctl` (Linux only)
+    ///
+    /// [`TimedOut`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.TimedOut " "
+    pub fn wait_flushed(&self, timeout: Option<Duration>) -> io::Result<()> {
+        #[cfg(target_os = "linux")]
+        {
+            const MAX_POLL_INTERVAL: Duration = Duration::from_millis(64);
+            let deadline = timeout.map(|timeout| Instant::now() + timeout);
+            let mut interval = Duration::from_millis(1);
+            while unsafe { raw_get_send_queue_len(self.fd.0) }? != 0 {
+                let mut sleep_for = interval;
+                if let Some(deadline) = deadline {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "the send queue did not drain in time",
+                        ));
+                    }
+                    sleep_for = sleep_for.min(deadline - now);
+                }
+                thread::sleep(sleep_for);
+                interval = (interval * 2).min(MAX_POLL_INTERVAL);
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = timeout;
+        Ok(())
+    }
+
     /// Fetches the credentials of the other end of the connection without using
     /// ancillary data. The returned structure contains the process identifier,
     /// user identifier and group identifier of the peer.
@@ -356,6 +864,18 @@ impl UdStream {
     pub fn get_peer_credentials(&self) -> io::Result<ucred> {
         unsafe { get_peer_ucred(self.fd.0) }
     }
+    /// Consumes the stream and returns its file descriptor without closing it.
+    ///
+    /// This is the same as `into_raw_fd`, for code which transfers ownership of
+    /// the socket out-of-band, such as by passing it to a C API which will
+    /// close it. **Closing the file descriptor becomes the caller's
+    /// responsibility**: it is leaked unless closed by whoever it's given to or
+    /// turned back into a stream with `from_raw_fd`, and closing it more than
+    /// once might close an unrelated file descriptor which has been given the
+    /// same number in the meantime.
+    pub fn forget(self) -> c_int {
+        self.into_raw_fd()
+    }
 }
 impl Read for UdStream {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
@@ -389,6 +909,7 @@ impl AsRawFd for UdStream {
         self.fd.as_raw_fd()
     }
 }
+impl_as_borrowed_handle!(UdStream);
 #[cfg(unix)]
 impl IntoRawFd for UdStream {
     fn into_raw_fd(self) -> c_int {
@@ -398,6 +919,9 @@ impl IntoRawFd for UdStream {
 #[cfg(unix)]
 impl FromRawFd for UdStream {
     unsafe fn from_raw_fd(fd: c_int) -> Self {
-        Self { fd: FdOps::new(fd) }
+        Self {
+            fd:   FdOps::new(fd),
+            slot: None,
+        }
     }
 }
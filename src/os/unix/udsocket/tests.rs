@@ -1,9 +1,13 @@
-use super::{UdStream, UdStreamListener};
+use super::{UdSocket, UdSocketPath, UdStream, UdStreamListener};
+use crate::OverflowPolicy;
 use std::{
     fs::remove_file,
     io::{self, prelude::*},
     net::Shutdown,
+    num::NonZeroUsize,
     process,
+    thread,
+    time::Duration,
 };
 
 #[test]
@@ -49,3 +53,604 @@ fn basic_stream_client() {
     println!("Server answered: {}", string_buffer);
     conn.write_all(b"Hello from client!").unwrap();
 }
+
+#[test]
+fn datagram_boundaries() {
+    let _ = remove_file(DATAGRAM_SOCKET_NAME);
+    let receiver = UdSocket::bind(DATAGRAM_SOCKET_NAME).unwrap();
+    receiver.set_nonblocking(true).unwrap();
+    let mut buffer = [0_u8; 64];
+    let e = receiver.recv(&mut buffer).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+
+    let messages: [&[u8]; 3] = [b"first", b"the second one", b"3"];
+    let senders = messages
+        .iter()
+        .map(|_| UdSocket::connect(DATAGRAM_SOCKET_NAME).unwrap())
+        .collect::<Vec<_>>();
+    for (sender, msg) in senders.iter().zip(messages.iter()) {
+        assert_eq!(sender.send(msg).unwrap(), msg.len());
+    }
+    for msg in messages.iter() {
+        let (len, truncated) = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], *msg);
+        assert!(!truncated);
+    }
+    let e = receiver.recv(&mut buffer).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+
+    // An oversized datagram is cut to the buffer size, with the remainder
+    // discarded instead of showing up in the next call.
+    senders[0].send(&[0xAA; 32]).unwrap();
+    let mut small_buffer = [0_u8; 8];
+    assert_eq!(receiver.recv(&mut small_buffer).unwrap(), (8, true));
+    assert_eq!(small_buffer, [0xAA; 8]);
+    let e = receiver.recv(&mut buffer).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+
+    // The size of the whole datagram can be retrieved separately.
+    #[cfg(target_os = "linux")]
+    {
+        senders[0].send(&[0xBB; 32]).unwrap();
+        assert_eq!(receiver.recv_with_size(&mut small_buffer).unwrap(), (8, 32));
+        assert_eq!(small_buffer, [0xBB; 8]);
+        senders[0].send(b"fits").unwrap();
+        assert_eq!(receiver.recv_with_size(&mut buffer).unwrap(), (4, 4));
+        assert_eq!(&buffer[..4], b"fits");
+    }
+}
+static DATAGRAM_SOCKET_NAME: &str = "/tmp/interprocess_udsocket_test_boundaries.sock";
+
+#[test]
+fn datagrams_from_concurrent_senders() {
+    const SENDERS: u8 = 4;
+    const DATAGRAMS: u8 = 50;
+    let path = "/tmp/interprocess_udsocket_test_concurrent_senders.sock";
+    let _ = remove_file(path);
+    let receiver = UdSocket::bind(path).unwrap();
+    receiver.set_nonblocking(true).unwrap();
+    let senders = (0..SENDERS)
+        .map(|id| {
+            let sender = UdSocket::connect(path).unwrap();
+            thread::spawn(move || {
+                // Each datagram is made of its sender's ID, its sequence number
+                // and a payload whose length depends on both, so that mixing
+                // up or splitting datagrams can't go unnoticed.
+                for seq in 0..DATAGRAMS {
+                    let mut datagram = vec![id, seq];
+                    datagram.resize(2 + usize::from(id) * 7 + usize::from(seq), id);
+                    assert_eq!(sender.send(&datagram).unwrap(), datagram.len());
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut next_seq = [0_u8; SENDERS as usize];
+    let mut buffer = [0_u8; 256];
+    let mut received = 0;
+    while received < usize::from(SENDERS) * usize::from(DATAGRAMS) {
+        let (len, truncated) = match receiver.recv(&mut buffer) {
+            Ok(result) => result,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(1));
+                continue;
+            },
+            Err(e) => panic!("receiving failed: {}", e),
+        };
+        assert!(!truncated);
+        let (id, seq) = (buffer[0], buffer[1]);
+        assert_eq!(len, 2 + usize::from(id) * 7 + usize::from(seq));
+        assert!(buffer[2..len].iter().all(|&byte| byte == id));
+        // Datagrams from one sender arrive in the order they were sent in.
+        assert_eq!(seq, next_seq[usize::from(id)]);
+        next_seq[usize::from(id)] += 1;
+        received += 1;
+    }
+    for sender in senders {
+        sender.join().unwrap();
+    }
+    let e = receiver.recv(&mut buffer).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+    let _ = remove_file(path);
+}
+
+#[test]
+fn overflow_policies() {
+    let path = "/tmp/interprocess_udstream_test_overflow.sock";
+    let one = NonZeroUsize::new(1).unwrap();
+    let _ = remove_file(path);
+    let listener = UdStreamListener::bind_with_limit(path, one, OverflowPolicy::Queue).unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let _client1 = UdStream::connect(path).unwrap();
+    let server1 = listener.accept().unwrap();
+    let mut client2 = UdStream::connect(path).unwrap();
+    // At the limit, the second client stays in the backlog.
+    let error = listener.accept().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+    drop(server1);
+    let mut server2 = listener.accept().unwrap();
+    client2.write_all(b"queued").unwrap();
+    let mut buf = [0; 6];
+    server2.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"queued");
+    drop(listener);
+
+    let _ = remove_file(path);
+    let listener = UdStreamListener::bind_with_limit(path, one, OverflowPolicy::Reject).unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let _client1 = UdStream::connect(path).unwrap();
+    let server1 = listener.accept().unwrap();
+    let mut client2 = UdStream::connect(path).unwrap();
+    // The second client gets accepted and closed, after which there is nobody
+    // else left to accept.
+    let error = listener.accept().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+    assert_eq!(client2.read(&mut buf).unwrap(), 0);
+    drop(server1);
+    let _client3 = UdStream::connect(path).unwrap();
+    listener.accept().unwrap();
+}
+
+#[cfg(uds_peercred)]
+#[test]
+fn own_credentials_match_peer() {
+    let path = "/tmp/interprocess_udstream_test_credentials.sock";
+    let _ = remove_file(path);
+    let listener = UdStreamListener::bind(path).unwrap();
+    let client = UdStream::connect(path).unwrap();
+    let server = listener.accept().unwrap();
+    let own = super::own_credentials();
+    for peer in [client.get_peer_credentials(), server.get_peer_credentials()].iter() {
+        let peer = peer.as_ref().unwrap();
+        assert_eq!((peer.pid, peer.uid, peer.gid), (own.pid, own.uid, own.gid));
+        assert_eq!(peer.pid as u32, process::id());
+    }
+}
+
+// Needs libselinux to link, and SELinux to be enabled to do anything.
+#[cfg(all(target_os = "linux", feature = "selinux"))]
+#[test]
+fn bind_with_label() {
+    use super::selinux::{current_context, get_socket_creation_context};
+
+    let context = match current_context().unwrap() {
+        Some(context) => context,
+        None => return,
+    };
+    let path = "/tmp/interprocess_udstream_test_label.sock";
+    let _ = remove_file(path);
+    let previous = get_socket_creation_context().unwrap();
+    let listener = UdStreamListener::bind_with_label(path, &context).unwrap();
+    assert_eq!(get_socket_creation_context().unwrap(), previous);
+    let mut client = UdStream::connect(path).unwrap();
+    let mut server = listener.accept().unwrap();
+    client.write_all(b"label").unwrap();
+    let mut buf = [0; 5];
+    server.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"label");
+}
+
+#[test]
+fn read_chunks() {
+    let path = "/tmp/interprocess_udstream_test_chunks.sock";
+    let _ = remove_file(path);
+    let listener = UdStreamListener::bind(path).unwrap();
+    let mut client = UdStream::connect(path).unwrap();
+    let server = listener.accept().unwrap();
+    let data = (0..10_000).map(|i| i as u8).collect::<Vec<u8>>();
+    client.write_all(&data).unwrap();
+    client.shutdown(Shutdown::Write).unwrap();
+
+    let mut received = Vec::new();
+    let total = server
+        .read_chunks(64, |chunk| {
+            assert!(!chunk.is_empty() && chunk.len() <= 64);
+            received.extend_from_slice(chunk);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(total, data.len() as u64);
+    assert_eq!(received, data);
+
+    // Stopping early with an error from the closure.
+    let mut client = UdStream::connect(path).unwrap();
+    let server = listener.accept().unwrap();
+    client.write_all(&data).unwrap();
+    let error = server
+        .read_chunks(64, |_| Err(io::Error::new(io::ErrorKind::Other, "enough")))
+        .unwrap_err();
+    assert_eq!(error.to_string(), "enough");
+}
+
+#[test]
+fn inherit_nonblocking() {
+    let path = "/tmp/interprocess_udstream_test_inherit_nonblocking.sock";
+    let _ = remove_file(path);
+    let listener = UdStreamListener::bind(path).unwrap();
+    listener.set_nonblocking(true).unwrap();
+    assert!(!listener.inherits_nonblocking());
+    let _client = UdStream::connect(path).unwrap();
+    assert!(!listener.accept().unwrap().is_nonblocking().unwrap());
+
+    listener.set_inherit_nonblocking(true);
+    let _client = UdStream::connect(path).unwrap();
+    assert!(listener.accept().unwrap().is_nonblocking().unwrap());
+    listener.set_nonblocking(false).unwrap();
+    let _client = UdStream::connect(path).unwrap();
+    assert!(!listener.accept().unwrap().is_nonblocking().unwrap());
+}
+
+#[test]
+fn splice_to_file() {
+    let path = "/tmp/interprocess_udstream_test_splice.sock";
+    let file_path = "/tmp/interprocess_udstream_test_splice.bin";
+    let _ = remove_file(path);
+    let listener = UdStreamListener::bind(path).unwrap();
+    let mut client = UdStream::connect(path).unwrap();
+    let server = listener.accept().unwrap();
+    let data = (0..200_000).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+    let writer = std::thread::spawn(move || {
+        client.write_all(&data).unwrap();
+        data
+    });
+
+    let file = std::fs::File::create(file_path).unwrap();
+    // Stops at the requested amount, even though more data is available...
+    assert_eq!(server.splice_to_file(&file, 100_000).unwrap(), 100_000);
+    // ...and at end of file, even though more data was requested. The writer
+    // thread drops the client once it's done, which is what causes end of file.
+    assert_eq!(server.splice_to_file(&file, usize::MAX).unwrap(), 100_000);
+    let data = writer.join().unwrap();
+    drop(file);
+    assert_eq!(std::fs::read(file_path).unwrap(), data);
+    let _ = remove_file(file_path);
+}
+
+#[test]
+fn connect_immediate() {
+    let path = "/tmp/interprocess_udstream_test_immediate.sock";
+    let _ = remove_file(path);
+    let listener = UdStreamListener::bind(path).unwrap();
+    // Fill up the backlog without ever accepting.
+    let mut clients = Vec::new();
+    let error = loop {
+        match UdStream::connect_immediate(path) {
+            Ok(client) => clients.push(client),
+            Err(error) => break error,
+        }
+        assert!(clients.len() < 1024, "the backlog never filled up");
+    };
+    assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+    assert!(!clients[0].is_nonblocking().unwrap());
+    // Accepting one client makes room for another.
+    let _server = listener.accept().unwrap();
+    clients.push(UdStream::connect_immediate(path).unwrap());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn mark() {
+    let path = "/tmp/interprocess_udstream_test_mark.sock";
+    let _ = remove_file(path);
+    let _listener = UdStreamListener::bind(path).unwrap();
+    let client = UdStream::connect(path).unwrap();
+    assert_eq!(client.mark().unwrap(), 0);
+    match client.set_mark(42) {
+        Ok(()) => assert_eq!(client.mark().unwrap(), 42),
+        // Running without CAP_NET_ADMIN.
+        Err(e) => {
+            assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+            assert_eq!(client.mark().unwrap(), 0);
+        },
+    }
+}
+
+#[test]
+fn set_timeouts_rollback() {
+    let path = "/tmp/interprocess_udstream_test_timeouts.sock";
+    let _ = remove_file(path);
+    let _listener = UdStreamListener::bind(path).unwrap();
+    let client = UdStream::connect(path).unwrap();
+    let (read, write) = (Duration::from_secs(5), Duration::from_secs(7));
+    client.set_timeouts(Some(read), Some(write)).unwrap();
+    assert_eq!(client.read_timeout().unwrap(), Some(read));
+    assert_eq!(client.write_timeout().unwrap(), Some(write));
+
+    // A zero write timeout is rejected after the read timeout has been applied.
+    let error = client
+        .set_timeouts(Some(Duration::from_secs(1)), Some(Duration::from_secs(0)))
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(client.read_timeout().unwrap(), Some(read));
+    assert_eq!(client.write_timeout().unwrap(), Some(write));
+
+    client.set_timeouts(None, None).unwrap();
+    assert_eq!(client.read_timeout().unwrap(), None);
+    assert_eq!(client.write_timeout().unwrap(), None);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn wait_flushed() {
+    let path = "/tmp/interprocess_udstream_test_wait_flushed.sock";
+    let _ = remove_file(path);
+    let listener = UdStreamListener::bind(path).unwrap();
+    let mut client = UdStream::connect(path).unwrap();
+    let mut server = listener.accept().unwrap();
+    client.wait_flushed(None).unwrap();
+    client.write_all(&[0; 100]).unwrap();
+    let error = client.wait_flushed(Some(Duration::from_millis(20))).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    server.read_exact(&mut [0; 100]).unwrap();
+    client.wait_flushed(Some(Duration::from_secs(5))).unwrap();
+}
+
+#[test]
+fn bind_error() {
+    let path = "/tmp/interprocess_udstream_test_bind_error.sock";
+    let _ = remove_file(path);
+    let _listener = UdStreamListener::bind(path).unwrap();
+    let error = UdStreamListener::bind(path).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+    assert_eq!(error.raw_os_error(), Some(libc::EADDRINUSE));
+}
+
+#[test]
+fn bind_in_read_only_directory() {
+    use std::{fs, os::unix::fs::PermissionsExt};
+    if unsafe { libc::geteuid() } == 0 {
+        // The superuser can write into any directory.
+        return;
+    }
+    let dir = "/tmp/interprocess_udstream_test_read_only";
+    let _ = fs::create_dir(dir);
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o555)).unwrap();
+    let error = UdStreamListener::bind(format!("{}/socket", dir)).unwrap_err();
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+    assert_eq!(error.raw_os_error(), Some(libc::EACCES));
+}
+
+// Other systems don't necessarily record a reset as a pending error.
+#[cfg(target_os = "linux")]
+#[test]
+fn take_error() {
+    let path = "/tmp/interprocess_udstream_test_take_error.sock";
+    let _ = remove_file(path);
+    let listener = UdStreamListener::bind(path).unwrap();
+    let mut client = UdStream::connect(path).unwrap();
+    let server = listener.accept().unwrap();
+    assert!(client.take_error().unwrap().is_none());
+    // Closing a socket with unread data in it resets the connection, which the
+    // system records as the pending error of the other end.
+    client.write_all(b"never read").unwrap();
+    drop(server);
+    let error = client.take_error().unwrap().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::ConnectionReset);
+    assert!(client.take_error().unwrap().is_none());
+}
+
+#[test]
+fn take_error_after_failed_nonblocking_connect() {
+    use super::{imports::sockaddr_un, ToUdSocketPath};
+    use std::{mem::size_of, os::unix::io::FromRawFd};
+    use to_method::To;
+
+    let path = "/tmp/interprocess_udstream_test_take_error_connect.sock";
+    let _ = remove_file(path);
+    // Leaves the socket file behind without anyone listening on it.
+    drop(UdStreamListener::bind(path).unwrap());
+    let addr = path.to_socket_path().unwrap().try_to::<sockaddr_un>().unwrap();
+    let fd = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0) };
+    assert_ne!(fd, -1);
+    let client = unsafe { UdStream::from_raw_fd(fd) };
+    client.set_nonblocking(true).unwrap();
+    let success = unsafe {
+        libc::connect(
+            fd,
+            &addr as *const _ as *const _,
+            size_of::<sockaddr_un>() as u32,
+        )
+    } != -1;
+    assert!(!success);
+    let error = io::Error::last_os_error();
+    // Local connects don't go on in the background: they're refused right
+    // away rather than reporting EINPROGRESS and failing later.
+    assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLOUT,
+        revents: 0,
+    };
+    assert_ne!(unsafe { libc::poll(&mut pollfd, 1, 1000) }, -1);
+    // Since connect already reported the error, it isn't left pending.
+    assert!(client.take_error().unwrap().is_none());
+    let _ = remove_file(path);
+}
+
+#[test]
+fn recv_with_ancillary_into() {
+    use super::{AncillaryBuffer, AncillaryData};
+    use std::{
+        borrow::Cow,
+        io::IoSliceMut,
+        os::unix::{
+            io::{AsRawFd, FromRawFd},
+            net::UnixStream,
+        },
+    };
+
+    let path = "/tmp/interprocess_udstream_test_ancillary_into.sock";
+    let _ = remove_file(path);
+    let listener = UdStreamListener::bind(path).unwrap();
+    let client = UdStream::connect(path).unwrap();
+    let server = listener.accept().unwrap();
+
+    let mut abuf = AncillaryBuffer::for_file_descriptors(2);
+    let mut fds = Vec::with_capacity(2);
+    for round in 0_u8..3 {
+        let (reader, writer) = UnixStream::pair().unwrap();
+        let fd_list = [reader.as_raw_fd(), writer.as_raw_fd()];
+        let fd_data = AncillaryData::FileDescriptors(Cow::Borrowed(&fd_list[..]));
+        client.send_ancillary(&[round], vec![fd_data]).unwrap();
+        drop((reader, writer));
+
+        fds.clear();
+        let mut buf = [0; 1];
+        let bytes_read = server
+            .recv_with_ancillary_into(&mut [IoSliceMut::new(&mut buf)], &mut abuf, &mut fds)
+            .unwrap();
+        assert_eq!((bytes_read, buf[0]), (1, round));
+        assert!(!abuf.is_truncated());
+        assert_eq!(fds.len(), 2);
+        // The received descriptors are fresh copies of the connected pair.
+        let mut reader = unsafe { UnixStream::from_raw_fd(fds[0]) };
+        let mut writer = unsafe { UnixStream::from_raw_fd(fds[1]) };
+        writer.write_all(b"passed").unwrap();
+        let mut received = [0; 6];
+        reader.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"passed");
+    }
+}
+
+#[test]
+fn queue_lengths() {
+    let path = "/tmp/interprocess_udstream_test_queue_lengths.sock";
+    let _ = remove_file(path);
+    let listener = UdStreamListener::bind(path).unwrap();
+    let mut client = UdStream::connect(path).unwrap();
+    let mut server = listener.accept().unwrap();
+    assert_eq!(server.recv_queue_len().unwrap(), 0);
+    #[cfg(target_os = "linux")]
+    assert_eq!(client.send_queue_len().unwrap(), 0);
+    client.write_all(b"twelve bytes").unwrap();
+    assert_eq!(server.recv_queue_len().unwrap(), 12);
+    // Counts memory rather than bytes, so only the fact that it's nonzero is
+    // predictable.
+    #[cfg(target_os = "linux")]
+    assert_ne!(client.send_queue_len().unwrap(), 0);
+    server.read_exact(&mut [0; 5]).unwrap();
+    assert_eq!(server.recv_queue_len().unwrap(), 7);
+    server.read_exact(&mut [0; 7]).unwrap();
+    assert_eq!(server.recv_queue_len().unwrap(), 0);
+    #[cfg(target_os = "linux")]
+    assert_eq!(client.send_queue_len().unwrap(), 0);
+}
+
+#[test]
+fn forget_leaves_fd_open() {
+    use std::os::unix::io::FromRawFd;
+
+    let path = "/tmp/interprocess_udstream_test_forget.sock";
+    let _ = remove_file(path);
+    let listener = UdStreamListener::bind(path).unwrap();
+    let client = UdStream::connect(path).unwrap();
+    let mut server = listener.accept().unwrap();
+    let fd = client.forget();
+    assert_ne!(unsafe { libc::fcntl(fd, libc::F_GETFD) }, -1);
+    // Taking ownership back closes the socket normally.
+    let mut client = unsafe { UdStream::from_raw_fd(fd) };
+    client.write_all(b"still here").unwrap();
+    drop(client);
+    let mut received = Vec::new();
+    server.read_to_end(&mut received).unwrap();
+    assert_eq!(received, b"still here");
+}
+
+#[test]
+fn connect_without_server() {
+    let path = "/tmp/interprocess_udstream_test_stale.sock";
+    let _ = remove_file(path);
+    // Dropping the listener leaves the socket file behind.
+    drop(UdStreamListener::bind(path).unwrap());
+    let error = UdStream::connect(path).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+    let error = UdStream::connect_immediate(path).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+    remove_file(path).unwrap();
+    let error = UdStream::connect(path).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn read_framed() {
+    let path = "/tmp/interprocess_udstream_test_read_framed.sock";
+    let _ = remove_file(path);
+    let listener = UdStreamListener::bind(path).unwrap();
+    let mut client = UdStream::connect(path).unwrap();
+    let server = listener.accept().unwrap();
+    let body_len = |header: &[u8]| {
+        let len = usize::from(header[0]) << 8 | usize::from(header[1]);
+        if len > 16 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too big"));
+        }
+        Ok(len)
+    };
+
+    // Written in pieces which don't line up with the header and the body.
+    client.write_all(&[0]).unwrap();
+    client.write_all(&[5, b'h', b'e']).unwrap();
+    client.write_all(b"llo").unwrap();
+    let (header, body) = server.read_framed(2, body_len).unwrap();
+    assert_eq!(header, [0, 5]);
+    assert_eq!(body, b"hello");
+
+    client.write_all(&[1, 0]).unwrap();
+    let error = server.read_framed(2, body_len).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+    client.write_all(&[0, 4, b'a']).unwrap();
+    drop(client);
+    let error = server.read_framed(2, body_len).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[cfg(uds_scm_credentials)]
+#[test]
+fn datagram_credentials() {
+    let path = "/tmp/interprocess_udsocket_test_credentials.sock";
+    let _ = remove_file(path);
+    let receiver = UdSocket::bind(path).unwrap();
+    let sender = UdSocket::connect(path).unwrap();
+    sender.send(b"authenticate me").unwrap();
+    let mut buffer = [0_u8; 64];
+    let mut addr_buf = UdSocketPath::buffer();
+    let (len, truncated, credentials) =
+        receiver.recv_from_with_credentials(&mut buffer, &mut addr_buf).unwrap();
+    assert_eq!(&buffer[..len], b"authenticate me");
+    assert!(!truncated);
+    // Sockets with credential passing enabled are bound to an automatically
+    // generated name in the abstract namespace instead of being left unnamed.
+    #[cfg(uds_linux_namespace)]
+    assert!(matches!(addr_buf, UdSocketPath::Namespaced(..)));
+    let credentials = credentials.expect("no credentials received");
+    assert_eq!(credentials.pid as u32, process::id());
+    assert_eq!(credentials.uid, unsafe { libc::getuid() });
+    assert_eq!(credentials.gid, unsafe { libc::getgid() });
+}
+
+#[test]
+fn accept_batch() {
+    let path = "/tmp/interprocess_udstream_test_accept_batch.sock";
+    let _ = remove_file(path);
+    let listener = UdStreamListener::bind(path).unwrap();
+    let clients = (0..5).map(|_| UdStream::connect(path).unwrap()).collect::<Vec<_>>();
+    assert_eq!(listener.accept_batch(3).unwrap().len(), 3);
+    // Returns the remaining ones without waiting for a full batch.
+    assert_eq!(listener.accept_batch(10).unwrap().len(), 2);
+    assert!(listener.accept_batch(0).unwrap().is_empty());
+    listener.set_nonblocking(true).unwrap();
+    let error = listener.accept_batch(10).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+    drop(clients);
+
+    // The batch ends at the connection limit.
+    let limited_path = "/tmp/interprocess_udstream_test_accept_batch_limited.sock";
+    let _ = remove_file(limited_path);
+    let two = NonZeroUsize::new(2).unwrap();
+    let listener =
+        UdStreamListener::bind_with_limit(limited_path, two, OverflowPolicy::Queue).unwrap();
+    let _clients = (0..3).map(|_| UdStream::connect(limited_path).unwrap()).collect::<Vec<_>>();
+    assert_eq!(listener.accept_batch(10).unwrap().len(), 2);
+}
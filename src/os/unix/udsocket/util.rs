@@ -8,6 +8,7 @@ use std::{
     io::{self, IoSlice, IoSliceMut},
     mem::{size_of, size_of_val, zeroed},
     ptr::null,
+    time::Duration,
 };
 use std::convert::TryInto;
 use to_method::To;
@@ -130,6 +131,26 @@ pub unsafe fn raw_get_nonblocking(socket: i32) -> io::Result<bool> {
         Err(io::Error::last_os_error())
     }
 }
+pub unsafe fn raw_set_cloexec(socket: i32, cloexec: bool) -> io::Result<()> {
+    let (old_flags, success) = unsafe {
+        let result = libc::fcntl(socket, libc::F_GETFD);
+        (result, result != -1)
+    };
+    if !success {
+        return Err(io::Error::last_os_error());
+    }
+    let new_flags = if cloexec {
+        old_flags | libc::FD_CLOEXEC
+    } else {
+        old_flags & !libc::FD_CLOEXEC
+    };
+    let success = unsafe { libc::fcntl(socket, libc::F_SETFD, new_flags) } != -1;
+    if success {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
 #[cfg(uds_supported)]
 pub unsafe fn raw_shutdown(socket: i32, how: Shutdown) -> io::Result<()> {
     let how = match how {
@@ -144,6 +165,167 @@ pub unsafe fn raw_shutdown(socket: i32, how: Shutdown) -> io::Result<()> {
         Err(io::Error::last_os_error())
     }
 }
+#[cfg(target_os = "linux")]
+pub unsafe fn raw_set_mark(socket: i32, mark: u32) -> io::Result<()> {
+    let success = unsafe {
+        libc::setsockopt(
+            socket,
+            SOL_SOCKET,
+            libc::SO_MARK,
+            &mark as *const _ as *const _,
+            size_of_val(&mark) as socklen_t,
+        )
+    } != -1;
+    if success {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+#[cfg(target_os = "linux")]
+pub unsafe fn raw_get_mark(socket: i32) -> io::Result<u32> {
+    let mut mark: u32 = 0;
+    let mut mark_len = size_of_val(&mark) as socklen_t;
+    let success = unsafe {
+        libc::getsockopt(
+            socket,
+            SOL_SOCKET,
+            libc::SO_MARK,
+            &mut mark as *mut _ as *mut _,
+            &mut mark_len as *mut _,
+        )
+    } != -1;
+    if success {
+        Ok(mark)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+/// Returns the amount of bytes which have been received by the socket but not
+/// yet read from it (`FIONREAD`).
+#[cfg(uds_supported)]
+pub unsafe fn raw_get_recv_queue_len(socket: i32) -> io::Result<usize> {
+    let mut len: c_int = 0;
+    let success = unsafe { libc::ioctl(socket, libc::FIONREAD, &mut len as *mut _) } != -1;
+    if success {
+        Ok(len as usize)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+/// Returns the amount of bytes which have been written into the socket but
+/// not yet read by the peer (`SIOCOUTQ`).
+#[cfg(target_os = "linux")]
+pub unsafe fn raw_get_send_queue_len(socket: i32) -> io::Result<usize> {
+    let mut len: c_int = 0;
+    // SIOCOUTQ is the same request as TIOCOUTQ, which is the one libc provides.
+    let success = unsafe { libc::ioctl(socket, libc::TIOCOUTQ, &mut len as *mut _) } != -1;
+    if success {
+        Ok(len as usize)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+/// Sets `SO_RCVTIMEO` or `SO_SNDTIMEO`, as specified by `option`, with the same
+/// semantics as the timeout setters on `std::net::TcpStream`: `None` disables
+/// the timeout and a zero duration is an error.
+#[cfg(uds_supported)]
+pub unsafe fn raw_set_timeout(
+    socket: i32,
+    option: c_int,
+    timeout: Option<Duration>,
+) -> io::Result<()> {
+    let timeout = match timeout {
+        Some(timeout) if timeout == Duration::from_secs(0) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot set a zero duration timeout",
+            ));
+        },
+        Some(timeout) => {
+            let mut timeout = timeval {
+                tv_sec:  timeout.as_secs().try_into().unwrap_or(libc::time_t::MAX),
+                tv_usec: timeout.subsec_micros() as _,
+            };
+            // A zero timeval would disable the timeout, so round sub-microsecond
+            // durations up instead.
+            if timeout.tv_sec == 0 && timeout.tv_usec == 0 {
+                timeout.tv_usec = 1;
+            }
+            timeout
+        },
+        None => timeval {
+            tv_sec:  0,
+            tv_usec: 0,
+        },
+    };
+    let success = unsafe {
+        libc::setsockopt(
+            socket,
+            SOL_SOCKET,
+            option,
+            &timeout as *const _ as *const _,
+            size_of_val(&timeout) as socklen_t,
+        )
+    } != -1;
+    if success {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+/// Retrieves `SO_RCVTIMEO` or `SO_SNDTIMEO`, as specified by `option`.
+#[cfg(uds_supported)]
+pub unsafe fn raw_get_timeout(socket: i32, option: c_int) -> io::Result<Option<Duration>> {
+    let mut timeout = timeval {
+        tv_sec:  0,
+        tv_usec: 0,
+    };
+    let mut timeout_len = size_of_val(&timeout) as socklen_t;
+    let success = unsafe {
+        libc::getsockopt(
+            socket,
+            SOL_SOCKET,
+            option,
+            &mut timeout as *mut _ as *mut _,
+            &mut timeout_len as *mut _,
+        )
+    } != -1;
+    if !success {
+        return Err(io::Error::last_os_error());
+    }
+    if timeout.tv_sec == 0 && timeout.tv_usec == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(
+            Duration::from_secs(timeout.tv_sec as u64)
+                + Duration::from_micros(timeout.tv_usec as u64),
+        ))
+    }
+}
+/// Retrieves and clears the pending error of the socket (`SO_ERROR`).
+#[cfg(uds_supported)]
+pub unsafe fn raw_take_error(socket: i32) -> io::Result<Option<io::Error>> {
+    let mut error: c_int = 0;
+    let mut error_len = size_of_val(&error) as socklen_t;
+    let success = unsafe {
+        libc::getsockopt(
+            socket,
+            SOL_SOCKET,
+            SO_ERROR,
+            &mut error as *mut _ as *mut _,
+            &mut error_len as *mut _,
+        )
+    } != -1;
+    if !success {
+        return Err(io::Error::last_os_error());
+    }
+    if error == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(io::Error::from_raw_os_error(error)))
+    }
+}
 pub fn empty_cstring() -> CString {
     unsafe {
         // SAFETY: the value returned by Vec::new() is always empty, thus it
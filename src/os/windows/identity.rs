@@ -0,0 +1,106 @@
+use super::{imports::*, FileHandleOps};
+use std::{ffi::OsString, io, mem::size_of, process, ptr, slice};
+
+/// The identity of a process as seen from the other end of a connection.
+///
+/// Returned by [`own_credentials`] for the current process and by
+/// `client_credentials` on server-side pipe streams for the client, so that a
+/// handshake can compare the two directly.
+///
+/// [`own_credentials`]: fn.own_credentials.html " "
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PeerCredentials {
+    /// The process identifier, as returned by `client_process_id` and
+    /// `server_process_id` on pipe streams or `peer_pid` on local sockets.
+    pub pid: u32,
+    /// The security identifier (SID) of the user which the process runs as, in
+    /// its string form (`S-1-5-21-…`).
+    pub sid: OsString,
+}
+
+/// Retrieves the identity of the current process in the same form as the
+/// identity of a client returned by `client_credentials` on server-side pipe
+/// streams, allowing the two to be compared directly.
+///
+/// This is meant for handshakes where both sides of a connection exchange their
+/// own identity up front. The process identifier is the one from
+/// [`std::process::id`].
+///
+/// # System calls
+/// - `OpenProcessToken`
+/// - `GetTokenInformation`
+/// - `ConvertSidToStringSidW`
+/// - `LocalFree`
+/// - `CloseHandle`
+///
+/// [`std::process::id`]: https://doc.rust-lang.org/std/process/fn.id.html " "
+pub fn own_credentials() -> io::Result<PeerCredentials> {
+    let token = {
+        let mut token: HANDLE = ptr::null_mut();
+        let success =
+            unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token as *mut _) }
+                != 0;
+        if !success {
+            return Err(io::Error::last_os_error());
+        }
+        // Only used to close the token handle once we're done with it.
+        unsafe {
+            // SAFETY: we just opened the handle and own it
+            FileHandleOps::from_raw_handle(token)
+        }
+    };
+    Ok(PeerCredentials {
+        pid: process::id(),
+        sid: unsafe {
+            // SAFETY: we just opened the token
+            token_user_sid(token.0)
+        }?,
+    })
+}
+
+/// Retrieves the SID of the user which the specified token belongs to, in its
+/// string form.
+///
+/// # Safety
+/// The token must be a valid handle opened with `TOKEN_QUERY` access.
+pub(crate) unsafe fn token_user_sid(token: HANDLE) -> io::Result<OsString> {
+    // The first call fails with ERROR_INSUFFICIENT_BUFFER and reports the size
+    // of the buffer which is needed.
+    let mut len: DWORD = 0;
+    unsafe { GetTokenInformation(token, TokenUser, ptr::null_mut(), 0, &mut len as *mut _) };
+    if len == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // TOKEN_USER contains a pointer, so the buffer is made out of usizes to align
+    // it properly, rounding up to fit all of it.
+    let mut buffer = vec![0_usize; len as usize / size_of::<usize>() + 1];
+    let success = unsafe {
+        GetTokenInformation(
+            token,
+            TokenUser,
+            buffer.as_mut_ptr() as *mut _,
+            len,
+            &mut len as *mut _,
+        )
+    } != 0;
+    if !success {
+        return Err(io::Error::last_os_error());
+    }
+    let sid = unsafe {
+        // SAFETY: the system has just filled the buffer with a TOKEN_USER structure
+        (*(buffer.as_ptr() as *const TOKEN_USER)).User.Sid
+    };
+
+    let mut string_sid: LPWSTR = ptr::null_mut();
+    let success = unsafe { ConvertSidToStringSidW(sid, &mut string_sid as *mut _) } != 0;
+    if !success {
+        return Err(io::Error::last_os_error());
+    }
+    let sid_string = unsafe {
+        // SAFETY: the system has allocated a nul-terminated string for us
+        let len = (0..).take_while(|&i| *string_sid.add(i) != 0).count();
+        OsString::from_wide(slice::from_raw_parts(string_sid, len))
+    };
+    unsafe { LocalFree(string_sid as *mut _) };
+    Ok(sid_string)
+}
@@ -12,27 +12,37 @@ macro_rules! fake_consts {
 cfg_if! {
     if #[cfg(windows)] {
         pub(super) use winapi::{
-            shared::{minwindef::{DWORD, LPVOID}, ntdef::HANDLE, winerror::ERROR_PIPE_CONNECTED},
+            shared::{minwindef::{DWORD, LPVOID}, ntdef::HANDLE, sddl::ConvertSidToStringSidW, winerror::{ERROR_ACCESS_DENIED, ERROR_CANT_OPEN_ANONYMOUS, ERROR_MORE_DATA, ERROR_NO_TOKEN, ERROR_PIPE_BUSY, ERROR_PIPE_CONNECTED}},
             um::{
                 winbase::{
                     FILE_FLAG_FIRST_PIPE_INSTANCE, FILE_FLAG_WRITE_THROUGH, FILE_FLAG_OVERLAPPED, PIPE_ACCESS_DUPLEX, PIPE_ACCESS_INBOUND,
                     PIPE_ACCESS_OUTBOUND, PIPE_READMODE_BYTE, PIPE_READMODE_MESSAGE,
                     PIPE_TYPE_BYTE, PIPE_TYPE_MESSAGE, PIPE_NOWAIT, PIPE_REJECT_REMOTE_CLIENTS,
                 },
-                winnt::{FILE_SHARE_READ, FILE_SHARE_WRITE, GENERIC_READ, GENERIC_WRITE},
-                fileapi::{CreateFileW, OPEN_EXISTING, FlushFileBuffers, ReadFile, WriteFile},
-                handleapi::{CloseHandle, DuplicateHandle, INVALID_HANDLE_VALUE},
+                winnt::{DUPLICATE_SAME_ACCESS, FILE_SHARE_READ, FILE_SHARE_WRITE, GENERIC_READ, GENERIC_WRITE},
+                fileapi::{CreateFileW, OPEN_EXISTING, FindClose, FindFirstFileW, FlushFileBuffers, ReadFile, WriteFile},
+                handleapi::{CloseHandle, DuplicateHandle, SetHandleInformation, INVALID_HANDLE_VALUE},
                 namedpipeapi::{
                     ConnectNamedPipe, DisconnectNamedPipe,
                     PeekNamedPipe,
                     CreatePipe, CreateNamedPipeW, SetNamedPipeHandleState,
+                    GetNamedPipeHandleStateW, GetNamedPipeInfo,
+                    ImpersonateNamedPipeClient,
                 },
                 winbase::{
                     GetNamedPipeClientProcessId, GetNamedPipeClientSessionId,
                     GetNamedPipeServerProcessId, GetNamedPipeServerSessionId,
                 },
-                minwinbase::SECURITY_ATTRIBUTES,
-                processthreadsapi::GetCurrentProcess,
+                minwinbase::{SECURITY_ATTRIBUTES, WIN32_FIND_DATAW},
+                ioapiset::CancelSynchronousIo,
+                synchapi::{CreateEventW, ResetEvent, SetEvent},
+                processthreadsapi::{GetCurrentProcess, GetCurrentThread, OpenProcessToken, OpenThreadToken},
+                securitybaseapi::{GetTokenInformation, RevertToSelf},
+                winbase::{LocalFree, HANDLE_FLAG_INHERIT},
+                winnt::{
+                    TokenImpersonationLevel, TokenUser, LPWSTR, SECURITY_IMPERSONATION_LEVEL, TOKEN_QUERY, TOKEN_USER,
+                    SecurityAnonymous, SecurityDelegation, SecurityIdentification, SecurityImpersonation,
+                },
             },
         };
         pub(super) use std::os::windows::{io::{AsRawHandle, FromRawHandle, IntoRawHandle}, ffi::{OsStrExt, OsStringExt}};
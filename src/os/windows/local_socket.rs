@@ -4,18 +4,38 @@ use super::named_pipe::{
     PipeListenerOptions,
     PipeMode,
 };
-use crate::local_socket::{LocalSocketName, NameTypeSupport, ToLocalSocketName};
+use crate::{
+    local_socket::{Availability, InputState, LocalSocketName, NameTypeSupport, ToLocalSocketName},
+    Connection,
+    OverflowPolicy,
+};
 use std::{
     borrow::Cow,
+    convert::TryFrom,
     ffi::{c_void, OsStr, OsString},
     fmt::{self, Debug, Formatter},
+    fs::File,
     io::{self, prelude::*, IoSlice, IoSliceMut},
-    os::windows::io::{AsRawHandle, FromRawHandle, IntoRawHandle},
+    num::{NonZeroU8, NonZeroUsize},
+    os::windows::{
+        ffi::OsStrExt,
+        io::{AsRawHandle, FromRawHandle, IntoRawHandle},
+    },
     ptr,
     sync::atomic::{AtomicU8, Ordering::Relaxed},
 };
 use to_method::To;
-use winapi::um::{namedpipeapi::GetNamedPipeInfo, winbase::PIPE_SERVER_END};
+use winapi::{
+    shared::{
+        minwindef::DWORD,
+        winerror::{ERROR_FILE_NOT_FOUND, ERROR_NOT_FOUND, ERROR_SEM_TIMEOUT},
+    },
+    um::{
+        ioapiset::CancelIoEx,
+        namedpipeapi::{GetNamedPipeInfo, PeekNamedPipe, WaitNamedPipeW},
+        winbase::PIPE_SERVER_END,
+    },
+};
 
 type PipeListener = GenericPipeListener<PipeStream>;
 
@@ -29,6 +49,31 @@ impl LocalSocketListener {
             PipeListenerOptions::new().name(name.into_inner()).mode(PipeMode::Bytes).create()?;
         Ok(Self { inner })
     }
+    pub fn bind_with_limit<'a>(
+        name: impl ToLocalSocketName<'a>,
+        max_connections: NonZeroUsize,
+        overflow_policy: OverflowPolicy,
+    ) -> io::Result<Self> {
+        // 255 is reserved by the system to mean "no limit".
+        let instance_limit = u8::try_from(max_connections.get())
+            .ok()
+            .filter(|&limit| limit != 255)
+            .and_then(NonZeroU8::new)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "named pipes cannot have a connection limit above 254",
+                )
+            })?;
+        let name = name.to_local_socket_name()?;
+        let inner = PipeListenerOptions::new()
+            .name(name.into_inner())
+            .mode(PipeMode::Bytes)
+            .instance_limit(instance_limit)
+            .overflow_policy(overflow_policy)
+            .create()?;
+        Ok(Self { inner })
+    }
     pub fn accept(&self) -> io::Result<LocalSocketStream> {
         let inner = self.inner.accept()?;
         Ok(LocalSocketStream {
@@ -39,6 +84,26 @@ impl LocalSocketListener {
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         self.inner.set_nonblocking(nonblocking)
     }
+    pub fn wake_accepts(&self) {
+        self.inner.wake_accepts();
+    }
+    pub fn into_inheritable_handle(self) -> io::Result<*mut c_void> {
+        self.inner.into_inheritable_handle()
+    }
+    pub unsafe fn from_inherited_handle<'a>(
+        handle: *mut c_void,
+        name: impl ToLocalSocketName<'a>,
+    ) -> io::Result<Self> {
+        let name = name.to_local_socket_name()?;
+        let inner = unsafe {
+            // SAFETY: as per safety contract
+            PipeListenerOptions::new()
+                .name(name.into_inner())
+                .mode(PipeMode::Bytes)
+                .create_from_inherited(handle)
+        }?;
+        Ok(Self { inner })
+    }
 }
 impl Debug for LocalSocketListener {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -74,6 +139,14 @@ impl LocalSocketStream {
             server_or_client: AtomicU8::new(ServerOrClient::Client as _),
         })
     }
+    pub fn connect_immediate<'a>(name: impl ToLocalSocketName<'a>) -> io::Result<Self> {
+        let name = name.to_local_socket_name()?;
+        let inner = PipeStream::connect_immediate(name.inner())?;
+        Ok(Self {
+            inner,
+            server_or_client: AtomicU8::new(ServerOrClient::Client as _),
+        })
+    }
     pub fn peer_pid(&self) -> io::Result<u32> {
         match self.server_or_client.load(Relaxed).to::<ServerOrClient>() {
             ServerOrClient::Server => self.inner.client_process_id(),
@@ -102,9 +175,77 @@ impl LocalSocketStream {
             },
         }
     }
+    pub fn read_chunks(
+        &mut self,
+        chunk_size: usize,
+        f: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> io::Result<u64> {
+        crate::read_chunks(chunk_size, self.read_until_disconnect(), f)
+    }
+    pub fn splice_to_file(
+        &mut self,
+        file: &File,
+        count: usize,
+        progress: &mut dyn FnMut(),
+    ) -> io::Result<usize> {
+        let mut read = self.read_until_disconnect();
+        let read = |buf: &mut [u8]| {
+            let result = read(buf);
+            if matches!(result, Ok(bytes_read) if bytes_read > 0) {
+                progress();
+            }
+            result
+        };
+        crate::copy_to_writer(count, read, &mut &*file)
+    }
+    /// Returns a closure which reads from the stream, reporting the other side
+    /// disconnecting as end of file rather than as a broken pipe, which is what
+    /// named pipes do.
+    fn read_until_disconnect(&mut self) -> impl FnMut(&mut [u8]) -> io::Result<usize> + '_ {
+        move |buf| match self.inner.read(buf) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(0),
+            result => result,
+        }
+    }
+    pub fn server_instance_count(&self) -> io::Result<u32> {
+        self.inner.handle_state().map(|state| state.current_instances)
+    }
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         self.inner.set_nonblocking(nonblocking)
     }
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.peek(buf)
+    }
+    pub fn shutdown(&self) -> io::Result<()> {
+        self.inner.shutdown()
+    }
+    pub(crate) fn input_state(&self) -> io::Result<InputState> {
+        let mut available: DWORD = 0;
+        let success = unsafe {
+            PeekNamedPipe(
+                self.as_raw_handle(),
+                ptr::null_mut(),
+                0,
+                ptr::null_mut(),
+                &mut available as *mut _,
+                ptr::null_mut(),
+            )
+        } != 0;
+        if !success {
+            let error = io::Error::last_os_error();
+            return match error.kind() {
+                io::ErrorKind::BrokenPipe | io::ErrorKind::NotConnected => {
+                    Ok(InputState::Disconnected)
+                },
+                _ => Err(error),
+            };
+        }
+        Ok(if available == 0 {
+            InputState::Empty
+        } else {
+            InputState::Pending
+        })
+    }
 }
 impl Read for LocalSocketStream {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
@@ -125,6 +266,25 @@ impl Write for LocalSocketStream {
         self.inner.flush()
     }
 }
+impl Read for &LocalSocketStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&self.inner).read(buf)
+    }
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        (&self.inner).read_vectored(bufs)
+    }
+}
+impl Write for &LocalSocketStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&self.inner).write(buf)
+    }
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        (&self.inner).write_vectored(bufs)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.inner).flush()
+    }
+}
 impl Debug for LocalSocketStream {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("LocalSocketStream").field("handle", &self.as_raw_handle()).finish()
@@ -153,8 +313,52 @@ impl FromRawHandle for LocalSocketStream {
     }
 }
 
+/// What a `CancelToken` cancels the pending I/O of.
+#[derive(Debug)]
+pub(crate) struct CancelTarget(*mut c_void);
+// SAFETY: the handle is only ever used to call CancelIoEx, which can be done
+// from any thread.
+unsafe impl Send for CancelTarget {
+}
+unsafe impl Sync for CancelTarget {
+}
+impl CancelTarget {
+    pub fn new(stream: &LocalSocketStream) -> Self {
+        Self(stream.as_raw_handle())
+    }
+    /// Cancels all pending I/O on the handle, issued by any thread.
+    pub fn cancel(&self) -> io::Result<()> {
+        let success = unsafe { CancelIoEx(self.0, ptr::null_mut()) } != 0;
+        let error = io::Error::last_os_error();
+        // Nothing being in progress is not an error, since the stream checks for
+        // cancellation before starting new operations.
+        if success || error.raw_os_error() == Some(ERROR_NOT_FOUND as i32) {
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+}
+
 pub const NAME_TYPE_ALWAYS_SUPPORTED: NameTypeSupport = NameTypeSupport::OnlyNamespaced;
 
+pub fn check_availability(name: &LocalSocketName<'_>) -> io::Result<Availability> {
+    let mut path = OsString::from(r"\\.\pipe\");
+    path.push(name.inner());
+    let path = path.encode_wide().chain(Some(0)).collect::<Vec<u16>>();
+    let success = unsafe { WaitNamedPipeW(path.as_ptr(), 1) } != 0;
+    if success {
+        return Ok(Availability::InUseByLiveServer);
+    }
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(code) if code == ERROR_FILE_NOT_FOUND as i32 => Ok(Availability::Free),
+        // Every instance is busy.
+        Some(code) if code == ERROR_SEM_TIMEOUT as i32 => Ok(Availability::InUseByLiveServer),
+        _ => Err(error),
+    }
+}
+
 pub fn name_type_support_query() -> NameTypeSupport {
     NAME_TYPE_ALWAYS_SUPPORTED
 }
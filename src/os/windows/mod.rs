@@ -12,6 +12,9 @@ pub mod unnamed_pipe;
 #[cfg(windows)]
 pub(crate) mod local_socket;
 
+mod identity;
+pub use identity::*;
+
 mod imports;
 use imports::*;
 
@@ -140,6 +143,28 @@ impl FileHandleOps {
             Err(io::Error::last_os_error())
         }
     }
+    /// Duplicates the handle within the current process, producing one which
+    /// refers to the same object but is closed independently of this one.
+    pub fn duplicate(&self, inheritable: bool) -> io::Result<Self> {
+        let (success, new_handle) = unsafe {
+            let mut new_handle = INVALID_HANDLE_VALUE;
+            let success = DuplicateHandle(
+                GetCurrentProcess(),
+                self.0,
+                GetCurrentProcess(),
+                &mut new_handle,
+                0,
+                inheritable as _,
+                DUPLICATE_SAME_ACCESS,
+            );
+            (success != 0, new_handle)
+        };
+        if success {
+            Ok(Self(new_handle))
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
 }
 impl Drop for FileHandleOps {
     fn drop(&mut self) {
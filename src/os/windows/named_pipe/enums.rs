@@ -226,3 +226,58 @@ impl TryFrom<DWORD> for PipeMode {
         }
     }
 }
+
+/// The outcome of reading a message from a message-mode pipe into a buffer of
+/// limited size, as returned by the `read_msg_part` method on message reader
+/// pipe streams.
+///
+/// When a message does not fit into the buffer, `ReadFile` fills the buffer
+/// with its beginning and fails with `ERROR_MORE_DATA`, leaving the rest of the
+/// message in the pipe to be retrieved by subsequent reads. This enumeration
+/// tells those two cases apart without forcing the caller to inspect raw
+/// error codes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ReadMsgOutcome {
+    /// The message was received in its entirety, or its last part was if it
+    /// was previously read partially. Contains the amount of bytes read.
+    Complete(usize),
+    /// Only a part of the message fit into the buffer. Contains the amount of
+    /// bytes read, which is always the size of the buffer. The next read
+    /// continues with the rest of the same message.
+    Partial(usize),
+}
+impl ReadMsgOutcome {
+    /// Returns the amount of bytes read, regardless of whether the message was
+    /// received completely.
+    pub const fn bytes_read(self) -> usize {
+        match self {
+            Self::Complete(n) | Self::Partial(n) => n,
+        }
+    }
+    /// Returns `true` if the end of the message has been reached.
+    pub const fn is_complete(self) -> bool {
+        matches!(self, Self::Complete(..))
+    }
+}
+
+/// How the buffers which messages are received into by `recv_msgs_upto` are
+/// sized, set with `set_grow_strategy` on message-reading pipe streams.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GrowStrategy {
+    /// Every message is received into a buffer of exactly its size, which is
+    /// found out by peeking into the pipe before reading. The memory used by
+    /// the received buffers never exceeds the size of the messages, at the
+    /// cost of an extra system call per message. This is the default.
+    ExactFit,
+    /// Messages are received into buffers of the biggest size seen so far on
+    /// the stream, which is doubled whenever a message does not fit. Messages
+    /// of similar sizes then never need more than one read and one allocation
+    /// each, but every buffer keeps its spare capacity and may thus hold
+    /// several times as much memory as its message needs.
+    Doubling,
+}
+impl Default for GrowStrategy {
+    fn default() -> Self {
+        Self::ExactFit
+    }
+}
@@ -0,0 +1,144 @@
+use super::{super::imports::*, PipeMode};
+use std::{ffi::OsString, io, ptr};
+
+/// The state of a named pipe handle, as reported by the system.
+///
+/// This is the read-side counterpart of the configuration methods on pipe
+/// streams, such as `set_nonblocking`: it reflects the state which the handle
+/// actually has, regardless of whether it was set during creation of the
+/// stream or changed afterwards. Retrieved via the `handle_state` method on
+/// pipe streams.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct HandleState {
+    /// Whether the handle is in nonblocking mode.
+    pub nonblocking:          bool,
+    /// The amount of instances of the pipe which currently exist.
+    ///
+    /// Since every instance either serves a client or waits for one, this can
+    /// be used by clients to gauge how busy a server is, e.g. to pick the least
+    /// loaded one out of several. The value is a snapshot which may already be
+    /// out of date by the time it's returned, as clients connect and the server
+    /// creates instances concurrently.
+    pub current_instances:    u32,
+    /// The mode in which data is read from the handle. For pipes which are
+    /// only written to via this handle, this still reflects the mode set on
+    /// it, even though it has no effect.
+    pub read_mode:            PipeMode,
+    /// The maximum number of bytes which are collected on the client's
+    /// computer before being transmitted to the server.
+    ///
+    /// The system only keeps track of this for client handles of pipes which
+    /// are connected to a server on a different computer; in all other
+    /// cases, this is `None`.
+    pub max_collection_count: Option<u32>,
+    /// The maximum amount of time, in milliseconds, which can pass before a
+    /// remote named pipe transfers information over the network.
+    ///
+    /// Like with `max_collection_count`, this is `None` unless the handle is
+    /// the client end of a connection to a server on a different computer.
+    pub collect_data_timeout: Option<u32>,
+    /// The name of the user associated with the client application.
+    ///
+    /// Only available for the server end of the connection, and only if the
+    /// client opened the pipe with the permission to be impersonated (which is
+    /// the default for clients); `None` otherwise.
+    pub user_name:            Option<OsString>,
+}
+
+/// Maximum length of a user name, not including the terminating nul (`UNLEN`).
+const MAX_USER_NAME_LEN: usize = 256;
+
+/// Queries the state of the specified pipe handle.
+///
+/// The system refuses to fill out some of the fields depending on the side of
+/// the connection and on whether the client is remote, so those are only asked
+/// for where they can be available, and the query is repeated without them if
+/// the system could not provide them.
+///
+/// # Safety
+/// The handle must be a valid named pipe handle.
+pub(super) unsafe fn get_handle_state(handle: HANDLE, is_server: bool) -> io::Result<HandleState> {
+    let mut state: DWORD = 0;
+    let mut current_instances: DWORD = 0;
+    let mut max_collection_count: DWORD = 0;
+    let mut collect_data_timeout: DWORD = 0;
+    let mut user_name = [0_u16; MAX_USER_NAME_LEN + 1];
+
+    let mut query = |extra: bool| -> bool {
+        let (max_collection_count_ptr, collect_data_timeout_ptr) = if extra && !is_server {
+            (
+                &mut max_collection_count as *mut DWORD,
+                &mut collect_data_timeout as *mut DWORD,
+            )
+        } else {
+            (ptr::null_mut(), ptr::null_mut())
+        };
+        let (user_name_ptr, user_name_len) = if extra && is_server {
+            (user_name.as_mut_ptr(), user_name.len() as DWORD)
+        } else {
+            (ptr::null_mut(), 0)
+        };
+        unsafe {
+            GetNamedPipeHandleStateW(
+                handle,
+                &mut state as *mut _,
+                &mut current_instances as *mut _,
+                max_collection_count_ptr,
+                collect_data_timeout_ptr,
+                user_name_ptr,
+                user_name_len,
+            ) != 0
+        }
+    };
+    let has_extra = if query(true) {
+        true
+    } else if query(false) {
+        false
+    } else {
+        return Err(io::Error::last_os_error());
+    };
+
+    let user_name = if has_extra && is_server {
+        let len = user_name.iter().position(|&c| c == 0).unwrap_or(user_name.len());
+        Some(OsString::from_wide(&user_name[..len]))
+    } else {
+        None
+    };
+    let collection_info = has_extra && !is_server;
+    Ok(HandleState {
+        nonblocking: state & PIPE_NOWAIT != 0,
+        current_instances,
+        read_mode: if state & PIPE_READMODE_MESSAGE != 0 {
+            PipeMode::Messages
+        } else {
+            PipeMode::Bytes
+        },
+        max_collection_count: Some(max_collection_count).filter(|_| collection_info),
+        collect_data_timeout: Some(collect_data_timeout).filter(|_| collection_info),
+        user_name,
+    })
+}
+
+/// Retrieves the maximum amount of instances of the pipe which the specified
+/// handle belongs to.
+///
+/// # Safety
+/// The handle must be a valid named pipe handle.
+pub(super) unsafe fn get_max_instances(handle: HANDLE) -> io::Result<u32> {
+    let mut max_instances: DWORD = 0;
+    let success = unsafe {
+        GetNamedPipeInfo(
+            handle,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &mut max_instances as *mut _,
+        )
+    } != 0;
+    if success {
+        Ok(max_instances)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
@@ -0,0 +1,177 @@
+use super::super::{identity::token_user_sid, imports::*, FileHandleOps, PeerCredentials};
+use std::{io, mem::size_of_val, process, ptr};
+
+/// How far a client lets the server act on its behalf, as chosen by the client
+/// when connecting (the `SECURITY_SQOS_PRESENT` flags of `CreateFile`).
+///
+/// The levels are ordered from the least to the most permissive, so they can
+/// be compared to check whether a client grants at least a certain level.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ImpersonationLevel {
+    /// The server cannot obtain any information about the client.
+    Anonymous,
+    /// The server can obtain the identity and privileges of the client, but
+    /// cannot act as the client.
+    Identification,
+    /// The server can act as the client on the local system. This is what
+    /// clients grant by default.
+    Impersonation,
+    /// The server can act as the client on remote systems as well.
+    Delegation,
+}
+
+/// Checks whether the current thread is impersonating someone, e.g. the client
+/// of a named pipe via [`impersonate_client_scoped`].
+///
+/// This reflects the state of the calling thread only: impersonation is a
+/// per-thread property, so the answer can differ between threads of the same
+/// process, and a thread spawned while impersonating is not impersonating.
+/// Meant for security-sensitive code deep in a call stack which wants to
+/// verify that it runs with the identity it expects.
+///
+/// # System calls
+/// - `OpenThreadToken`
+/// - `CloseHandle`
+///
+/// [`impersonate_client_scoped`]: struct.DuplexBytePipeStream.html#method.impersonate_client_scoped " "
+pub fn is_impersonating() -> io::Result<bool> {
+    let mut token: HANDLE = ptr::null_mut();
+    // Only threads which are impersonating have a token of their own; the rest use
+    // the one of the process.
+    let opened =
+        unsafe { OpenThreadToken(GetCurrentThread(), TOKEN_QUERY, 1, &mut token as *mut _) } != 0;
+    if opened {
+        drop(unsafe {
+            // SAFETY: we just opened the handle and own it
+            FileHandleOps::from_raw_handle(token)
+        });
+        return Ok(true);
+    }
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(code) if code == ERROR_NO_TOKEN as i32 => Ok(false),
+        // Impersonating an anonymous client leaves a token which cannot be opened.
+        Some(code) if code == ERROR_CANT_OPEN_ANONYMOUS as i32 => Ok(true),
+        _ => Err(error),
+    }
+}
+
+/// Impersonates the client connected to the specified server-side pipe handle
+/// on the current thread for the duration of the closure.
+///
+/// The impersonation is undone by a drop guard, so that it ends even if the
+/// closure panics. If it cannot be undone, the process is aborted, since
+/// carrying on would mean running with the client's identity.
+///
+/// # Safety
+/// The handle must be a valid server-side named pipe handle.
+pub(super) unsafe fn impersonate_client_scoped<R>(
+    handle: HANDLE,
+    f: impl FnOnce() -> R,
+) -> io::Result<R> {
+    let success = unsafe { ImpersonateNamedPipeClient(handle) } != 0;
+    if !success {
+        return Err(io::Error::last_os_error());
+    }
+    let _guard = RevertGuard;
+    Ok(f())
+}
+struct RevertGuard;
+impl Drop for RevertGuard {
+    fn drop(&mut self) {
+        if unsafe { RevertToSelf() } == 0 {
+            process::abort();
+        }
+    }
+}
+
+/// Opens the token of the client connected to the specified server-side pipe
+/// handle for querying, by briefly impersonating it on the current thread.
+///
+/// # Safety
+/// The handle must be a valid server-side named pipe handle.
+unsafe fn open_client_token(handle: HANDLE) -> io::Result<FileHandleOps> {
+    let mut token: HANDLE = ptr::null_mut();
+    let (opened, open_error) = unsafe {
+        impersonate_client_scoped(handle, || {
+            // Opened as self because the client's own identity may not be allowed to
+            // look at the token, e.g. with the identification level.
+            let opened =
+                OpenThreadToken(GetCurrentThread(), TOKEN_QUERY, 1, &mut token as *mut _) != 0;
+            (opened, io::Error::last_os_error())
+        })
+    }?;
+    if !opened {
+        return Err(open_error);
+    }
+    Ok(unsafe {
+        // SAFETY: we just opened the handle and own it
+        FileHandleOps::from_raw_handle(token)
+    })
+}
+
+/// Retrieves the identity of the client connected to the specified server-side
+/// pipe handle.
+///
+/// # Safety
+/// The handle must be a valid server-side named pipe handle.
+pub(super) unsafe fn get_client_credentials(handle: HANDLE) -> io::Result<PeerCredentials> {
+    let mut pid: u32 = 0;
+    if unsafe { GetNamedPipeClientProcessId(handle, &mut pid as *mut _) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let token = unsafe { open_client_token(handle) }?;
+    Ok(PeerCredentials {
+        pid,
+        sid: unsafe {
+            // SAFETY: the token has been opened for querying
+            token_user_sid(token.0)
+        }?,
+    })
+}
+
+/// Determines the impersonation level which the client connected to the
+/// specified server-side pipe handle has granted, by briefly impersonating it
+/// on the current thread.
+///
+/// # Safety
+/// The handle must be a valid server-side named pipe handle.
+pub(super) unsafe fn get_client_impersonation_level(
+    handle: HANDLE,
+) -> io::Result<ImpersonationLevel> {
+    let token = match unsafe { open_client_token(handle) } {
+        Ok(token) => token,
+        // Anonymous tokens cannot be opened at all.
+        Err(e) if e.raw_os_error() == Some(ERROR_CANT_OPEN_ANONYMOUS as i32) => {
+            return Ok(ImpersonationLevel::Anonymous)
+        },
+        Err(e) => return Err(e),
+    };
+
+    let mut level: SECURITY_IMPERSONATION_LEVEL = SecurityAnonymous;
+    let mut len: DWORD = 0;
+    let success = unsafe {
+        GetTokenInformation(
+            token.0,
+            TokenImpersonationLevel,
+            &mut level as *mut _ as *mut _,
+            size_of_val(&level) as DWORD,
+            &mut len as *mut _,
+        )
+    } != 0;
+    if !success {
+        return Err(io::Error::last_os_error());
+    }
+    // The winapi constants keep the names from the Windows headers.
+    #[allow(non_upper_case_globals)]
+    match level {
+        SecurityAnonymous => Ok(ImpersonationLevel::Anonymous),
+        SecurityIdentification => Ok(ImpersonationLevel::Identification),
+        SecurityImpersonation => Ok(ImpersonationLevel::Impersonation),
+        SecurityDelegation => Ok(ImpersonationLevel::Delegation),
+        _ => Err(io::Error::new(
+            io::ErrorKind::Other,
+            "the system reported an unknown impersonation level",
+        )),
+    }
+}
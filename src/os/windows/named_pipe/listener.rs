@@ -1,18 +1,24 @@
 use super::{
-    super::{imports::*, FromRawHandle},
+    super::{imports::*, FileHandleOps, FromRawHandle},
+    impersonation::get_client_impersonation_level,
+    pending_connect::PendingConnect,
+    ImpersonationLevel,
     Instancer,
     PipeMode,
     PipeOps,
     PipeStream,
     PipeStreamRole,
 };
+use crate::OverflowPolicy;
 use std::{
     borrow::Cow,
     convert::TryInto,
-    ffi::OsStr,
-    fmt::{self, Debug, Formatter},
+    error::Error,
+    ffi::{OsStr, OsString},
+    fmt::{self, Debug, Display, Formatter},
     io,
     marker::PhantomData,
+    mem::zeroed,
     num::{NonZeroU32, NonZeroU8},
     ptr,
     sync::{
@@ -20,20 +26,59 @@ use std::{
         Arc,
         RwLock,
     },
+    thread,
+    time::Duration,
 };
 use to_method::To;
 
+/// How often `accept` checks whether an instance has been freed up when waiting
+/// at the instance limit with the `Queue` overflow policy.
+pub(super) const OVERFLOW_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Returns whether the error is the one emitted when trying to create a pipe
+/// instance beyond the instance limit.
+pub(super) fn is_pipe_busy(error: &io::Error) -> bool {
+    error.raw_os_error() == Some(ERROR_PIPE_BUSY as i32)
+}
+
+/// Returns whether the client connected to the specified server-side handle
+/// has granted at least the specified impersonation level, if any. Clients
+/// whose level cannot be determined, such as those which haven't sent anything
+/// yet, are treated as not meeting the minimum.
+pub(super) fn meets_impersonation_level(handle: HANDLE, min: Option<ImpersonationLevel>) -> bool {
+    match min {
+        Some(min) => matches!(
+            unsafe {
+                // SAFETY: the caller passes the handle of an instance it owns
+                get_client_impersonation_level(handle)
+            },
+            Ok(level) if level >= min
+        ),
+        None => true,
+    }
+}
+
 /// The server for a named pipe, listening for connections to clients and
 /// producing pipe streams.
 ///
 /// The only way to create a `PipeListener` is to use [`PipeListenerOptions`].
 /// See its documentation for more.
+///
+/// The name of the pipe is converted to the wide string which the system
+/// expects once, when the listener is created, rather than whenever a new
+/// instance is created, so accepting connections doesn't allocate for it. The
+/// converted name is never modified afterwards, which means that accepting
+/// from several threads at once needs no locking around it.
 pub struct PipeListener<Stream: PipeStream> {
-    config:      PipeListenerOptions<'static>, // We need the options to create new instances
+    config:          PipeListenerOptions<'static>, // We need the options to create new instances
+    // Encoded once, so that creating instances doesn't allocate; never written to
+    // afterwards, so concurrent accepts can share it without locking
+    path:            Vec<u16>,
     // Store the nonblocking boolean separately to change it without mutable access
-    nonblocking: AtomicBool,
-    instancer:   Instancer<PipeOps>,
-    _phantom:    PhantomData<fn() -> Stream>,
+    nonblocking:     AtomicBool,
+    instancer:       Instancer<PipeOps>,
+    pending_connect: PendingConnect,
+    _phantom:        PhantomData<fn() -> Stream>,
 }
 /// An iterator that infinitely [`accept`]s connections on a [`PipeListener`].
 ///
@@ -63,14 +108,205 @@ impl<Stream: PipeStream> PipeListener<Stream> {
     /// communicate with the pipe.
     ///
     /// See `incoming` for an iterator version of this.
+    ///
+    /// If all instances allowed by the [`instance_limit`] are occupied by
+    /// streams, the [`overflow_policy`] decides what happens: with `Queue`, the
+    /// call waits until one of those streams is dropped (or immediately fails
+    /// with [`WouldBlock`] in nonblocking mode), and with `Reject`, it fails
+    /// with the `ERROR_PIPE_BUSY` error right away.
+    ///
+    /// If a [`min_impersonation_level`] is set, the call waits for the client
+    /// to send something before checking it, since the system doesn't let a
+    /// client be impersonated before that. Clients which do not grant it are
+    /// disconnected without ever being returned, and the call carries on
+    /// waiting for the next client.
+    ///
+    /// If an instance has been armed with [`pending_connect_event`], the
+    /// client connecting to it is the one which is returned, and the call
+    /// waits for it if it hasn't connected yet.
+    ///
+    /// [`instance_limit`]: struct.PipeListenerOptions.html#structfield.instance_limit " "
+    /// [`overflow_policy`]: struct.PipeListenerOptions.html#structfield.overflow_policy " "
+    /// [`min_impersonation_level`]: struct.PipeListenerOptions.html#structfield.min_impersonation_level " "
+    /// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock " "
+    /// [`pending_connect_event`]: #method.pending_connect_event " "
     pub fn accept(&self) -> io::Result<Stream> {
-        let instance = if let Some(instance) = self.instancer.allocate() {
-            instance
-        } else {
-            self.instancer.add_instance(self.create_instance()?)
+        loop {
+            let (instance, armed) = match self.pending_connect.take() {
+                Some(instance) => (instance?, true),
+                None => {
+                    let instance = self.allocate_instance()?;
+                    instance.0.connect_server()?;
+                    (instance, false)
+                },
+            };
+            let min_level = self.config.min_impersonation_level;
+            if min_level.is_some() && Stream::READ_MODE.is_some() {
+                // The system only lets the client be impersonated, which is how
+                // its level is found out, once the client has sent something.
+                // Should this fail, so does the check right after it.
+                let _ = instance.0.wait_for_data();
+            }
+            let handle = instance.0.as_raw_handle();
+            let stream = Stream::build(instance);
+            if meets_impersonation_level(handle, min_level) {
+                return Ok(stream);
+            }
+            // Dropping the stream disconnects the client and frees up the
+            // instance.
+            drop(stream);
+            if armed {
+                // Waiting for the next client here would block a caller which
+                // only calls this once the event is signalled.
+                self.pending_connect_event()?;
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "the client was rejected, and the event has been rearmed",
+                ));
+            }
+        }
+    }
+    /// Like [`accept`], but only returns clients for which `verify` returns
+    /// `Ok(true)`. Clients it returns `Ok(false)` for are disconnected, and the
+    /// call carries on waiting for the next client; errors returned by it are
+    /// returned as-is, also disconnecting the client.
+    ///
+    /// This is the server-side counterpart of
+    /// [`LocalSocketStream::connect_authenticated`], for checking who the
+    /// client is, through its process identifier or by
+    /// [impersonating][imp] it, before anything is sent to it. The
+    /// [`min_impersonation_level`] is checked first, so `verify` is only
+    /// called for clients which meet it.
+    ///
+    /// Since it goes on to wait for another client after rejecting one, this
+    /// doesn't fit the protocol of [`pending_connect_event`]; with that, use
+    /// `accept` and check the client after it returns instead.
+    ///
+    /// [`accept`]: #method.accept " "
+    /// [`pending_connect_event`]: #method.pending_connect_event " "
+    /// [`LocalSocketStream::connect_authenticated`]: ../../../local_socket/struct.LocalSocketStream.html#method.connect_authenticated " "
+    /// [imp]: struct.DuplexBytePipeStream.html#method.impersonate_client_scoped " "
+    /// [`min_impersonation_level`]: struct.PipeListenerOptions.html#structfield.min_impersonation_level " "
+    pub fn accept_authenticated(
+        &self,
+        mut verify: impl FnMut(&Stream) -> io::Result<bool>,
+    ) -> io::Result<Stream> {
+        loop {
+            let stream = self.accept()?;
+            if verify(&stream)? {
+                return Ok(stream);
+            }
+        }
+    }
+    /// Returns a manual-reset event which is signalled once a client connects
+    /// to the pipe, for use with `WaitForMultipleObjects` and the like, to wait
+    /// for clients on several listeners and other objects at once.
+    ///
+    /// The protocol is as follows:
+    /// 1. This method arms an instance of the pipe to wait for a client, unless
+    ///    one is armed already, and returns the event. The event is owned by
+    ///    the listener and stays the same for its entire lifetime, so the
+    ///    handle can be kept instead of calling this again, but must not be
+    ///    closed;
+    /// 2. Once the event is signalled, [`accept`] returns the client which
+    ///    connected to the armed instance without blocking, and resets the
+    ///    event. If connecting failed, the error is returned by `accept`
+    ///    instead;
+    /// 3. The event is not signalled again until this method is called again to
+    ///    arm another instance, and arming it again is only possible after
+    ///    `accept` has picked up the previous client. **Waiting on the event
+    ///    without rearming it after every `accept` misses all further
+    ///    connections.**
+    ///
+    /// With a [`min_impersonation_level`], `accept` still has to wait for the
+    /// client to send something. If the client is then disconnected because
+    /// it doesn't grant that level, `accept` rearms the instance by itself and
+    /// fails with [`WouldBlock`] instead of waiting for another client, so
+    /// that the event can be waited on again.
+    ///
+    /// Calling `accept` while the instance is armed but the event is not yet
+    /// signalled blocks until a client connects to that instance, just like
+    /// `accept` normally does.
+    ///
+    /// Since the instances of the listener perform blocking I/O, the system
+    /// cannot signal the event by itself; a thread is started for every armed
+    /// instance to wait for a client to connect to it. Dropping the listener
+    /// interrupts that thread and waits for it to exit. The listener must not
+    /// be in nonblocking mode, since a nonblocking instance doesn't wait for
+    /// clients.
+    ///
+    /// # System calls
+    /// - `CreateEventW`, the first time
+    /// - `CreateNamedPipeW`, if all instances are busy
+    /// - `ConnectNamedPipe`, on a separate thread
+    /// - `SetEvent`, on that thread
+    ///
+    /// [`accept`]: #method.accept " "
+    /// [`min_impersonation_level`]: struct.PipeListenerOptions.html#structfield.min_impersonation_level " "
+    /// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock " "
+    pub fn pending_connect_event(&self) -> io::Result<HANDLE> {
+        if self.nonblocking.load(SeqCst) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "connection events are not available in nonblocking mode",
+            ));
+        }
+        self.pending_connect.arm(|| self.allocate_instance())
+    }
+    /// Connects a throwaway client, which disconnects right away, to every
+    /// instance which is waiting for a client, making the calls to `accept`
+    /// waiting on them return. This is how closing a local socket listener
+    /// interrupts them.
+    pub(crate) fn wake_accepts(&self) {
+        let access = match Stream::ROLE {
+            PipeStreamRole::Reader => GENERIC_WRITE,
+            PipeStreamRole::Writer => GENERIC_READ,
+            PipeStreamRole::ReaderAndWriter => GENERIC_READ | GENERIC_WRITE,
+        };
+        // Opening fails once no instance is waiting anymore, since the woken
+        // accepts don't wait again.
+        loop {
+            let handle = unsafe {
+                CreateFileW(
+                    self.path.as_ptr(),
+                    access,
+                    FILE_SHARE_READ | FILE_SHARE_WRITE,
+                    ptr::null_mut(),
+                    OPEN_EXISTING,
+                    0,
+                    ptr::null_mut(),
+                )
+            };
+            if handle == INVALID_HANDLE_VALUE {
+                break;
+            }
+            // SAFETY: we just opened the handle, and closing it is all that's
+            // done with it
+            drop(unsafe { FileHandleOps::from_raw_handle(handle) });
+        }
+    }
+    fn allocate_instance(&self) -> io::Result<Arc<(PipeOps, AtomicBool)>> {
+        let instance = loop {
+            if let Some(instance) = self.instancer.allocate() {
+                break instance;
+            }
+            match self.create_instance() {
+                Ok(instance) => break self.instancer.add_instance(instance),
+                Err(e)
+                    if is_pipe_busy(&e) && self.config.overflow_policy == OverflowPolicy::Queue =>
+                {
+                    if self.nonblocking.load(SeqCst) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::WouldBlock,
+                            "all instances of the pipe are in use",
+                        ));
+                    }
+                    thread::sleep(OVERFLOW_POLL_INTERVAL);
+                },
+                Err(e) => return Err(e),
+            }
         };
-        instance.0.connect_server()?;
-        Ok(Stream::build(instance))
+        Ok(instance)
     }
     /// Creates an iterator which accepts connections from clients, blocking
     /// each time `next()` is called until one connects.
@@ -103,8 +339,40 @@ impl<Stream: PipeStream> PipeListener<Stream> {
         Ok(())
     }
 
+    /// Converts the listener into the handle of one of its idle instances,
+    /// marked as inheritable so that a child process can take over accepting
+    /// connections with [`PipeListenerOptions::create_from_inherited`].
+    ///
+    /// Hitless reloads work by spawning the new version of the server with
+    /// handle inheritance enabled, which `std::process::Command` does by
+    /// default, telling it the value of the handle (for example via an
+    /// environment variable) and exiting once it has taken over. Since the
+    /// inherited instance keeps the pipe alive, clients can keep connecting
+    /// in the meantime — they are let in once the new process calls `accept`.
+    ///
+    /// All other idle instances are closed. Streams which the listener has
+    /// already produced are unaffected.
+    ///
+    /// # System calls
+    /// - `CreateNamedPipeW`, if all instances are busy
+    /// - `DuplicateHandle`
+    ///
+    /// [`PipeListenerOptions::create_from_inherited`]: struct.PipeListenerOptions.html#method.create_from_inherited " "
+    pub fn into_inheritable_handle(self) -> io::Result<HANDLE> {
+        let instance = match self.instancer.allocate() {
+            Some(instance) => instance,
+            None => self.instancer.add_instance(self.create_instance()?),
+        };
+        // Duplicated rather than taken out of the instance, since something else may
+        // still hold a reference to it: the instance stays alive through the copy
+        // once everything else lets go of it.
+        let handle = (instance.0).0.duplicate(true)?;
+        Ok(handle.into_raw_handle())
+    }
+
     fn create_instance(&self) -> io::Result<PipeOps> {
         let handle = self.config.create_instance(
+            &self.path,
             false,
             self.nonblocking.load(SeqCst),
             false,
@@ -199,6 +467,99 @@ pub struct PipeListenerOptions<'a> {
     /// The default timeout when waiting for a client to connect. Used unless
     /// another timeout is specified when waiting for a client.
     pub wait_timeout:            NonZeroU32,
+    /// Specifies whether instances of the pipe are created on demand or ahead
+    /// of time when the listener is created. By default, they are created
+    /// lazily. See the documentation of [`InstanceStrategy`] for the tradeoffs
+    /// involved.
+    pub instance_strategy:       InstanceStrategy,
+    /// Specifies what happens to clients which arrive while all instances
+    /// allowed by the [`instance_limit`] are occupied. By default, they are
+    /// queued, meaning that `accept` waits for an instance to free up. Has no
+    /// effect if there is no instance limit. See the documentation of
+    /// [`OverflowPolicy`] for the exact behavior.
+    ///
+    /// [`instance_limit`]: #structfield.instance_limit " "
+    /// [`OverflowPolicy`]: ../../../enum.OverflowPolicy.html " "
+    pub overflow_policy:         OverflowPolicy,
+    /// Specifies the lowest impersonation level which clients have to grant to
+    /// be accepted, or `None` to accept clients regardless of it, which is the
+    /// default.
+    ///
+    /// Clients choose the impersonation level when connecting, and a client
+    /// which only grants the `Anonymous` level does not allow the server to
+    /// find out who it is. Requiring at least `Identification` thus makes sure
+    /// that the identity of every accepted client can be checked. The level is
+    /// determined by briefly impersonating the client, which the system only
+    /// allows once the client has sent something, so `accept` waits for that
+    /// first; clients which fall short, as well as those whose level cannot be
+    /// determined, are disconnected without being returned from `accept`.
+    /// Since clients can't send anything through streams which only let the
+    /// server write, no client is accepted by such a listener with this set.
+    ///
+    /// This check is specific to Windows, since there is no impersonation on
+    /// other platforms. It happens before the verification closure passed to
+    /// [`accept_authenticated`] is called, so that closure only ever sees
+    /// clients which meet the minimum and can safely impersonate or identify
+    /// them.
+    ///
+    /// [`accept_authenticated`]: struct.PipeListener.html#method.accept_authenticated " "
+    pub min_impersonation_level: Option<ImpersonationLevel>,
+}
+/// Specifies when a [`PipeListener`] creates the instances of its named pipe.
+///
+/// Every connected client occupies one instance of the pipe, and an instance
+/// which is not occupied by any client is what allows a client to connect in
+/// the first place. If every instance is busy, clients which try to connect
+/// fail with the `ERROR_PIPE_BUSY` error until an instance becomes available.
+///
+/// The two strategies trade memory and kernel resources for connection
+/// latency:
+/// - With [`Lazy`], the listener starts out with a single instance and only
+///   creates a new one when [`accept`] is called and all of the existing ones
+///   are busy. This keeps the amount of handles and kernel buffers to a minimum
+///   for servers which rarely serve more than one client at a time, but clients
+///   which arrive in a burst may see `ERROR_PIPE_BUSY` while the server is not
+///   inside `accept`, since there is no pipe instance that they could connect
+///   to.
+/// - With [`Eager`], the listener creates the specified amount of instances
+///   right away, all of which immediately become available for clients to
+///   connect to, even before the first call to `accept`. A burst of up to that
+///   many clients can thus connect without any of them being turned away, at
+///   the cost of holding on to all of the instances (and their input and output
+///   buffers) for the lifetime of the listener, regardless of whether clients
+///   ever make use of them. Once all of the pre-created instances are busy,
+///   further instances are created on demand, just like with `Lazy`.
+///
+/// The amount of pre-created instances is clamped by the [`instance_limit`], if
+/// one is set.
+///
+/// [`Lazy`]: #variant.Lazy " "
+/// [`Eager`]: #variant.Eager " "
+/// [`accept`]: struct.PipeListener.html#method.accept " "
+/// [`instance_limit`]: struct.PipeListenerOptions.html#structfield.instance_limit " "
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InstanceStrategy {
+    /// Create a single instance upfront and the rest on demand.
+    Lazy,
+    /// Create the specified amount of instances upfront, and the rest on
+    /// demand.
+    Eager(NonZeroU8),
+}
+impl InstanceStrategy {
+    /// Returns the amount of instances which the listener should create when
+    /// it's constructed, taking the specified instance limit into account.
+    fn upfront_instances(self, instance_limit: Option<NonZeroU8>) -> u8 {
+        let requested = match self {
+            Self::Lazy => 1,
+            Self::Eager(amount) => amount.get(),
+        };
+        instance_limit.map_or(requested, |limit| requested.min(limit.get()))
+    }
+}
+impl Default for InstanceStrategy {
+    fn default() -> Self {
+        Self::Lazy
+    }
 }
 macro_rules! genset {
     ($name:ident : $ty:ty) => {
@@ -226,6 +587,9 @@ impl<'a> PipeListenerOptions<'a> {
             input_buffer_size_hint:  512,
             output_buffer_size_hint: 512,
             wait_timeout:            NonZeroU32::new(50).unwrap(),
+            instance_strategy:       InstanceStrategy::Lazy,
+            overflow_policy:         OverflowPolicy::Queue,
+            min_impersonation_level: None,
         }
     }
     /// Clones configuration options which are not owned by value and returns a
@@ -251,6 +615,9 @@ impl<'a> PipeListenerOptions<'a> {
             input_buffer_size_hint:  self.input_buffer_size_hint,
             output_buffer_size_hint: self.output_buffer_size_hint,
             wait_timeout:            self.wait_timeout,
+            instance_strategy:       self.instance_strategy,
+            overflow_policy:         self.overflow_policy,
+            min_impersonation_level: self.min_impersonation_level,
         }
     }
     genset!(
@@ -263,18 +630,24 @@ impl<'a> PipeListenerOptions<'a> {
         input_buffer_size_hint: usize,
         output_buffer_size_hint: usize,
         wait_timeout: NonZeroU32,
+        instance_strategy: InstanceStrategy,
+        overflow_policy: OverflowPolicy,
+        min_impersonation_level: Option<ImpersonationLevel>,
     );
     /// Creates an instance of a pipe for a listener with the specified stream
     /// type and with the first-instance flag set to the specified value.
+    ///
+    /// `path` is the name as encoded by `path`, which listeners keep around so
+    /// that creating instances doesn't allocate.
     pub(super) fn create_instance(
         &self,
+        path: &[u16],
         first: bool,
         nonblocking: bool,
         overlapped: bool,
         role: PipeStreamRole,
         read_mode: Option<PipeMode>,
     ) -> io::Result<HANDLE> {
-        let path = super::convert_path(&self.name, None);
         let open_mode = self.to_open_mode(first, role, overlapped);
         let pipe_mode = self.to_pipe_mode(read_mode, nonblocking);
         let (handle, success) = unsafe {
@@ -316,11 +689,57 @@ impl<'a> PipeListenerOptions<'a> {
     /// `Stream`'s `WRITE_MODE`. Otherwise, the call will panic in debug builds
     /// or, in release builds, the `WRITE_MODE` will take priority.
     pub fn create<Stream: PipeStream>(&self) -> io::Result<PipeListener<Stream>> {
-        let (owned_config, instancer) = self._create(Stream::ROLE, Stream::READ_MODE)?;
+        let (owned_config, path, instancer) = self._create(Stream::ROLE, Stream::READ_MODE)?;
         Ok(PipeListener {
             config: owned_config,
+            path,
             nonblocking: AtomicBool::new(self.nonblocking),
             instancer,
+            pending_connect: PendingConnect::default(),
+            _phantom: PhantomData,
+        })
+    }
+    /// Creates a pipe listener which accepts connections on the specified
+    /// instance of an existing pipe, inherited from the process which has
+    /// created it using [`PipeListener::into_inheritable_handle`]. Further
+    /// instances are created according to the options, so they, and the name
+    /// in particular, should be the same as the ones which the original
+    /// listener was created with.
+    ///
+    /// The handle is made non-inheritable again, so that it does not leak into
+    /// processes spawned later on.
+    ///
+    /// # Safety
+    /// The handle must be a valid server-side named pipe handle which is not
+    /// owned by anything else. The `Stream` type must match the direction and
+    /// read mode of the pipe.
+    ///
+    /// # System calls
+    /// - `SetHandleInformation`
+    ///
+    /// [`PipeListener::into_inheritable_handle`]: struct.PipeListener.html#method.into_inheritable_handle " "
+    pub unsafe fn create_from_inherited<Stream: PipeStream>(
+        &self,
+        handle: HANDLE,
+    ) -> io::Result<PipeListener<Stream>> {
+        let instance = unsafe {
+            // SAFETY: as per safety contract
+            PipeOps::from_raw_handle(handle)
+        };
+        let success = unsafe { SetHandleInformation(handle, HANDLE_FLAG_INHERIT, 0) } != 0;
+        if !success {
+            return Err(io::Error::last_os_error());
+        }
+        let instancer = Instancer(RwLock::new(vec![Arc::new((
+            instance,
+            AtomicBool::new(false),
+        ))]));
+        Ok(PipeListener {
+            config: self.to_owned(),
+            path: self.path(),
+            nonblocking: AtomicBool::new(self.nonblocking),
+            instancer,
+            pending_connect: PendingConnect::default(),
             _phantom: PhantomData,
         })
     }
@@ -328,21 +747,79 @@ impl<'a> PipeListenerOptions<'a> {
         &self,
         role: PipeStreamRole,
         read_mode: Option<PipeMode>,
-    ) -> io::Result<(PipeListenerOptions<'static>, Instancer<PipeOps>)> {
+    ) -> io::Result<(PipeListenerOptions<'static>, Vec<u16>, Instancer<PipeOps>)> {
         let owned_config = self.to_owned();
+        let path = self.path();
         let instancer_capacity =
             self.instance_limit.map_or(INITIAL_INSTANCER_CAPACITY, NonZeroU8::get).to::<usize>();
         let mut instance_vec = Vec::with_capacity(instancer_capacity);
-        let first_instance_raw =
-            self.create_instance(true, self.nonblocking, false, role, read_mode)?;
+        let first_instance_raw = self
+            .create_instance(&path, true, self.nonblocking, false, role, read_mode)
+            .map_err(|error| self.diagnose_first_instance_error(error, &path))?;
         let first_instance = Arc::new((
             // SAFETY: we just created this handle
             unsafe { PipeOps::from_raw_handle(first_instance_raw) },
             AtomicBool::new(false),
         ));
         instance_vec.push(first_instance);
+        for _ in 1..self.upfront_instances() {
+            let instance_raw =
+                self.create_instance(&path, false, self.nonblocking, false, role, read_mode)?;
+            instance_vec.push(Arc::new((
+                // SAFETY: as above
+                unsafe { PipeOps::from_raw_handle(instance_raw) },
+                AtomicBool::new(false),
+            )));
+        }
         let instancer = Instancer(RwLock::new(instance_vec));
-        Ok((owned_config, instancer))
+        Ok((owned_config, path, instancer))
+    }
+    /// Encodes the name of the pipe into the null-terminated wide string which
+    /// `CreateNamedPipeW` expects.
+    pub(super) fn path(&self) -> Vec<u16> {
+        super::convert_path(&self.name, None)
+    }
+    /// Replaces the error which the system reports when the name of the pipe is
+    /// already taken with a [`PipeNameTakenError`].
+    ///
+    /// The first instance is created with `FILE_FLAG_FIRST_PIPE_INSTANCE`,
+    /// which makes the system fail with `ERROR_ACCESS_DENIED` if a pipe
+    /// with the same name already exists, including one of a different type
+    /// or direction which could not be joined anyway. That's
+    /// indistinguishable from an actual permission problem unless the pipe
+    /// directory is checked for the name, which doesn't connect to the
+    /// existing pipe, unlike opening it. `path` is the encoded name which the
+    /// instance was created with.
+    fn diagnose_first_instance_error(&self, error: io::Error, path: &[u16]) -> io::Error {
+        if error.raw_os_error() != Some(ERROR_ACCESS_DENIED as i32) {
+            return error;
+        }
+        let exists = unsafe {
+            let mut find_data: WIN32_FIND_DATAW = zeroed();
+            let handle = FindFirstFileW(path.as_ptr(), &mut find_data as *mut _);
+            if handle == INVALID_HANDLE_VALUE {
+                false
+            } else {
+                FindClose(handle);
+                true
+            }
+        };
+        if exists {
+            io::Error::new(
+                io::ErrorKind::AddrInUse,
+                PipeNameTakenError {
+                    name:   self.name.clone().into_owned(),
+                    source: error,
+                },
+            )
+        } else {
+            error
+        }
+    }
+    /// Returns the amount of instances which should be created together with
+    /// the listener, according to the instance strategy.
+    pub(super) fn upfront_instances(&self) -> u8 {
+        self.instance_strategy.upfront_instances(self.instance_limit)
     }
 
     fn to_open_mode(&self, first: bool, role: PipeStreamRole, overlapped: bool) -> DWORD {
@@ -378,3 +855,44 @@ impl Default for PipeListenerOptions<'_> {
         Self::new()
     }
 }
+
+/// Error indicating that a pipe listener could not be created because a pipe
+/// with the same name already exists.
+///
+/// The existing pipe may have been created by another process, or by another
+/// listener in the same process, and may even be of a different type or
+/// direction — e.g. a byte pipe when a message pipe is being created — which
+/// the system reports as a generic "access denied" error. This error is
+/// emitted instead in that case, with the `ErrorKind::AddrInUse` error type.
+/// The error reported by the system is available via
+/// [`os_error`](#method.os_error).
+#[derive(Debug)]
+pub struct PipeNameTakenError {
+    name:   OsString,
+    source: io::Error,
+}
+impl PipeNameTakenError {
+    /// Returns the name of the pipe which was to be created.
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+    /// Returns the error reported by the system.
+    pub fn os_error(&self) -> &io::Error {
+        &self.source
+    }
+}
+impl Display for PipeNameTakenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the pipe name {:?} is already taken by an existing pipe, which may be of an \
+             incompatible type",
+            self.name,
+        )
+    }
+}
+impl Error for PipeNameTakenError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
@@ -0,0 +1,92 @@
+use super::{super::imports::*, PipeOps, ReadMsgOutcome};
+use std::{
+    fmt::{self, Debug, Formatter},
+    io::{self, Read},
+};
+
+/// How big the buffer used for discarding the rest of a message is.
+const DISCARD_CHUNK_SIZE: usize = 512;
+
+/// Exposes one message at a time from a message-mode pipe stream as a byte
+/// reader, without ever buffering the whole message.
+///
+/// Reading from a `MessageReader` reads the current message chunk by chunk
+/// with the [`Read`] trait, with every `read` call performing one `ReadFile`
+/// directly into the provided buffer. Once the end of the message has been
+/// read, which the system reports by no longer failing with `ERROR_MORE_DATA`,
+/// all further reads report end of file, so the message can be handed to a
+/// parser which reads until end of file, and memory usage stays the same
+/// regardless of how big messages are. [`next_message`] then advances to the
+/// next message.
+///
+/// The message is read directly from the pipe, meaning that **seeking
+/// backwards is not possible** — whatever has been read has to be kept around
+/// by the caller if it's needed again.
+///
+/// Created by the `message_reader` method on message-mode pipe streams, which
+/// borrows the stream for as long as the reader exists.
+///
+/// [`Read`]: https://doc.rust-lang.org/std/io/trait.Read.html " "
+/// [`next_message`]: #method.next_message " "
+pub struct MessageReader<'a> {
+    pipe:        &'a PipeOps,
+    end_reached: bool,
+}
+impl<'a> MessageReader<'a> {
+    pub(super) fn new(pipe: &'a PipeOps) -> Self {
+        Self {
+            pipe,
+            end_reached: false,
+        }
+    }
+    /// Advances to the next message, discarding the rest of the current one
+    /// if it hasn't been read to the end yet. Does not wait for the next
+    /// message to arrive — that happens on the next read.
+    ///
+    /// # System calls
+    /// - `ReadFile`, if part of the current message is left
+    pub fn next_message(&mut self) -> io::Result<()> {
+        let mut discard = [0; DISCARD_CHUNK_SIZE];
+        while !self.end_reached {
+            self.read_chunk(&mut discard)?;
+        }
+        self.end_reached = false;
+        Ok(())
+    }
+    /// Returns `true` if the current message has been read to the end, i.e.
+    /// if reading would report end of file until [`next_message`] is called.
+    ///
+    /// [`next_message`]: #method.next_message " "
+    pub fn is_message_finished(&self) -> bool {
+        self.end_reached
+    }
+    fn read_chunk(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // The rest of a bigger buffer is filled out by the next call.
+        let len = buf.len().min(DWORD::MAX as usize);
+        match self.pipe.read_msg_part(&mut buf[..len])? {
+            ReadMsgOutcome::Complete(bytes_read) => {
+                self.end_reached = true;
+                Ok(bytes_read)
+            },
+            ReadMsgOutcome::Partial(bytes_read) => Ok(bytes_read),
+        }
+    }
+}
+impl Debug for MessageReader<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MessageReader")
+            .field("handle", &self.pipe.as_raw_handle())
+            .field("end_reached", &self.end_reached)
+            .finish()
+    }
+}
+impl Read for MessageReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // An empty read would make the system report ERROR_MORE_DATA for every
+        // nonempty message without consuming anything, which is of no use.
+        if self.end_reached || buf.is_empty() {
+            return Ok(0);
+        }
+        self.read_chunk(buf)
+    }
+}
@@ -36,17 +36,27 @@
 // TODO improve docs, add examples
 
 mod enums;
+mod handle_state;
+mod impersonation;
 mod instancer;
 mod listener;
+mod message_reader;
+mod pending_connect;
 mod pipeops;
 #[macro_use]
 mod stream;
 pub use enums::*;
+pub use handle_state::*;
+pub use impersonation::*;
 pub use listener::*;
+pub use message_reader::*;
 pub use stream::*;
 
 pub mod tokio;
 
+#[cfg(test)]
+mod tests;
+
 use super::imports::*;
 use instancer::*;
 use pipeops::*;
@@ -75,10 +85,19 @@ unsafe fn set_nonblocking_for_stream<Stream: PipeStream>(
     handle: HANDLE,
     nonblocking: bool,
 ) -> io::Result<()> {
-    let read_mode: u32 = Stream::READ_MODE.map_or(0, PipeMode::to_readmode);
+    // Pipes which are only written to are left in byte read mode.
+    let read_mode = Stream::READ_MODE.unwrap_or(PipeMode::Bytes);
+    unsafe { set_handle_mode(handle, read_mode, nonblocking) }
+}
+#[cfg(windows)]
+unsafe fn set_handle_mode(
+    handle: HANDLE,
+    read_mode: PipeMode,
+    nonblocking: bool,
+) -> io::Result<()> {
     // Bitcast the boolean without additional transformations since
     // the flag is in the first bit.
-    let mut mode: u32 = read_mode | nonblocking as u32;
+    let mut mode: u32 = read_mode.to_readmode() | nonblocking as u32;
     let success = unsafe {
         SetNamedPipeHandleState(
             handle,
@@ -0,0 +1,146 @@
+use super::{super::imports::*, PipeOps};
+use std::{
+    io,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Arc,
+        Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+static LPE: &str = "unexpected lock poisoning";
+
+/// How long dropping the listener waits between attempts to interrupt the
+/// thread waiting for a client, in case it hasn't entered the system call yet.
+const CANCEL_RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+type Instance = Arc<(PipeOps, AtomicBool)>;
+
+/// The machinery behind `PipeListener::pending_connect_event`.
+///
+/// The instances of a synchronous listener are not overlapped, and cannot be,
+/// since the streams made out of them perform blocking I/O with them; the
+/// system has no way of signalling an event when a client connects to such an
+/// instance. Instead, a thread is spawned which waits for a client on the
+/// armed instance with a blocking `ConnectNamedPipe` and signals the event once
+/// it returns, after which `accept` picks up the connected instance.
+#[derive(Default)]
+pub(super) struct PendingConnect(Mutex<State>);
+#[derive(Default)]
+struct State {
+    /// Created on first use and kept for the lifetime of the listener, so that
+    /// callers can hold on to the handle.
+    event: Option<Event>,
+    armed: Option<Armed>,
+}
+struct Armed {
+    instance: Instance,
+    thread:   JoinHandle<io::Result<()>>,
+    done:     Arc<AtomicBool>,
+}
+
+impl PendingConnect {
+    /// Returns the event, arming an instance obtained from `allocate` if none
+    /// is armed yet.
+    pub fn arm(&self, allocate: impl FnOnce() -> io::Result<Instance>) -> io::Result<HANDLE> {
+        let mut state = self.0.lock().expect(LPE);
+        if state.event.is_none() {
+            state.event = Some(Event::new()?);
+        }
+        let event = state.event.as_ref().map_or(ptr::null_mut(), |event| event.0);
+        if state.armed.is_some() {
+            return Ok(event);
+        }
+        let instance = allocate()?;
+        let done = Arc::new(AtomicBool::new(false));
+        let spawned = {
+            let instance = Arc::clone(&instance);
+            let done = Arc::clone(&done);
+            // Handles aren't Send, but the event outlives the thread, since dropping
+            // the listener waits for it.
+            let event = event as usize;
+            thread::Builder::new().spawn(move || {
+                let result = instance.0.connect_server();
+                done.store(true, SeqCst);
+                unsafe { SetEvent(event as HANDLE) };
+                result
+            })
+        };
+        match spawned {
+            Ok(thread) => {
+                state.armed = Some(Armed {
+                    instance,
+                    thread,
+                    done,
+                });
+                Ok(event)
+            },
+            Err(e) => {
+                // Nobody is going to use the instance.
+                instance.1.store(false, SeqCst);
+                Err(e)
+            },
+        }
+    }
+    /// Takes the armed instance, if any, waiting for the client to connect to
+    /// it if that hasn't happened yet, and resets the event.
+    pub fn take(&self) -> Option<io::Result<Instance>> {
+        let mut state = self.0.lock().expect(LPE);
+        let Armed {
+            instance, thread, ..
+        } = state.armed.take()?;
+        let result = thread.join().unwrap_or_else(|_| {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the thread waiting for a client panicked",
+            ))
+        });
+        if let Some(event) = &state.event {
+            unsafe { ResetEvent(event.0) };
+        }
+        Some(result.map(|()| instance))
+    }
+}
+impl Drop for PendingConnect {
+    fn drop(&mut self) {
+        let state = match self.0.get_mut() {
+            Ok(state) => state,
+            Err(..) => return,
+        };
+        if let Some(Armed { thread, done, .. }) = state.armed.take() {
+            // The thread might not have entered ConnectNamedPipe by the time the
+            // first cancellation happens, so keep trying until it's out.
+            while !done.load(SeqCst) {
+                unsafe { CancelSynchronousIo(thread.as_raw_handle()) };
+                thread::sleep(CANCEL_RETRY_INTERVAL);
+            }
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A manual-reset event, closed when dropped.
+struct Event(HANDLE);
+// SAFETY: events can be signalled, reset and closed from any thread.
+unsafe impl Send for Event {
+}
+unsafe impl Sync for Event {
+}
+impl Event {
+    fn new() -> io::Result<Self> {
+        let handle = unsafe { CreateEventW(ptr::null_mut(), 1, 0, ptr::null()) };
+        if handle.is_null() {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(Self(handle))
+        }
+    }
+}
+impl Drop for Event {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.0) };
+    }
+}
@@ -1,4 +1,9 @@
-use super::super::{imports::*, FileHandleOps};
+use super::{
+    super::{imports::*, FileHandleOps},
+    handle_state::{get_handle_state, HandleState},
+    GrowStrategy,
+    ReadMsgOutcome,
+};
 use std::{
     io,
     mem::ManuallyDrop,
@@ -6,6 +11,19 @@ use std::{
     sync::{atomic::AtomicBool, Arc},
 };
 
+/// The size of the buffer which messages are first read into by
+/// `recv_msgs_upto` with the doubling strategy, before it has grown.
+const FIRST_MSG_BUFFER_SIZE: usize = 1024;
+
+/// How a stream sizes the buffers which `recv_msgs_upto` receives messages
+/// into, kept by the stream between calls.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MsgSizing {
+    pub strategy: GrowStrategy,
+    /// The size of the biggest buffer which a message was received into.
+    pub capacity: usize,
+}
+
 /// The actual implementation of a named pipe server or client.
 #[repr(transparent)]
 pub struct PipeOps(pub(crate) FileHandleOps);
@@ -80,6 +98,173 @@ impl PipeOps {
             Ok(Err(bytes_left_in_message))
         }
     }
+    /// Reads a message, or the next part of one, into the specified buffer with
+    /// a single `ReadFile` call, reporting whether the end of the message was
+    /// reached instead of returning `ERROR_MORE_DATA` as an error.
+    pub fn read_msg_part(&self, buf: &mut [u8]) -> io::Result<ReadMsgOutcome> {
+        debug_assert!(
+            buf.len() <= DWORD::max_value() as usize,
+            "buffer is bigger than maximum buffer size for ReadFile",
+        );
+        let mut bytes_read: DWORD = 0;
+        let success = unsafe {
+            ReadFile(
+                self.as_raw_handle(),
+                buf.as_mut_ptr() as *mut _,
+                buf.len() as DWORD,
+                &mut bytes_read as *mut _,
+                ptr::null_mut(),
+            ) != 0
+        };
+        if success {
+            Ok(ReadMsgOutcome::Complete(bytes_read as usize))
+        } else {
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() == Some(ERROR_MORE_DATA as i32) {
+                Ok(ReadMsgOutcome::Partial(bytes_read as usize))
+            } else {
+                Err(error)
+            }
+        }
+    }
+    /// Blocks until the other end sends something, without taking anything out
+    /// of the pipe, by reading zero bytes.
+    pub fn wait_for_data(&self) -> io::Result<()> {
+        self.read_msg_part(&mut []).map(|_| ())
+    }
+    /// Reads one message in its entirety, blocking until one arrives, sizing
+    /// the buffer according to the strategy and the length of the message, if
+    /// it's already known.
+    fn read_whole_msg(
+        &self,
+        msg_len: Option<usize>,
+        sizing: &mut MsgSizing,
+    ) -> io::Result<Vec<u8>> {
+        let initial_size = match (sizing.strategy, msg_len) {
+            (GrowStrategy::ExactFit, Some(msg_len)) => msg_len,
+            (GrowStrategy::ExactFit, None) => {
+                // A read into an empty buffer waits for a message to arrive without taking
+                // it out of the pipe, unless the message is empty, after which its size can
+                // be peeked.
+                if self.read_msg_part(&mut [])?.is_complete() {
+                    return Ok(Vec::new());
+                }
+                self.peek_sizes()?.1
+            },
+            (GrowStrategy::Doubling, msg_len) => {
+                let mut size = sizing.capacity.max(FIRST_MSG_BUFFER_SIZE);
+                while size < msg_len.unwrap_or(0) {
+                    size *= 2;
+                }
+                size
+            },
+        };
+        let mut msg = vec![0; initial_size.max(1)];
+        let mut len = 0;
+        loop {
+            match self.read_msg_part(&mut msg[len..])? {
+                ReadMsgOutcome::Complete(bytes_read) => {
+                    sizing.capacity = sizing.capacity.max(msg.len());
+                    msg.truncate(len + bytes_read);
+                    return Ok(msg);
+                },
+                ReadMsgOutcome::Partial(bytes_read) => {
+                    len += bytes_read;
+                    msg.resize(msg.len() * 2, 0);
+                },
+            }
+        }
+    }
+    /// Returns the total amount of bytes in the pipe and the amount of bytes
+    /// left in the message which a read would start with, without blocking.
+    fn peek_sizes(&self) -> io::Result<(usize, usize)> {
+        let mut total_bytes_available: DWORD = 0;
+        let mut bytes_left_in_message: DWORD = 0;
+        let success = unsafe {
+            PeekNamedPipe(
+                self.as_raw_handle(),
+                ptr::null_mut(),
+                0,
+                ptr::null_mut(),
+                &mut total_bytes_available as *mut _,
+                &mut bytes_left_in_message as *mut _,
+            ) != 0
+        };
+        if success {
+            Ok((
+                total_bytes_available as usize,
+                bytes_left_in_message as usize,
+            ))
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+    /// Reads one message, blocking until it arrives, and then as many of the
+    /// messages which are already in the pipe as possible without blocking, up
+    /// to `max` in total, appending them to `buf_pool`.
+    pub fn recv_msgs_upto(
+        &self,
+        max: usize,
+        buf_pool: &mut Vec<Vec<u8>>,
+        sizing: &mut MsgSizing,
+    ) -> io::Result<usize> {
+        if max == 0 {
+            return Ok(0);
+        }
+        buf_pool.push(self.read_whole_msg(None, sizing)?);
+        let mut count = 1;
+        while count < max {
+            let (total_bytes_available, msg_len) = self.peek_sizes()?;
+            if total_bytes_available == 0 {
+                break;
+            }
+            // The exact size is known, so this never has to grow the buffer.
+            buf_pool.push(self.read_whole_msg(Some(msg_len), sizing)?);
+            count += 1;
+        }
+        Ok(count)
+    }
+    /// Copies the data waiting in the pipe into the buffer without consuming
+    /// it, failing with `WouldBlock` if there is none and returning 0 if the
+    /// other end has disconnected.
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut bytes_read: DWORD = 0;
+        let mut total_bytes_available: DWORD = 0;
+        let len = buf.len().min(DWORD::max_value() as usize);
+        let success = unsafe {
+            PeekNamedPipe(
+                self.as_raw_handle(),
+                buf.as_mut_ptr() as *mut _,
+                len as DWORD,
+                &mut bytes_read as *mut _,
+                &mut total_bytes_available as *mut _,
+                ptr::null_mut(),
+            ) != 0
+        };
+        if !success {
+            let error = io::Error::last_os_error();
+            return match error.kind() {
+                io::ErrorKind::BrokenPipe | io::ErrorKind::NotConnected => Ok(0),
+                _ => Err(error),
+            };
+        }
+        if total_bytes_available == 0 && !buf.is_empty() {
+            Err(io::Error::from(io::ErrorKind::WouldBlock))
+        } else {
+            Ok(bytes_read as usize)
+        }
+    }
+    /// Returns `false` if the other end has disconnected and there is no data
+    /// left in the pipe, without blocking.
+    pub fn is_connected(&self) -> io::Result<bool> {
+        match self.peek_sizes() {
+            Ok(..) => Ok(true),
+            Err(e) => match e.kind() {
+                io::ErrorKind::BrokenPipe | io::ErrorKind::NotConnected => Ok(false),
+                _ => Err(e),
+            },
+        }
+    }
     /// Reads bytes from the named pipe. Mirrors `std::io::Read`.
     pub fn read_bytes(&self, buf: &mut [u8]) -> io::Result<usize> {
         self.0.read(buf)
@@ -131,6 +316,9 @@ impl PipeOps {
             Err(io::Error::last_os_error())
         }
     }
+    pub fn get_handle_state(&self, is_server: bool) -> io::Result<HandleState> {
+        unsafe { get_handle_state(self.as_raw_handle(), is_server) }
+    }
 
     /// Blocks until connected. If connected, does not do anything.
     pub fn connect_server(&self) -> io::Result<()> {
@@ -1,11 +1,17 @@
 use super::{
-    super::{imports::*, AsRawHandle, FromRawHandle, IntoRawHandle},
+    super::{imports::*, AsRawHandle, FromRawHandle, IntoRawHandle, PeerCredentials},
+    GrowStrategy,
+    HandleState,
+    ImpersonationLevel,
+    MessageReader,
+    MsgSizing,
     PipeMode,
     PipeOps,
     PipeStreamInternals,
     PipeStreamRole,
+    ReadMsgOutcome,
 };
-use crate::{PartialMsgWriteError, ReliableReadMsg};
+use crate::{unsupported, Connection, PartialMsgWriteError, ReliableReadMsg};
 use std::{
     ffi::OsStr,
     fmt::{self, Debug, Formatter},
@@ -24,12 +30,15 @@ use std::{
 macro_rules! create_stream_type_base {
     (
         $ty:ident:
+            extra_fields: {$($field:ident: $field_ty:ty),*},
             extra_methods: {$($extra_methods:tt)*},
             doc: $doc:tt
     ) => {
         #[doc = $doc]
         pub struct $ty {
             instance: Arc<(PipeOps, AtomicBool)>,
+            // Shared by all stream types, some of which have no use for them.
+            $(#[allow(dead_code)] $field: $field_ty,)*
         }
         impl $ty {
             // fn is_server(&self) -> bool and fn is_client(&self) -> bool
@@ -51,6 +60,27 @@ macro_rules! create_stream_type_base {
             pub fn server_session_id(&self) -> io::Result<u32> {
                 self.instance.0.get_server_session_id()
             }
+            /// Retrieves the state of the pipe handle — whether it's in nonblocking mode, its read mode, the collection parameters for remote client connections and the user name of the client — as one [`HandleState`] snapshot, using a single system call in most cases.
+            ///
+            /// This is the read-side counterpart of [`set_nonblocking`](#method.set_nonblocking), useful for verifying what the handle was actually configured with.
+            pub fn handle_state(&self) -> io::Result<HandleState> {
+                self.instance.0.get_handle_state(self.is_server())
+            }
+            /// Retrieves the amount of instances of the pipe which currently exist and the maximum amount of instances which the server allows, as `(current_instances, max_instances)`, giving monitoring tools which connect as clients a view of how much capacity the server has left.
+            ///
+            /// The maximum is the one which the first instance of the pipe was created with, since the system uses that one for all instances regardless of what the later ones ask for. A maximum of 255 (`PIPE_UNLIMITED_INSTANCES`) means that the amount of instances is only limited by system resources. Both numbers are a snapshot which may already be out of date by the time they're returned.
+            ///
+            /// # System calls
+            /// - `GetNamedPipeHandleStateW`
+            /// - `GetNamedPipeInfo`
+            pub fn server_instance_info(&self) -> io::Result<(u32, u32)> {
+                let current_instances = self.handle_state()?.current_instances;
+                let max_instances = unsafe {
+                    // SAFETY: the handle is owned by the stream and thus valid
+                    crate::os::windows::named_pipe::handle_state::get_max_instances(self.as_raw_handle())?
+                };
+                Ok((current_instances, max_instances))
+            }
             // fn is_split(&self) -> bool — generated by the downstream macros
             /// Disconnects the named pipe stream without flushing buffers, causing all data in those buffers to be lost. This is much faster than simply dropping the stream, since, for non-async named pipes. the `Drop` implementation flushes first.
             ///
@@ -80,7 +110,7 @@ macro_rules! create_stream_type_base {
         #[doc(hidden)]
         impl PipeStreamInternals for $ty {
             fn build(instance: Arc<(PipeOps, AtomicBool)>) -> Self {
-                Self { instance }
+                Self { instance, $($field: Default::default(),)* }
             }
         }
         impl Drop for $ty {
@@ -99,6 +129,7 @@ macro_rules! create_stream_type_base {
                 self.instance.0.as_raw_handle()
             }
         }
+        impl_as_borrowed_handle!($ty);
         impl Debug for $ty {
             fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
                 f.debug_struct(stringify!($ty))
@@ -120,6 +151,7 @@ macro_rules! create_stream_type {
     ) => {
         create_stream_type_base!(
             $ty:
+            extra_fields: {msg_sizing: MsgSizing},
             extra_methods: {
                 /// Tries to connect to the specified named pipe (the `\\.\pipe\` prefix is added automatically), returning a named pipe stream of the stream type provided via generic parameters. If there is no available server, returns immediately.
                 pub fn connect(name: impl AsRef<OsStr>) -> io::Result<Self> {
@@ -133,7 +165,19 @@ macro_rules! create_stream_type {
                         Self::WRITE_MODE.is_some(),
                     )?;
                     let instance = (pipeops, AtomicBool::new(false));
-                    Ok(Self { instance: Arc::new(instance) })
+                    Ok(Self { instance: Arc::new(instance), msg_sizing: MsgSizing::default() })
+                }
+                /// Tries to connect to the specified named pipe like [`connect`](#method.connect) does, performing a single attempt which fails with an error of kind [`AddrInUse`] if all instances of the pipe are busy, instead of the raw `ERROR_PIPE_BUSY` error. Useful for tools which probe whether a server is available, since the error kind is the same as the one of the equivalent method on Ud-sockets.
+                ///
+                /// [`AddrInUse`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.AddrInUse " "
+                pub fn connect_immediate(name: impl AsRef<OsStr>) -> io::Result<Self> {
+                    Self::_connect(name.as_ref()).map_err(|e| {
+                        if e.raw_os_error() == Some(ERROR_PIPE_BUSY as i32) {
+                            io::Error::new(io::ErrorKind::AddrInUse, "all instances of the pipe are busy")
+                        } else {
+                            e
+                        }
+                    })
                 }
                 /// Tries to connect to the specified named pipe at a remote computer (the `\\<hostname>\pipe\` prefix is added automatically), returning a named pipe stream of the stream type provided via generic parameters. If there is no available server, returns immediately.
                 pub fn connect_to_remote(pipe_name: impl AsRef<OsStr>, hostname: impl AsRef<OsStr>) -> io::Result<Self> {
@@ -147,7 +191,7 @@ macro_rules! create_stream_type {
                         Self::WRITE_MODE.is_some(),
                     )?;
                     let instance = (pipeops, AtomicBool::new(false));
-                    Ok(Self { instance: Arc::new(instance) })
+                    Ok(Self { instance: Arc::new(instance), msg_sizing: MsgSizing::default() })
                 }
                 /// Sets whether the nonblocking mode for the pipe stream is enabled. By default, it is disabled.
                 ///
@@ -163,6 +207,25 @@ macro_rules! create_stream_type {
                         super::set_nonblocking_for_stream::<Self>(self.as_raw_handle(), nonblocking)
                     }
                 }
+                /// Switches the read mode of the pipe stream on a live connection, leaving the nonblocking mode as it is. This is what the transfer mode agreed on with [`negotiate_mode_client`]/[`negotiate_mode_server`] is meant to be applied with.
+                ///
+                /// Only pipes which the server created in message mode can be read in message mode; switching a byte-mode pipe to [`PipeMode::Messages`] fails. Reading a message-mode pipe in byte mode makes reads disregard message boundaries. The type of the stream does not change, however: the read mode which it's named after is what [`set_nonblocking`] switches back to, so it has to be called before this method if both are needed.
+                ///
+                /// # System calls
+                /// - `GetNamedPipeHandleStateW`
+                /// - `SetNamedPipeHandleState`
+                ///
+                /// [`negotiate_mode_client`]: ../../../fn.negotiate_mode_client.html " "
+                /// [`negotiate_mode_server`]: ../../../fn.negotiate_mode_server.html " "
+                /// [`PipeMode::Messages`]: enum.PipeMode.html#variant.Messages " "
+                /// [`set_nonblocking`]: #method.set_nonblocking " "
+                pub fn set_read_mode(&self, mode: PipeMode) -> io::Result<()> {
+                    let nonblocking = self.handle_state()?.nonblocking;
+                    unsafe {
+                        // SAFETY: the handle is owned by the stream and thus valid
+                        super::set_handle_mode(self.as_raw_handle(), mode, nonblocking)
+                    }
+                }
                 /// Returns `true` if the stream was created by a listener (server-side), `false` if it was created by connecting to a server (server-side).
                 pub fn is_server(&self) -> bool {
                     // When a listener reserves an instance, it sets the flag and then clears it
@@ -175,6 +238,54 @@ macro_rules! create_stream_type {
                 pub fn is_client(&self) -> bool {
                     !self.is_server()
                 }
+                /// Retrieves the process identifier of the client side of the named pipe connection and the SID of the user which it runs as, in the same form as [`own_credentials`] describes the current process, so that a handshake can compare the two.
+                ///
+                /// The SID is found out by briefly impersonating the client, so this only works on the server side, once the client has sent something, and only if the client has granted at least the [`Identification`] level.
+                ///
+                /// [`own_credentials`]: ../fn.own_credentials.html " "
+                /// [`Identification`]: enum.ImpersonationLevel.html#variant.Identification " "
+                pub fn client_credentials(&self) -> io::Result<PeerCredentials> {
+                    unsafe {
+                        // SAFETY: the handle is owned by the stream and thus valid
+                        super::impersonation::get_client_credentials(self.as_raw_handle())
+                    }
+                }
+                /// Impersonates the client on the current thread, runs the closure, and reverts to the identity of the server before returning the closure's return value, making it impossible for the impersonation to outlive the closure. This is how a server performs operations, such as opening files, with the permissions of the client rather than its own.
+                ///
+                /// The impersonation is undone by a drop guard, and thus ends even if the closure panics; if the system fails to undo it, the process is aborted, since carrying on would mean running with the client's identity. Only works on the server side, and only if the client has granted at least the [`Impersonation`] level; otherwise, the closure never runs and an error is returned. The level is checked by impersonating the client once beforehand, which the system only allows once the client has sent something.
+                ///
+                /// # Threads
+                /// Impersonation applies to the current thread only. Threads spawned by the closure, as well as work which the closure sends to other threads — such as a thread pool or an async runtime — run with the identity of the server, not the client.
+                ///
+                /// # System calls
+                /// - `ImpersonateNamedPipeClient`, twice
+                /// - `OpenThreadToken`
+                /// - `GetTokenInformation`
+                /// - `RevertToSelf`, twice
+                ///
+                /// [`Impersonation`]: enum.ImpersonationLevel.html#variant.Impersonation " "
+                pub fn impersonate_client_scoped<R>(&self, f: impl FnOnce() -> R) -> io::Result<R> {
+                    let level = unsafe {
+                        // SAFETY: the handle is owned by the stream and thus valid
+                        super::impersonation::get_client_impersonation_level(self.as_raw_handle())?
+                    };
+                    if level < ImpersonationLevel::Impersonation {
+                        return Err(io::Error::new(
+                            io::ErrorKind::PermissionDenied,
+                            "the client has not granted the impersonation level",
+                        ));
+                    }
+                    unsafe {
+                        // SAFETY: as above
+                        super::impersonation::impersonate_client_scoped(self.as_raw_handle(), f)
+                    }
+                }
+                /// Consumes the stream and returns its handle without closing it or disconnecting the client, the same way as `into_raw_handle` does.
+                ///
+                /// This is meant for code which transfers ownership of the pipe instance out-of-band, such as by passing it to a C API which will close it. **Closing the handle becomes the caller's responsibility**: it is leaked unless closed by whoever it's given to or turned back into a stream with `from_raw_handle`, and closing it more than once might close an unrelated handle which has been given the same value in the meantime. On the server side, the listener never reuses an instance which has been forgotten.
+                pub fn forget(self) -> HANDLE {
+                    self.into_raw_handle()
+                }
                 fn is_split(&self) -> bool {
                     false // non-async streams don't yet implement splitting
                 }
@@ -189,15 +300,19 @@ macro_rules! create_stream_type {
                     PipeOps::from_raw_handle(handle)
                 };
                 Self {
-                    instance: Arc::new((pipeops, AtomicBool::new(true)))
+                    instance: Arc::new((pipeops, AtomicBool::new(true))),
+                    msg_sizing: MsgSizing::default(),
                 }
             }
         }
         #[cfg(windows)]
         impl IntoRawHandle for $ty {
             fn into_raw_handle(self) -> HANDLE {
-                let handle = self.instance.0.as_raw_handle();
-                handle
+                // Neither disconnect nor release the instance: the listener's reference to it,
+                // if any, is kept alive by the leaked one, and the in-use flag stays set, which
+                // keeps the listener from handing the instance out again.
+                let self_ = ManuallyDrop::new(self);
+                self_.instance.0.as_raw_handle()
             }
         }
         #[cfg(windows)]
@@ -302,6 +417,19 @@ Created either by using `PipeListener` or by connecting to a named pipe server.
 "
 }
 
+// Accepted pipe streams are routinely moved into worker threads, so make sure
+// that doesn't silently become impossible.
+const _: fn() = || {
+    fn assert_send_static<T: Send + 'static>() {
+    }
+    assert_send_static::<ByteReaderPipeStream>();
+    assert_send_static::<ByteWriterPipeStream>();
+    assert_send_static::<DuplexBytePipeStream>();
+    assert_send_static::<MsgReaderPipeStream>();
+    assert_send_static::<MsgWriterPipeStream>();
+    assert_send_static::<DuplexMsgPipeStream>();
+};
+
 impl Read for ByteReaderPipeStream {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         self.instance.0.read_bytes(buf)
@@ -330,12 +458,131 @@ impl Write for DuplexBytePipeStream {
         self.instance.0.flush()
     }
 }
+impl Read for &DuplexBytePipeStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.instance.0.read_bytes(buf)
+    }
+}
+impl Write for &DuplexBytePipeStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.instance.0.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.instance.0.flush()
+    }
+}
+
+macro_rules! impl_connection {
+    ($ty:ident) => {
+        impl Connection for $ty {
+            fn peer_process_id(&self) -> io::Result<u32> {
+                if self.is_server() {
+                    self.client_process_id()
+                } else {
+                    self.server_process_id()
+                }
+            }
+            fn shutdown(&self) -> io::Result<()> {
+                Err(unsupported(
+                    "named pipes cannot be shut down without closing them",
+                ))
+            }
+            fn is_connected(&self) -> io::Result<bool> {
+                self.instance.0.is_connected()
+            }
+            fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+                self.instance.0.peek(buf)
+            }
+        }
+    };
+}
+impl_connection!(DuplexBytePipeStream);
+impl_connection!(DuplexMsgPipeStream);
 
 impl Read for MsgReaderPipeStream {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         self.instance.0.read_bytes(buf)
     }
 }
+impl MsgReaderPipeStream {
+    /// Reads a message into the specified buffer, or as much of it as fits,
+    /// reporting whether the whole message was received.
+    ///
+    /// Unlike [`read_msg`], which allocates a bigger buffer when a message does
+    /// not fit, this performs exactly one read: if the message is bigger than
+    /// the buffer, the buffer is filled with its beginning and
+    /// [`ReadMsgOutcome::Partial`] is returned, with the rest of the message
+    /// left in the pipe for the next call (or the next read of any other kind)
+    /// to continue from. Useful for receiving messages of unbounded size in
+    /// fixed-size chunks.
+    ///
+    /// # System calls
+    /// - `ReadFile`
+    ///
+    /// [`read_msg`]: ../../../trait.ReliableReadMsg.html#tymethod.read_msg " "
+    /// [`ReadMsgOutcome::Partial`]: enum.ReadMsgOutcome.html#variant.Partial " "
+    pub fn read_msg_part(&mut self, buf: &mut [u8]) -> io::Result<ReadMsgOutcome> {
+        self.instance.0.read_msg_part(buf)
+    }
+    /// Creates a [`MessageReader`] which reads messages from the stream one at
+    /// a time as byte readers, in chunks, without buffering whole messages.
+    ///
+    /// [`MessageReader`]: struct.MessageReader.html " "
+    pub fn message_reader(&mut self) -> MessageReader<'_> {
+        MessageReader::new(&self.instance.0)
+    }
+    /// Receives a batch of messages: blocks until at least one message arrives,
+    /// then reads as many of the messages which are already available as
+    /// possible without blocking, up to `max` messages in total. The messages
+    /// are appended to `buf_pool` as separate buffers, and the amount of
+    /// messages read is returned.
+    ///
+    /// As opposed to reading messages one by one, this allows bursts of
+    /// messages to be processed in bulk, amortizing the per-message overhead of
+    /// whatever happens after reading. The call never waits for more messages
+    /// than the first one: if fewer than `max` messages were already in the
+    /// pipe, it returns right away with however many there were. In nonblocking
+    /// mode, a [`WouldBlock`] error is returned if there isn't even a single
+    /// message available.
+    ///
+    /// Empty messages are only received as the first message of a batch, since
+    /// they cannot be told apart from an empty pipe without blocking.
+    ///
+    /// How the buffers are sized is controlled by [`set_grow_strategy`].
+    ///
+    /// # System calls
+    /// - `ReadFile`
+    /// - `PeekNamedPipe`
+    ///
+    /// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock " "
+    /// [`set_grow_strategy`]: #method.set_grow_strategy " "
+    pub fn recv_msgs_upto(&mut self, max: usize, buf_pool: &mut Vec<Vec<u8>>) -> io::Result<usize> {
+        self.instance.0.recv_msgs_upto(max, buf_pool, &mut self.msg_sizing)
+    }
+    /// Sets how the buffers which [`recv_msgs_upto`] receives messages into
+    /// are sized. The default is [`GrowStrategy::ExactFit`], which never
+    /// allocates more memory than the messages take up.
+    ///
+    /// Switching to [`GrowStrategy::Doubling`] trades memory for fewer system
+    /// calls and allocations, which pays off for streams which receive a lot
+    /// of messages of similar sizes. The size which the doubling strategy has
+    /// grown to so far is kept when switching between the strategies.
+    ///
+    /// [`recv_msgs_upto`]: #method.recv_msgs_upto " "
+    /// [`GrowStrategy::ExactFit`]: enum.GrowStrategy.html#variant.ExactFit " "
+    /// [`GrowStrategy::Doubling`]: enum.GrowStrategy.html#variant.Doubling " "
+    pub fn set_grow_strategy(&mut self, strategy: GrowStrategy) {
+        self.msg_sizing.strategy = strategy;
+    }
+    /// Returns the strategy which is used to size the buffers of
+    /// [`recv_msgs_upto`], as set by [`set_grow_strategy`].
+    ///
+    /// [`recv_msgs_upto`]: #method.recv_msgs_upto " "
+    /// [`set_grow_strategy`]: #method.set_grow_strategy " "
+    pub fn grow_strategy(&self) -> GrowStrategy {
+        self.msg_sizing.strategy
+    }
+}
 impl ReliableReadMsg for MsgReaderPipeStream {
     fn read_msg(&mut self, buf: &mut [u8]) -> io::Result<Result<usize, Vec<u8>>> {
         self.instance.0.read_msg(buf)
@@ -345,6 +592,9 @@ impl ReliableReadMsg for MsgReaderPipeStream {
     }
 }
 
+/// Every `write` call sends one message with a single `WriteFile` straight
+/// from the provided buffer, without allocating, which keeps sending small
+/// messages at a high rate cheap.
 impl Write for MsgWriterPipeStream {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         if self.instance.0.write(buf)? == buf.len() {
@@ -363,6 +613,49 @@ impl Read for DuplexMsgPipeStream {
         self.instance.0.read_bytes(buf)
     }
 }
+impl DuplexMsgPipeStream {
+    /// Reads a message into the specified buffer, or as much of it as fits,
+    /// with exactly one read. See [the same method on `MsgReaderPipeStream`]
+    /// for details.
+    ///
+    /// [the same method on `MsgReaderPipeStream`]: struct.MsgReaderPipeStream.html#method.read_msg_part " "
+    pub fn read_msg_part(&mut self, buf: &mut [u8]) -> io::Result<ReadMsgOutcome> {
+        self.instance.0.read_msg_part(buf)
+    }
+    /// Creates a [`MessageReader`] which reads messages from the stream one at
+    /// a time as byte readers, like [the same method on
+    /// `MsgReaderPipeStream`].
+    ///
+    /// [`MessageReader`]: struct.MessageReader.html " "
+    /// [the same method on `MsgReaderPipeStream`]: struct.MsgReaderPipeStream.html#method.message_reader " "
+    pub fn message_reader(&mut self) -> MessageReader<'_> {
+        MessageReader::new(&self.instance.0)
+    }
+    /// Receives a batch of up to `max` messages which are already available,
+    /// waiting only for the first one. See [the same method on
+    /// `MsgReaderPipeStream`] for details.
+    ///
+    /// [the same method on `MsgReaderPipeStream`]: struct.MsgReaderPipeStream.html#method.recv_msgs_upto " "
+    pub fn recv_msgs_upto(&mut self, max: usize, buf_pool: &mut Vec<Vec<u8>>) -> io::Result<usize> {
+        self.instance.0.recv_msgs_upto(max, buf_pool, &mut self.msg_sizing)
+    }
+    /// Sets how the buffers which [`recv_msgs_upto`] receives messages into
+    /// are sized. See [the same method on `MsgReaderPipeStream`] for details.
+    ///
+    /// [`recv_msgs_upto`]: #method.recv_msgs_upto " "
+    /// [the same method on `MsgReaderPipeStream`]: struct.MsgReaderPipeStream.html#method.set_grow_strategy " "
+    pub fn set_grow_strategy(&mut self, strategy: GrowStrategy) {
+        self.msg_sizing.strategy = strategy;
+    }
+    /// Returns the strategy which is used to size the buffers of
+    /// [`recv_msgs_upto`], as set by [`set_grow_strategy`].
+    ///
+    /// [`recv_msgs_upto`]: #method.recv_msgs_upto " "
+    /// [`set_grow_strategy`]: #method.set_grow_strategy " "
+    pub fn grow_strategy(&self) -> GrowStrategy {
+        self.msg_sizing.strategy
+    }
+}
 impl ReliableReadMsg for DuplexMsgPipeStream {
     fn read_msg(&mut self, buf: &mut [u8]) -> io::Result<Result<usize, Vec<u8>>> {
         self.instance.0.read_msg(buf)
@@ -371,6 +664,9 @@ impl ReliableReadMsg for DuplexMsgPipeStream {
         self.instance.0.try_read_msg(buf)
     }
 }
+/// Every `write` call sends one message with a single `WriteFile` straight
+/// from the provided buffer, without allocating, which keeps sending small
+/// messages at a high rate cheap.
 impl Write for DuplexMsgPipeStream {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         if self.instance.0.write(buf)? == buf.len() {
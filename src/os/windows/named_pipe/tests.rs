@@ -0,0 +1,392 @@
+use super::{
+    super::own_credentials,
+    is_impersonating,
+    DuplexBytePipeStream,
+    DuplexMsgPipeStream,
+    GrowStrategy,
+    ImpersonationLevel,
+    PipeListenerOptions,
+    PipeMode,
+    PipeNameTakenError,
+    ReadMsgOutcome,
+};
+use std::{
+    ffi::OsStr,
+    io::{self, prelude::*},
+    num::NonZeroU8,
+    panic::{self, AssertUnwindSafe},
+    process,
+    sync::mpsc,
+    thread::{self, JoinHandle},
+};
+
+/// Sends the specified messages from a server thread to a client, which is
+/// returned to receive them once all of them have been written. The server
+/// thread finishes once the client has read everything, since dropping the
+/// server end of the pipe flushes it.
+///
+/// The client doesn't read anything until all of the messages have been
+/// written, so the pipe is given a buffer big enough to hold them all at once:
+/// with the default size, writing more than fits would wait for the client
+/// forever.
+fn send_messages(
+    name: &str,
+    messages: &'static [&'static [u8]],
+) -> (DuplexMsgPipeStream, JoinHandle<()>) {
+    let name = format!("interprocess-test-{}-{}", name, process::id());
+    let listener = PipeListenerOptions::new()
+        .name(OsStr::new(&name))
+        .mode(PipeMode::Messages)
+        .output_buffer_size_hint(messages.iter().map(|msg| msg.len()).sum::<usize>() + 4096)
+        .create::<DuplexMsgPipeStream>()
+        .expect("failed to create listener");
+    let (written_sender, written) = mpsc::channel();
+    let server = thread::spawn(move || {
+        let mut conn = listener.accept().expect("failed to accept connection");
+        for msg in messages {
+            conn.write_all(msg).expect("failed to send message");
+        }
+        written_sender.send(()).unwrap();
+    });
+    let client = DuplexMsgPipeStream::connect(&name).expect("failed to connect");
+    written.recv().unwrap();
+    (client, server)
+}
+
+#[test]
+fn read_msg_part_smaller_than_buffer() {
+    let (mut client, server) = send_messages("read-msg-small", &[b"first", b"second"]);
+    let mut buf = [0; 64];
+    assert_eq!(
+        client.read_msg_part(&mut buf).unwrap(),
+        ReadMsgOutcome::Complete(5)
+    );
+    assert_eq!(&buf[..5], b"first");
+    assert_eq!(
+        client.read_msg_part(&mut buf).unwrap(),
+        ReadMsgOutcome::Complete(6)
+    );
+    assert_eq!(&buf[..6], b"second");
+    server.join().unwrap();
+}
+
+#[test]
+fn read_msg_part_larger_than_buffer() {
+    let (mut client, server) = send_messages("read-msg-large", &[b"0123456789", b"next"]);
+    let mut buf = [0; 4];
+    let mut received = Vec::new();
+    loop {
+        let outcome = client.read_msg_part(&mut buf).unwrap();
+        received.extend_from_slice(&buf[..outcome.bytes_read()]);
+        if outcome.is_complete() {
+            break;
+        }
+        assert_eq!(outcome, ReadMsgOutcome::Partial(4));
+    }
+    assert_eq!(received, b"0123456789");
+    // The message boundary is kept: the next read starts at the next message.
+    assert_eq!(
+        client.read_msg_part(&mut buf).unwrap(),
+        ReadMsgOutcome::Complete(4)
+    );
+    assert_eq!(&buf, b"next");
+    server.join().unwrap();
+}
+
+#[test]
+fn min_impersonation_level() {
+    let name = format!("interprocess-test-impersonation-{}", process::id());
+    let listener = PipeListenerOptions::new()
+        .name(OsStr::new(&name))
+        .mode(PipeMode::Messages)
+        .min_impersonation_level(ImpersonationLevel::Identification)
+        .create::<DuplexMsgPipeStream>()
+        .expect("failed to create listener");
+    let client = thread::spawn(move || {
+        let mut conn = DuplexMsgPipeStream::connect(&name).expect("failed to connect");
+        conn.write_all(b"ping").expect("failed to send message");
+    });
+    // Clients grant the impersonation level by default, which is more than enough.
+    let mut conn = listener.accept().expect("failed to accept connection");
+    let mut buf = [0; 4];
+    assert_eq!(
+        conn.read_msg_part(&mut buf).unwrap(),
+        ReadMsgOutcome::Complete(4)
+    );
+    client.join().unwrap();
+}
+
+#[test]
+fn accept_authenticated() {
+    use crate::Connection;
+    use std::time::Duration;
+
+    let name = format!("interprocess-test-accept-authenticated-{}", process::id());
+    let listener = PipeListenerOptions::new()
+        .name(OsStr::new(&name))
+        .mode(PipeMode::Messages)
+        .min_impersonation_level(ImpersonationLevel::Identification)
+        .create::<DuplexMsgPipeStream>()
+        .expect("failed to create listener");
+    let client = thread::spawn(move || {
+        for msg in [b"fake", b"real"].iter() {
+            // The listener only creates the next instance once it's done with
+            // the previous client.
+            let mut conn = loop {
+                match DuplexMsgPipeStream::connect(&name) {
+                    Ok(conn) => break conn,
+                    Err(_) => thread::sleep(Duration::from_millis(10)),
+                }
+            };
+            conn.write_all(*msg).expect("failed to send message");
+        }
+    });
+    let mut conn = listener
+        .accept_authenticated(|conn| {
+            let mut buf = [0; 4];
+            conn.peek(&mut buf)?;
+            Ok(&buf == b"real")
+        })
+        .expect("failed to accept connection");
+    let mut buf = [0; 4];
+    assert_eq!(
+        conn.read_msg_part(&mut buf).unwrap(),
+        ReadMsgOutcome::Complete(4)
+    );
+    assert_eq!(&buf, b"real");
+    client.join().unwrap();
+}
+
+#[test]
+fn impersonate_client_scoped() {
+    let name = format!("interprocess-test-impersonate-scoped-{}", process::id());
+    let listener = PipeListenerOptions::new()
+        .name(OsStr::new(&name))
+        .create::<DuplexBytePipeStream>()
+        .expect("failed to create listener");
+    let client = thread::spawn(move || {
+        let mut conn = DuplexBytePipeStream::connect(&name).expect("failed to connect");
+        conn.write_all(b"ping").expect("failed to send data");
+        conn
+    });
+    let mut conn = listener.accept().expect("failed to accept connection");
+    conn.read_exact(&mut [0; 4]).unwrap();
+    assert_eq!(conn.impersonate_client_scoped(|| 42).unwrap(), 42);
+    assert!(!is_impersonating().unwrap());
+    assert!(conn.impersonate_client_scoped(|| is_impersonating().unwrap()).unwrap());
+    assert!(!is_impersonating().unwrap());
+    // The impersonation ends even when the closure unwinds, so it can be started
+    // again afterwards.
+    let unwound = panic::catch_unwind(AssertUnwindSafe(|| {
+        conn.impersonate_client_scoped(|| panic!("closure panicked"))
+    }));
+    assert!(unwound.is_err());
+    assert!(conn.impersonate_client_scoped(|| ()).is_ok());
+    // Clients have nobody to impersonate.
+    let client_conn = client.join().unwrap();
+    assert!(client_conn.impersonate_client_scoped(|| ()).is_err());
+}
+
+#[test]
+fn client_credentials() {
+    let name = format!("interprocess-test-client-credentials-{}", process::id());
+    let listener = PipeListenerOptions::new()
+        .name(OsStr::new(&name))
+        .create::<DuplexBytePipeStream>()
+        .expect("failed to create listener");
+    let client = thread::spawn(move || {
+        let mut conn = DuplexBytePipeStream::connect(&name).expect("failed to connect");
+        conn.write_all(b"ping").expect("failed to send data");
+        conn
+    });
+    let mut conn = listener.accept().expect("failed to accept connection");
+    conn.read_exact(&mut [0; 4]).unwrap();
+    // Both ends are the current process.
+    assert_eq!(
+        conn.client_credentials().unwrap(),
+        own_credentials().unwrap()
+    );
+    client.join().unwrap();
+}
+
+#[test]
+fn message_reader() {
+    static BIG: [u8; 3000] = [7; 3000];
+    static MESSAGES: &[&[u8]] = &[&BIG, b"skipped", b"", b"last"];
+    let (mut client, server) = send_messages("message-reader", MESSAGES);
+    let mut reader = client.message_reader();
+    let mut chunk = [0; 1024];
+    let mut total = 0;
+    loop {
+        let bytes_read = reader.read(&mut chunk).unwrap();
+        if bytes_read == 0 {
+            break;
+        }
+        assert!(chunk[..bytes_read].iter().all(|&b| b == 7));
+        total += bytes_read;
+    }
+    assert_eq!(total, BIG.len());
+    assert!(reader.is_message_finished());
+    // Stays at end of file until explicitly advanced.
+    assert_eq!(reader.read(&mut chunk).unwrap(), 0);
+
+    reader.next_message().unwrap();
+    let mut start = [0; 4];
+    reader.read_exact(&mut start).unwrap();
+    assert_eq!(&start, b"skip");
+    // The rest of the message is discarded.
+    reader.next_message().unwrap();
+
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).unwrap();
+    assert!(buf.is_empty());
+    reader.next_message().unwrap();
+    reader.read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, b"last");
+    server.join().unwrap();
+}
+
+#[test]
+fn recv_msgs_upto() {
+    static BIG: [u8; 300] = [7; 300];
+    static MESSAGES: &[&[u8]] = &[b"one", &BIG, b"three", b"four", b"five"];
+    let (mut client, server) = send_messages("recv-msgs-upto", MESSAGES);
+    let mut pool = Vec::new();
+    assert_eq!(client.recv_msgs_upto(3, &mut pool).unwrap(), 3);
+    assert_eq!(pool.len(), 3);
+    assert_eq!(pool[0], b"one");
+    assert_eq!(pool[1], &BIG[..]);
+    assert_eq!(pool[2], b"three");
+    // Only two are left, so this returns early instead of waiting for more.
+    assert_eq!(client.recv_msgs_upto(10, &mut pool).unwrap(), 2);
+    assert_eq!(pool[3..], [&b"four"[..], &b"five"[..]]);
+    server.join().unwrap();
+}
+
+#[test]
+fn grow_strategy() {
+    static BIG: [u8; 300] = [7; 300];
+    static MESSAGES: &[&[u8]] = &[b"exact", &BIG, b"small", b"tiny"];
+    let (mut client, server) = send_messages("grow-strategy", MESSAGES);
+    assert_eq!(client.grow_strategy(), GrowStrategy::ExactFit);
+    let mut pool = Vec::new();
+    assert_eq!(client.recv_msgs_upto(2, &mut pool).unwrap(), 2);
+    assert_eq!(pool[0], b"exact");
+    assert_eq!(pool[0].capacity(), 5);
+    assert_eq!(pool[1], &BIG[..]);
+    assert_eq!(pool[1].capacity(), BIG.len());
+
+    client.set_grow_strategy(GrowStrategy::Doubling);
+    assert_eq!(client.recv_msgs_upto(2, &mut pool).unwrap(), 2);
+    assert_eq!(pool[2..], [&b"small"[..], &b"tiny"[..]]);
+    // Both were received into buffers of the initial size, regardless of how
+    // small they are.
+    assert!(pool[2].capacity() >= 1024);
+    assert!(pool[3].capacity() >= 1024);
+    server.join().unwrap();
+}
+
+#[test]
+fn server_instance_info() {
+    let name = format!("interprocess-test-instance-info-{}", process::id());
+    let listener = PipeListenerOptions::new()
+        .name(OsStr::new(&name))
+        .mode(PipeMode::Messages)
+        .instance_limit(NonZeroU8::new(3))
+        .create::<DuplexMsgPipeStream>()
+        .expect("failed to create listener");
+    let client = thread::spawn(move || {
+        let conn = DuplexMsgPipeStream::connect(&name).expect("failed to connect");
+        let (current, max) = conn.server_instance_info().unwrap();
+        assert!(current >= 1);
+        assert_eq!(max, 3);
+    });
+    let _conn = listener.accept().expect("failed to accept connection");
+    client.join().unwrap();
+}
+
+#[test]
+fn incompatible_pipe_name_taken() {
+    let name = format!("interprocess-test-name-taken-{}", process::id());
+    let _bytes = PipeListenerOptions::new()
+        .name(OsStr::new(&name))
+        .create::<DuplexBytePipeStream>()
+        .expect("failed to create listener");
+    let error = PipeListenerOptions::new()
+        .name(OsStr::new(&name))
+        .mode(PipeMode::Messages)
+        .create::<DuplexMsgPipeStream>()
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+    let error = error.get_ref().unwrap().downcast_ref::<PipeNameTakenError>().unwrap();
+    assert_eq!(error.name(), OsStr::new(&name));
+    assert_eq!(error.os_error().kind(), io::ErrorKind::PermissionDenied);
+}
+
+#[test]
+fn pending_connect_event() {
+    use winapi::{
+        shared::winerror::WAIT_TIMEOUT,
+        um::{synchapi::WaitForSingleObject, winbase::WAIT_OBJECT_0},
+    };
+
+    let name = format!("interprocess-test-connect-event-{}", process::id());
+    let listener = PipeListenerOptions::new()
+        .name(OsStr::new(&name))
+        .mode(PipeMode::Messages)
+        .create::<DuplexMsgPipeStream>()
+        .expect("failed to create listener");
+    let event = listener.pending_connect_event().unwrap();
+    // Asking again doesn't arm another instance.
+    assert_eq!(listener.pending_connect_event().unwrap(), event);
+    assert_eq!(unsafe { WaitForSingleObject(event, 0) }, WAIT_TIMEOUT);
+
+    let client = thread::spawn(move || {
+        let mut conn = DuplexMsgPipeStream::connect(&name).expect("failed to connect");
+        conn.write_all(b"hi").expect("failed to send message");
+    });
+    assert_eq!(unsafe { WaitForSingleObject(event, 5000) }, WAIT_OBJECT_0);
+    let mut conn = listener.accept().expect("failed to accept connection");
+    // Picking up the client resets the event.
+    assert_eq!(unsafe { WaitForSingleObject(event, 0) }, WAIT_TIMEOUT);
+    let mut buf = [0; 2];
+    assert_eq!(
+        conn.read_msg_part(&mut buf).unwrap(),
+        ReadMsgOutcome::Complete(2)
+    );
+    client.join().unwrap();
+    // Leaves an armed instance behind for the listener to clean up.
+    listener.pending_connect_event().unwrap();
+}
+
+#[test]
+fn flush_to_peer() {
+    use super::tokio::{DuplexBytePipeStream as TokioPipeStream, PipeListenerOptionsExt};
+    use futures::io::{AsyncReadExt, AsyncWriteExt};
+    use std::time::Duration;
+
+    let name = format!("interprocess-test-flush-to-peer-{}", process::id());
+    let runtime = ::tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        let listener = PipeListenerOptions::new()
+            .name(OsStr::new(&name))
+            .create_tokio::<TokioPipeStream>()
+            .unwrap();
+        let mut client = TokioPipeStream::connect(&name).unwrap();
+        let mut server = listener.accept().await.unwrap();
+        server.write_all(b"drain me").await.unwrap();
+
+        // Nobody is reading, so this only ends by being dropped, which cancels it.
+        let timed_out = ::tokio::time::timeout(Duration::from_millis(100), server.flush_to_peer());
+        assert!(timed_out.await.is_err());
+
+        let read = async {
+            ::tokio::time::sleep(Duration::from_millis(50)).await;
+            let mut buf = [0; 8];
+            client.read_exact(&mut buf).await.map(|()| buf)
+        };
+        let (flushed, read) = futures::join!(server.flush_to_peer(), read);
+        flushed.unwrap();
+        assert_eq!(&read.unwrap(), b"drain me");
+    });
+}
@@ -0,0 +1,139 @@
+use super::{imports::*, PipeOps};
+use crate::os::windows::FileHandleOps;
+use std::{
+    fmt::{self, Debug, Formatter},
+    future::Future,
+    io,
+    mem::ManuallyDrop,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Arc,
+        Mutex,
+    },
+    task::{Context, Poll},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+/// How long the thread which cancels an abandoned flush waits between attempts
+/// to interrupt the thread performing it, in case the latter hasn't entered the
+/// system call yet.
+const CANCEL_RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Future returned by the `flush_to_peer` method on Tokio pipe streams, which
+/// completes once the other end has read everything written to the pipe so
+/// far, rather than just once the system has accepted it.
+///
+/// `FlushFileBuffers` cannot be performed as overlapped I/O, so the flush is
+/// performed by a thread of its own, which completes the future once the call
+/// returns; the runtime's threads are never blocked by it. The thread flushes
+/// a duplicate of the pipe's handle, so the future doesn't borrow the stream,
+/// and the handle stays valid for as long as the thread needs it even if the
+/// future is leaked.
+///
+/// **The flush never completes if the other end never reads**, so it's best
+/// combined with a timeout. Dropping the future before it completes cancels
+/// the flush by interrupting its thread with `CancelSynchronousIo`; since the
+/// thread might not have entered the system call yet, this is retried by
+/// another thread until it has left it, so dropping never blocks.
+pub struct FlushToPeer {
+    /// `None` once the result has been returned.
+    flushing: Option<io::Result<Flushing>>,
+}
+struct Flushing {
+    thread: JoinHandle<()>,
+    done:   Arc<AtomicBool>,
+    result: oneshot::Receiver<io::Result<()>>,
+}
+impl Flushing {
+    /// Interrupts the thread until it has left `FlushFileBuffers`, and then
+    /// waits for it to exit.
+    fn cancel(self) {
+        while !self.done.load(SeqCst) {
+            unsafe { CancelSynchronousIo(self.thread.as_raw_handle()) };
+            thread::sleep(CANCEL_RETRY_INTERVAL);
+        }
+        let _ = self.thread.join();
+    }
+}
+impl FlushToPeer {
+    pub(super) fn new(pipe: &PipeOps) -> Self {
+        // Only a view of the stream's handle, which must not close it.
+        let view = ManuallyDrop::new(FileHandleOps(pipe.as_raw_handle()));
+        let flushing = view.duplicate(false).and_then(|handle| {
+            let (sender, result) = oneshot::channel();
+            let done = Arc::new(AtomicBool::new(false));
+            let thread = {
+                let done = Arc::clone(&done);
+                thread::Builder::new().spawn(move || {
+                    let result = handle.flush();
+                    done.store(true, SeqCst);
+                    let _ = sender.send(result);
+                })?
+            };
+            Ok(Flushing {
+                thread,
+                done,
+                result,
+            })
+        });
+        Self {
+            flushing: Some(flushing),
+        }
+    }
+}
+impl Future for FlushToPeer {
+    type Output = io::Result<()>;
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Self::Output> {
+        let flushing = match self.flushing.as_mut() {
+            Some(Ok(flushing)) => flushing,
+            Some(Err(..)) => {
+                // Duplicating the handle or spawning the thread failed.
+                return Poll::Ready(Err(self.flushing.take().unwrap().err().unwrap()));
+            },
+            None => panic!("`FlushToPeer` polled after completion"),
+        };
+        let result = futures::ready!(Pin::new(&mut flushing.result).poll(ctx));
+        // The thread exits right after sending the result, so it's left to do
+        // that on its own.
+        self.flushing = None;
+        Poll::Ready(result.unwrap_or_else(|_| {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the thread performing the flush panicked",
+            ))
+        }))
+    }
+}
+impl Drop for FlushToPeer {
+    fn drop(&mut self) {
+        if let Some(Ok(flushing)) = self.flushing.take() {
+            if flushing.done.load(SeqCst) {
+                return;
+            }
+            // The cancellation is handed off to a thread of its own, so as not to
+            // block the runtime. If that thread can't be spawned, leaving the
+            // flush running would keep the pipe open through its handle until
+            // the other end reads, so this falls back to cancelling in place.
+            let flushing = Arc::new(Mutex::new(Some(flushing)));
+            let spawned = {
+                let flushing = Arc::clone(&flushing);
+                thread::Builder::new().spawn(move || take(&flushing).cancel())
+            };
+            if spawned.is_err() {
+                take(&flushing).cancel();
+            }
+        }
+    }
+}
+/// Takes the flush out of the slot shared with the cancellation thread.
+fn take(slot: &Mutex<Option<Flushing>>) -> Flushing {
+    slot.lock().unwrap_or_else(|e| e.into_inner()).take().unwrap()
+}
+impl Debug for FlushToPeer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlushToPeer").field("finished", &self.flushing.is_none()).finish()
+    }
+}
@@ -1,10 +1,19 @@
 use super::{
-    super::{Instancer, PipeListenerOptions, INITIAL_INSTANCER_CAPACITY},
+    super::{
+        is_pipe_busy,
+        meets_impersonation_level,
+        Instancer,
+        PipeListenerOptions,
+        INITIAL_INSTANCER_CAPACITY,
+        OVERFLOW_POLL_INTERVAL,
+    },
     enums::{PipeMode, PipeStreamRole},
+    imports::*,
     PipeOps,
     TokioPipeStream,
 };
-use crate::Sealed;
+use crate::{OverflowPolicy, Sealed};
+use futures::future::{self, Either};
 use std::{
     fmt::{self, Debug, Formatter},
     io,
@@ -13,6 +22,7 @@ use std::{
     sync::{atomic::AtomicBool, Arc, RwLock},
 };
 use to_method::To;
+use tokio::sync::mpsc;
 
 /// A Tokio-based async server for a named pipe, asynchronously listening for
 /// connections to clients and producing asynchronous pipe streams.
@@ -21,41 +31,133 @@ use to_method::To;
 /// See its documentation for more.
 pub struct PipeListener<Stream: TokioPipeStream> {
     config:    PipeListenerOptions<'static>, // We need the options to create new instances
+    // Encoded once, so that creating instances doesn't allocate
+    path:      Vec<u16>,
     instancer: Instancer<PipeOps>,
     _phantom:  PhantomData<fn() -> Stream>,
 }
 impl<Stream: TokioPipeStream> PipeListener<Stream> {
     /// Asynchronously waits until a client connects to the named pipe, creating
     /// a `Stream` to communicate with the pipe.
+    ///
+    /// At the instance limit, the overflow policy is applied just like with the
+    /// [non-async `accept`](super::super::PipeListener::accept), and so is the
+    /// minimum impersonation level for the clients which connect.
     pub async fn accept(&self) -> io::Result<Stream> {
-        let instance = if let Some(instance) = self.instancer.allocate() {
-            instance
-        } else {
-            self.instancer.add_instance(self.create_instance()?)
-        };
-        instance.0.connect_server().await?;
-        // I have no idea why, but every time I run a minimal named pipe server example
-        // without this code, the second client to connect causes a "no process
-        // on the other end of the pipe" error, and for some reason, performing
-        // a read or write with a zero-sized buffer and discarding its result
-        // fixes this problem entirely. I'm not sure if it's a crazy bug of
-        // interprocess, Tokio or even Windows, but this is the best solution I've
-        // come up for.
-        if Stream::READ_MODE.is_some() {
-            instance.0.dry_read().await;
-        } else {
-            instance.0.dry_write().await;
+        loop {
+            let instance = self.allocate_instance().await?;
+            instance.0.connect_server().await?;
+            // I have no idea why, but every time I run a minimal named pipe server example
+            // without this code, the second client to connect causes a "no process
+            // on the other end of the pipe" error, and for some reason, performing
+            // a read or write with a zero-sized buffer and discarding its result
+            // fixes this problem entirely. I'm not sure if it's a crazy bug of
+            // interprocess, Tokio or even Windows, but this is the best solution I've
+            // come up for.
+            if Stream::READ_MODE.is_some() {
+                instance.0.dry_read().await;
+            } else {
+                instance.0.dry_write().await;
+            }
+            let handle = instance.0.as_raw_handle();
+            let stream = Stream::build(instance);
+            if meets_impersonation_level(handle, self.config.min_impersonation_level) {
+                return Ok(stream);
+            }
+            // Dropping the stream disconnects the client and frees up the
+            // instance.
+        }
+    }
+    /// Like [`accept`](Self::accept), but only returns clients for which
+    /// `verify` returns `Ok(true)`, just like the [non-async
+    /// `accept_authenticated`](super::super::PipeListener::accept_authenticated).
+    pub async fn accept_authenticated(
+        &self,
+        mut verify: impl FnMut(&Stream) -> io::Result<bool>,
+    ) -> io::Result<Stream> {
+        loop {
+            let stream = self.accept().await?;
+            if verify(&stream)? {
+                return Ok(stream);
+            }
         }
-        Ok(Stream::build(instance))
+    }
+    async fn allocate_instance(&self) -> io::Result<Arc<(PipeOps, AtomicBool)>> {
+        let instance = loop {
+            if let Some(instance) = self.instancer.allocate() {
+                break instance;
+            }
+            match self.create_instance() {
+                Ok(instance) => break self.instancer.add_instance(instance),
+                Err(e)
+                    if is_pipe_busy(&e) && self.config.overflow_policy == OverflowPolicy::Queue =>
+                {
+                    tokio::time::sleep(OVERFLOW_POLL_INTERVAL).await;
+                },
+                Err(e) => return Err(e),
+            }
+        };
+        Ok(instance)
     }
 
     fn create_instance(&self) -> io::Result<PipeOps> {
-        let handle =
-            self.config.create_instance(false, false, true, Stream::ROLE, Stream::READ_MODE)?;
+        let handle = self.config.create_instance(
+            &self.path,
+            false,
+            false,
+            true,
+            Stream::ROLE,
+            Stream::READ_MODE,
+        )?;
         // SAFETY: we just created this handle
         Ok(unsafe { PipeOps::from_raw_handle(handle, true)? })
     }
 }
+
+/// Spawns a Tokio task which accepts connections from the listener and passes
+/// the accepted streams, as well as the errors from accepting, to the returned
+/// receiver through a channel which holds up to `capacity` of them.
+///
+/// This is the Tokio counterpart of the [`spawn_acceptor`] function for local
+/// sockets, with the same backpressure: once the channel is full, the task
+/// waits for a stream to be received before accepting the next client, which
+/// waits for a free pipe instance in the meantime. A `capacity` of zero makes
+/// every accepted stream wait until it's received, like with the non-async
+/// version; since Tokio channels cannot be empty-sized, this is the same as a
+/// capacity of one.
+///
+/// Dropping the receiver makes the task stop right away, even if it's waiting
+/// for a client at that moment, and drops the listener.
+///
+/// # Panics
+/// If called outside of a Tokio runtime.
+///
+/// [`spawn_acceptor`]: ../../../../local_socket/fn.spawn_acceptor.html " "
+pub fn spawn_acceptor<Stream>(
+    listener: PipeListener<Stream>,
+    capacity: usize,
+) -> mpsc::Receiver<io::Result<Stream>>
+where
+    Stream: TokioPipeStream + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(capacity.max(1));
+    tokio::spawn(async move {
+        loop {
+            // Waiting for room before accepting is what provides the backpressure.
+            let permit = match sender.reserve().await {
+                Ok(permit) => permit,
+                Err(..) => return,
+            };
+            let accept = Box::pin(listener.accept());
+            let closed = Box::pin(sender.closed());
+            match future::select(accept, closed).await {
+                Either::Left((accepted, _)) => permit.send(accepted),
+                Either::Right(..) => return,
+            }
+        }
+    });
+    receiver
+}
 impl<Stream: TokioPipeStream> Debug for PipeListener<Stream> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("PipeListener")
@@ -77,9 +179,10 @@ pub trait PipeListenerOptionsExt: Sealed {
 }
 impl PipeListenerOptionsExt for PipeListenerOptions<'_> {
     fn create_tokio<Stream: TokioPipeStream>(&self) -> io::Result<PipeListener<Stream>> {
-        let (owned_config, instancer) = _create_tokio(self, Stream::ROLE, Stream::READ_MODE)?;
+        let (owned_config, path, instancer) = _create_tokio(self, Stream::ROLE, Stream::READ_MODE)?;
         Ok(PipeListener {
             config: owned_config,
+            path,
             instancer,
             _phantom: PhantomData,
         })
@@ -89,21 +192,30 @@ fn _create_tokio(
     config: &PipeListenerOptions<'_>,
     role: PipeStreamRole,
     read_mode: Option<PipeMode>,
-) -> io::Result<(PipeListenerOptions<'static>, Instancer<PipeOps>)> {
+) -> io::Result<(PipeListenerOptions<'static>, Vec<u16>, Instancer<PipeOps>)> {
     let mut owned_config = config.to_owned();
     owned_config.nonblocking = false;
+    let path = config.path();
     let instancer_capacity =
         config.instance_limit.map_or(INITIAL_INSTANCER_CAPACITY, NonZeroU8::get).to::<usize>();
     let mut instance_vec = Vec::with_capacity(instancer_capacity);
-    let first_instance_raw = config.create_instance(true, false, true, role, read_mode)?;
+    let first_instance_raw = config.create_instance(&path, true, false, true, role, read_mode)?;
     let first_instance = Arc::new((
         // SAFETY: we just created this handle
         unsafe { PipeOps::from_raw_handle(first_instance_raw, true)? },
         AtomicBool::new(false),
     ));
     instance_vec.push(first_instance);
+    for _ in 1..config.upfront_instances() {
+        let instance_raw = config.create_instance(&path, false, false, true, role, read_mode)?;
+        instance_vec.push(Arc::new((
+            // SAFETY: as above
+            unsafe { PipeOps::from_raw_handle(instance_raw, true)? },
+            AtomicBool::new(false),
+        )));
+    }
     let instancer = Instancer(RwLock::new(instance_vec));
-    Ok((owned_config, instancer))
+    Ok((owned_config, path, instancer))
 }
 impl Sealed for PipeListenerOptions<'_> {
 }
@@ -10,10 +10,12 @@
 //! they're called outside of a Tokio runtime context. Open an issue if you'd
 //! like to see other runtimes supported as well.
 
+mod flush_to_peer;
 mod listener;
 mod pipeops;
 mod stream;
 
+pub use flush_to_peer::*;
 pub use listener::*;
 pub use stream::*;
 
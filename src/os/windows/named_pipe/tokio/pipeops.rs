@@ -1,27 +1,37 @@
-#![allow(clippy::unnecessary_mut_passed)] // We get &mut with mutexes either way
-
-use super::imports::*;
+use super::{
+    super::{
+        handle_state::{get_handle_state, HandleState},
+        pipeops::PipeOps as SyncPipeOps,
+    },
+    imports::*,
+};
 use std::{
     future::Future,
     io,
+    mem::ManuallyDrop,
     pin::Pin,
-    sync::{atomic::AtomicBool, Arc, Mutex},
+    sync::{atomic::AtomicBool, Arc},
     task::{Context, Poll},
 };
-use to_method::To;
-
-static LPE: &str = "unexpected lock poisoning";
 
-macro_rules! l {
-    ($e:expr) => {
-        &mut *$e.lock().expect(LPE)
+/// Runs the expression with `$pipe` bound to the Tokio client or server,
+/// whichever the `PipeOps` holds, since both have the same methods.
+macro_rules! with_pipe {
+    ($ops:expr, $pipe:ident => $e:expr) => {
+        match $ops {
+            PipeOps::Client($pipe) => $e,
+            PipeOps::Server($pipe) => $e,
+        }
     };
 }
 
+/// Everything is done through shared references, so that the reading and the
+/// writing half of a split stream can use the pipe at the same time, and so
+/// that a server can be waited on to connect without anything being locked
+/// across the await.
 pub enum PipeOps {
-    // The mutexes can be replaced with UnsafeCell but I don't really feel like it.
-    Client(Mutex<TokioNPClient>),
-    Server(Mutex<TokioNPServer>),
+    Client(TokioNPClient),
+    Server(TokioNPServer),
 }
 impl PipeOps {
     /// Creates a `PipeOps` from a raw Windows API handle. The `server` argument
@@ -33,9 +43,9 @@ impl PipeOps {
     pub unsafe fn from_raw_handle(handle: HANDLE, server: bool) -> io::Result<Self> {
         // SAFETY: as per safety contract
         let val = if server {
-            Self::Server(unsafe { TokioNPServer::from_raw_handle(handle)? }.to::<Mutex<_>>())
+            Self::Server(unsafe { TokioNPServer::from_raw_handle(handle)? })
         } else {
-            Self::Client(unsafe { TokioNPClient::from_raw_handle(handle)? }.to::<Mutex<_>>())
+            Self::Client(unsafe { TokioNPClient::from_raw_handle(handle)? })
         };
         Ok(val)
     }
@@ -50,10 +60,18 @@ impl PipeOps {
         ctx: &mut Context<'_>,
         buf: &mut TokioReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
-        match self {
-            PipeOps::Client(c) => TokioAsyncRead::poll_read(Pin::new(l!(c)), ctx, buf),
-            PipeOps::Server(s) => TokioAsyncRead::poll_read(Pin::new(l!(s)), ctx, buf),
-        }
+        with_pipe!(self, pipe => loop {
+            futures::ready!(pipe.poll_read_ready(ctx))?;
+            match pipe.try_read(buf.initialize_unfilled()) {
+                Ok(bytes_read) => {
+                    buf.advance(bytes_read);
+                    return Poll::Ready(Ok(()));
+                },
+                // The readiness was stale, so this waits for the next one.
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {},
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        })
     }
     pub fn poll_read(&self, ctx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
         let mut buf = TokioReadBuf::new(buf);
@@ -61,22 +79,21 @@ impl PipeOps {
         Poll::Ready(Ok(buf.filled().len()))
     }
     pub fn poll_write(&self, ctx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
-        match self {
-            PipeOps::Client(c) => TokioAsyncWrite::poll_write(Pin::new(l!(c)), ctx, buf),
-            PipeOps::Server(s) => TokioAsyncWrite::poll_write(Pin::new(l!(s)), ctx, buf),
-        }
-    }
-    pub fn poll_flush(&self, ctx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        match self {
-            PipeOps::Client(c) => TokioAsyncWrite::poll_flush(Pin::new(l!(c)), ctx),
-            PipeOps::Server(s) => TokioAsyncWrite::poll_flush(Pin::new(l!(s)), ctx),
-        }
-    }
-    pub fn poll_shutdown(&self, ctx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        match self {
-            PipeOps::Client(c) => TokioAsyncWrite::poll_shutdown(Pin::new(l!(c)), ctx),
-            PipeOps::Server(s) => TokioAsyncWrite::poll_shutdown(Pin::new(l!(s)), ctx),
-        }
+        with_pipe!(self, pipe => loop {
+            futures::ready!(pipe.poll_write_ready(ctx))?;
+            match pipe.try_write(buf) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {},
+                result => return Poll::Ready(result),
+            }
+        })
+    }
+    // Writes go straight to the pipe, so there is nothing to flush or shut
+    // down, just like with Tokio's own AsyncWrite implementations.
+    pub fn poll_flush(&self, _ctx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+    pub fn poll_shutdown(&self, _ctx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
     }
     pub fn get_client_process_id(&self) -> io::Result<u32> {
         let mut id: u32 = 0;
@@ -118,10 +135,32 @@ impl PipeOps {
             Err(io::Error::last_os_error())
         }
     }
+    /// Copies the data waiting in the pipe into the buffer without consuming
+    /// it. See the non-async `PipeOps::peek`.
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.with_sync(|ops| ops.peek(buf))
+    }
+    /// Returns `false` if the other end has disconnected and there is no data
+    /// left in the pipe. See the non-async `PipeOps::is_connected`.
+    pub fn is_connected(&self) -> io::Result<bool> {
+        self.with_sync(SyncPipeOps::is_connected)
+    }
+    /// Runs `f` on the handle viewed as the non-async `PipeOps`, for the
+    /// operations which don't wait and thus don't need to go through Tokio.
+    fn with_sync<T>(&self, f: impl FnOnce(&SyncPipeOps) -> T) -> T {
+        // SAFETY: the handle stays open for as long as self is borrowed, and
+        // ManuallyDrop keeps the view from closing it.
+        let ops = ManuallyDrop::new(unsafe { SyncPipeOps::from_raw_handle(self.as_raw_handle()) });
+        f(&ops)
+    }
+    pub fn get_handle_state(&self, is_server: bool) -> io::Result<HandleState> {
+        debug_assert_eq!(is_server, self.is_server());
+        unsafe { get_handle_state(self.as_raw_handle(), is_server) }
+    }
     pub async fn connect_server(&self) -> io::Result<()> {
         match self {
             PipeOps::Client(_) => unimplemented!("connect_server() called on client PipeOps"),
-            PipeOps::Server(s) => l!(s).connect().await,
+            PipeOps::Server(s) => s.connect().await,
         }
     }
     pub fn disconnect(&self) -> io::Result<()> {
@@ -131,7 +170,7 @@ impl PipeOps {
                     "named pipes on the client side cannot be disconnected without flushing"
                 )
             },
-            PipeOps::Server(s) => l!(s).disconnect(),
+            PipeOps::Server(s) => s.disconnect(),
         }
     }
     pub fn server_drop_disconnect(&self) {
@@ -168,10 +207,7 @@ impl Future for DryWrite<'_> {
 #[cfg(windows)]
 impl AsRawHandle for PipeOps {
     fn as_raw_handle(&self) -> HANDLE {
-        match self {
-            PipeOps::Client(c) => l!(c).as_raw_handle(),
-            PipeOps::Server(s) => l!(s).as_raw_handle(),
-        }
+        with_pipe!(self, pipe => pipe.as_raw_handle())
     }
 }
 
@@ -1,10 +1,13 @@
 use super::{
-    super::convert_path,
+    super::{convert_path, HandleState},
     enums::{PipeMode, PipeStreamRole},
     imports::*,
+    FlushToPeer,
     PipeOps,
     PipeStreamInternals,
 };
+#[cfg(feature = "tokio_support")]
+use crate::{unsupported, AsyncConnection};
 use std::{
     ffi::{OsStr, OsString},
     fmt::{self, Debug, Formatter},
@@ -15,11 +18,9 @@ use std::{
     sync::{
         atomic::{AtomicBool, Ordering::Release},
         Arc,
-        Mutex,
     },
     task::{Context, Poll},
 };
-use to_method::To;
 
 /// Defines the properties of Tokio pipe stream types.
 ///
@@ -65,6 +66,7 @@ macro_rules! create_stream_type {
     ) => {
         create_stream_type_base!(
             $ty:
+            extra_fields: {},
             extra_methods: {
                 /// Tries to connect to the specified named pipe (the `\\.\pipe\` prefix is added automatically), returning a named pipe stream of the stream type provided via generic parameters. If there is no available server, returns immediately.
                 pub fn connect(name: impl AsRef<OsStr>) -> io::Result<Self> {
@@ -155,6 +157,10 @@ macro_rules! create_duplex_stream_type {
                 read_mode: $corresponding_reader::READ_MODE,
                 write_mode: $corresponding_writer::WRITE_MODE,
                 extra_methods: {
+                    /// Waits until the other end has read everything written to the pipe so far. See [`FlushToPeer`](super::FlushToPeer) for how, and for why it's best combined with a timeout.
+                    pub fn flush_to_peer(&self) -> FlushToPeer {
+                        FlushToPeer::new(&self.instance.0)
+                    }
                     /// Splits the duplex stream into its reading and writing half. Contended concurrent operations may experience insignificant slowdowns due to necessary synchronization, which is an implementation detail.
                     pub fn split(self) -> ($corresponding_reader, $corresponding_writer) {
                         let self_ = ManuallyDrop::new(self);
@@ -206,7 +212,12 @@ Created either by using `PipeListener` or by connecting to a named pipe server.
         role: PipeStreamRole::Writer,
         read_mode: None,
         write_mode: Some(PipeMode::Bytes),
-        extra_methods: {},
+        extra_methods: {
+            /// Waits until the other end has read everything written to the pipe so far. See [`FlushToPeer`](super::FlushToPeer) for how, and for why it's best combined with a timeout.
+            pub fn flush_to_peer(&self) -> FlushToPeer {
+                FlushToPeer::new(&self.instance.0)
+            }
+        },
         doc: "
 [Byte stream writer] for a Tokio-based named pipe.
 
@@ -232,7 +243,12 @@ Created either by using `PipeListener` or by connecting to a named pipe server.
         role: PipeStreamRole::Writer,
         read_mode: None,
         write_mode: Some(PipeMode::Messages),
-        extra_methods: {},
+        extra_methods: {
+            /// Waits until the other end has read everything written to the pipe so far. See [`FlushToPeer`](super::FlushToPeer) for how, and for why it's best combined with a timeout.
+            pub fn flush_to_peer(&self) -> FlushToPeer {
+                FlushToPeer::new(&self.instance.0)
+            }
+        },
         doc: "
 [Message stream writer] for a Tokio-based named pipe.
 
@@ -480,6 +496,34 @@ impl AsyncWrite for &DuplexMsgPipeStream {
     }
 }
 
+macro_rules! impl_async_connection {
+    ($ty:ident) => {
+        #[cfg(feature = "tokio_support")]
+        impl AsyncConnection for $ty {
+            fn peer_process_id(&self) -> io::Result<u32> {
+                if self.is_server() {
+                    self.instance.0.get_client_process_id()
+                } else {
+                    self.instance.0.get_server_process_id()
+                }
+            }
+            fn shutdown(&self) -> io::Result<()> {
+                Err(unsupported(
+                    "named pipes cannot be shut down without closing them",
+                ))
+            }
+            fn is_connected(&self) -> io::Result<bool> {
+                self.instance.0.is_connected()
+            }
+            fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+                self.instance.0.peek(buf)
+            }
+        }
+    };
+}
+impl_async_connection!(DuplexBytePipeStream);
+impl_async_connection!(DuplexMsgPipeStream);
+
 fn _connect(
     pipe_name: &OsStr,
     hostname: Option<&OsStr>,
@@ -490,6 +534,6 @@ fn _connect(
     let name = OsString::from_wide(&name[..]);
     let name_ref: &OsStr = name.as_ref();
     let tnpclient = TokioNPClientOptions::new().read(read).write(write).open(name_ref)?;
-    let pipeops = PipeOps::Client(tnpclient.to::<Mutex<_>>());
+    let pipeops = PipeOps::Client(tnpclient);
     Ok(pipeops)
 }
@@ -0,0 +1,551 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{self, Debug, Formatter},
+    io::{self, prelude::*},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+};
+
+/// Multiplexes any amount of virtual connections over a single byte stream,
+/// such as a TCP connection, allowing pipe traffic to be tunneled between
+/// computers.
+///
+/// Every piece of data written into a virtual connection, a [`MuxStream`], is
+/// sent over the transport as a frame with a small header, which specifies the
+/// connection it belongs to and its length. On the other end, another
+/// `PipeMuxer` reads the frames and hands their contents out to the virtual
+/// connections they belong to. Either end can open connections: [`connect`]
+/// opens one, and the muxer on the other end produces it from [`accept`].
+///
+/// There is no background thread: frames are read from the transport by
+/// whichever thread needs data, be it one reading from a virtual connection or
+/// one accepting, and data for other connections is buffered until they're
+/// read from. The buffers are unbounded, so a connection which is never read
+/// from while the peer keeps writing into it keeps consuming memory. Dropping a
+/// `MuxStream` closes the connection, after which the other end reads end of
+/// file.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use interprocess::PipeMuxer;
+/// use std::{io::prelude::*, net::TcpStream};
+///
+/// let tcp = TcpStream::connect("192.0.2.1:5000")?;
+/// let muxer = PipeMuxer::new(tcp.try_clone()?, tcp);
+/// let mut control = muxer.connect()?;
+/// let mut bulk = muxer.connect()?;
+/// control.write_all(b"hello")?;
+/// bulk.write_all(&[0; 100_000])?;
+/// let mut reply = [0; 5];
+/// control.read_exact(&mut reply)?;
+/// # Ok(()) }
+/// ```
+///
+/// # Wire format
+/// Each frame starts with a 9-byte header: the identifier of the connection as
+/// a little-endian 32-bit integer, a byte of flags and the length of the data
+/// following the header as a little-endian 32-bit integer, which never exceeds
+/// 64 KiB. The identifiers are assigned by the end which opened the
+/// connection, which is specified by the lowest bit of the flags, so that both
+/// ends can open connections without coordinating; the next bit marks the last
+/// frame of a connection from the sending end, which carries no data.
+///
+/// [`MuxStream`]: struct.MuxStream.html " "
+/// [`connect`]: #method.connect " "
+/// [`accept`]: #method.accept " "
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "pipe_muxer")))]
+pub struct PipeMuxer<T: Read + Write> {
+    shared: Arc<Shared<T>>,
+}
+impl<T: Read + Write> PipeMuxer<T> {
+    /// Creates a muxer which uses the specified transport.
+    ///
+    /// The transport is taken as two handles to the same byte stream, one of
+    /// which is only read from and the other only written to, so that reading
+    /// frames for one connection never holds up writes into another. For
+    /// `TcpStream`, those can be made with `try_clone`.
+    pub fn new(reader: T, writer: T) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                reader:   Mutex::new(reader),
+                writer:   Mutex::new(writer),
+                state:    Mutex::new(State::default()),
+                readable: Condvar::new(),
+            }),
+        }
+    }
+    /// Opens a new virtual connection to the other end of the transport.
+    ///
+    /// The other end is notified immediately, without waiting for any data to
+    /// be written into the connection.
+    pub fn connect(&self) -> io::Result<MuxStream<T>> {
+        let id = {
+            let mut state = self.shared.lock_state();
+            let id = state.allocate_id()?;
+            state.connections.insert(
+                Key {
+                    opened_by_peer: false,
+                    id,
+                },
+                Connection::default(),
+            );
+            id
+        };
+        let stream = MuxStream {
+            shared: Arc::clone(&self.shared),
+            key:    Key {
+                opened_by_peer: false,
+                id,
+            },
+        };
+        // An empty frame for an unknown connection is what opens it.
+        stream.send_frame(0, &[])?;
+        Ok(stream)
+    }
+    /// Waits for the other end of the transport to open a virtual connection,
+    /// returning it once it does.
+    ///
+    /// If the transport is closed, an error of kind [`ConnectionAborted`] is
+    /// returned.
+    ///
+    /// [`ConnectionAborted`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.ConnectionAborted " "
+    pub fn accept(&self) -> io::Result<MuxStream<T>> {
+        let key = self.shared.pump(|state| state.incoming.pop_front())?;
+        Ok(MuxStream {
+            shared: Arc::clone(&self.shared),
+            key,
+        })
+    }
+}
+impl<T: Read + Write> Debug for PipeMuxer<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let state = self.shared.lock_state();
+        f.debug_struct("PipeMuxer")
+            .field("connections", &state.connections.len())
+            .field("transport_closed", &state.transport_closed)
+            .finish()
+    }
+}
+
+/// A virtual connection multiplexed over a transport by a [`PipeMuxer`].
+///
+/// Created with the [`connect`] and [`accept`] methods on the muxer. Every
+/// `write` call sends at most 64 KiB of data as one frame, which makes
+/// connections take turns when several of them write big buffers at once.
+///
+/// [`PipeMuxer`]: struct.PipeMuxer.html " "
+/// [`connect`]: struct.PipeMuxer.html#method.connect " "
+/// [`accept`]: struct.PipeMuxer.html#method.accept " "
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "pipe_muxer")))]
+pub struct MuxStream<T: Read + Write> {
+    shared: Arc<Shared<T>>,
+    key:    Key,
+}
+impl<T: Read + Write> MuxStream<T> {
+    /// Returns the identifier of the connection, as assigned by the end which
+    /// opened it, and whether that was the other end or this one.
+    pub fn id(&self) -> (u32, bool) {
+        (self.key.id, self.key.opened_by_peer)
+    }
+    fn send_frame(&self, flags: u8, data: &[u8]) -> io::Result<()> {
+        let flags = if self.key.opened_by_peer {
+            flags
+        } else {
+            flags | FROM_OPENER
+        };
+        let mut header = [0; HEADER_LEN];
+        header[0..4].copy_from_slice(&self.key.id.to_le_bytes());
+        header[4] = flags;
+        header[5..9].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        let mut writer = self.shared.writer.lock().expect(LPE);
+        writer.write_all(&header)?;
+        writer.write_all(data)
+    }
+}
+impl<T: Read + Write> Read for MuxStream<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let key = self.key;
+        self.shared.pump(|state| {
+            let connection = state.connections.get_mut(&key)?;
+            if connection.buffer.is_empty() {
+                // The end of file is only reached once everything has been read.
+                return if connection.peer_closed {
+                    Some(0)
+                } else {
+                    None
+                };
+            }
+            let len = buf.len().min(connection.buffer.len());
+            for (dst, src) in buf.iter_mut().zip(connection.buffer.drain(..len)) {
+                *dst = src;
+            }
+            Some(len)
+        })
+    }
+}
+impl<T: Read + Write> Write for MuxStream<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let peer_closed = matches!(
+            self.shared.lock_state().connections.get(&self.key),
+            Some(c) if c.peer_closed
+        );
+        if peer_closed {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the connection was closed by the other end",
+            ));
+        }
+        let len = buf.len().min(MAX_FRAME_LEN);
+        self.send_frame(0, &buf[..len])?;
+        Ok(len)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.shared.writer.lock().expect(LPE).flush()
+    }
+}
+impl<T: Read + Write> Drop for MuxStream<T> {
+    fn drop(&mut self) {
+        let notify_peer = {
+            let mut state = self.shared.lock_state();
+            let transport_closed = state.transport_closed;
+            let connection = match state.connections.get_mut(&self.key) {
+                Some(c) => c,
+                None => return,
+            };
+            if connection.peer_closed {
+                state.connections.remove(&self.key);
+            } else {
+                // Kept around until the other end confirms, so that frames which it
+                // sent before learning about the closure are recognized as belonging
+                // to a closed connection rather than opening a new one.
+                connection.locally_closed = true;
+                connection.buffer = VecDeque::new();
+            }
+            // Sent even if the other end has closed the connection already, since
+            // that's the confirmation which it waits for to forget the connection.
+            !transport_closed
+        };
+        if notify_peer {
+            let _ = self.send_frame(CLOSE, &[]);
+        }
+    }
+}
+impl<T: Read + Write> Debug for MuxStream<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MuxStream")
+            .field("id", &self.key.id)
+            .field("opened_by_peer", &self.key.opened_by_peer)
+            .finish()
+    }
+}
+
+static LPE: &str = "unexpected lock poisoning";
+
+const HEADER_LEN: usize = 9;
+const MAX_FRAME_LEN: usize = 64 * 1024;
+/// Set on frames sent by the end which opened the connection.
+const FROM_OPENER: u8 = 0b01;
+/// Set on the last frame of a connection from the sending end.
+const CLOSE: u8 = 0b10;
+
+struct Shared<T> {
+    reader:   Mutex<T>,
+    writer:   Mutex<T>,
+    state:    Mutex<State>,
+    /// Signalled whenever a frame has been read or the transport has closed.
+    readable: Condvar,
+}
+impl<T: Read> Shared<T> {
+    fn lock_state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect(LPE)
+    }
+    /// Reads frames from the transport until `ready` returns a value, or waits
+    /// for another thread to do so if one already is.
+    fn pump<R>(&self, mut ready: impl FnMut(&mut State) -> Option<R>) -> io::Result<R> {
+        let mut state = self.lock_state();
+        loop {
+            if let Some(value) = ready(&mut state) {
+                return Ok(value);
+            }
+            if state.transport_closed {
+                return Err(io::Error::new(
+                    io::ErrorKind::ConnectionAborted,
+                    "the transport was closed",
+                ));
+            }
+            if state.reading {
+                state = self.readable.wait(state).expect(LPE);
+                continue;
+            }
+            state.reading = true;
+            drop(state);
+            let frame = read_frame(&mut *self.reader.lock().expect(LPE));
+            state = self.lock_state();
+            state.reading = false;
+            self.readable.notify_all();
+            match frame {
+                Ok(Some(frame)) => state.dispatch(frame),
+                Ok(None) => state.transport_closed = true,
+                Err(e) => {
+                    state.transport_closed = true;
+                    return Err(e);
+                },
+            }
+        }
+    }
+}
+
+/// Identifies a connection from the point of view of one end of the transport.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct Key {
+    opened_by_peer: bool,
+    id:             u32,
+}
+#[derive(Debug, Default)]
+struct Connection {
+    buffer:         VecDeque<u8>,
+    peer_closed:    bool,
+    locally_closed: bool,
+}
+#[derive(Debug, Default)]
+struct State {
+    connections:      HashMap<Key, Connection>,
+    /// Connections opened by the other end which haven't been accepted yet.
+    incoming:         VecDeque<Key>,
+    next_id:          u32,
+    /// Whether a thread is currently reading a frame from the transport.
+    reading:          bool,
+    transport_closed: bool,
+}
+impl State {
+    /// Picks the identifier for a new connection opened by this end. Once the
+    /// counter wraps around, the identifiers of connections which are still
+    /// open are skipped, so that they're never assigned twice.
+    fn allocate_id(&mut self) -> io::Result<u32> {
+        let start = self.next_id;
+        loop {
+            let id = self.next_id;
+            self.next_id = self.next_id.wrapping_add(1);
+            let key = Key {
+                opened_by_peer: false,
+                id,
+            };
+            if !self.connections.contains_key(&key) {
+                return Ok(id);
+            }
+            if self.next_id == start {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "all connection identifiers are in use",
+                ));
+            }
+        }
+    }
+    fn dispatch(&mut self, frame: Frame) {
+        let key = Key {
+            // Frames from the opener come from the other end, so the other end is
+            // the opener.
+            opened_by_peer: frame.flags & FROM_OPENER != 0,
+            id:             frame.id,
+        };
+        let connection = match self.connections.get_mut(&key) {
+            Some(c) => c,
+            None if key.opened_by_peer => {
+                self.incoming.push_back(key);
+                self.connections.entry(key).or_default()
+            },
+            // Connections opened by this end are never forgotten before the other end
+            // has closed them, so this one can only be a stray frame.
+            None => return,
+        };
+        if frame.flags & CLOSE != 0 {
+            if connection.locally_closed {
+                self.connections.remove(&key);
+            } else {
+                connection.peer_closed = true;
+            }
+        } else if !connection.locally_closed {
+            connection.buffer.extend(frame.data);
+        }
+    }
+}
+
+struct Frame {
+    id:    u32,
+    flags: u8,
+    data:  Vec<u8>,
+}
+/// Reads one frame, returning `None` if the transport has reached end of file
+/// at a frame boundary.
+fn read_frame(reader: &mut impl Read) -> io::Result<Option<Frame>> {
+    let mut header = [0; HEADER_LEN];
+    let mut filled = 0;
+    while filled < HEADER_LEN {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(bytes_read) => filled += bytes_read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => return Err(e),
+        }
+    }
+    let mut id = [0; 4];
+    id.copy_from_slice(&header[0..4]);
+    let mut len = [0; 4];
+    len.copy_from_slice(&header[5..9]);
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame is bigger than the maximum size",
+        ));
+    }
+    let mut data = vec![0; len];
+    reader.read_exact(&mut data)?;
+    Ok(Some(Frame {
+        id: u32::from_le_bytes(id),
+        flags: header[4],
+        data,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    fn muxer_pair() -> (PipeMuxer<TcpStream>, PipeMuxer<TcpStream>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (
+            PipeMuxer::new(client.try_clone().unwrap(), client),
+            PipeMuxer::new(server.try_clone().unwrap(), server),
+        )
+    }
+
+    #[test]
+    fn two_connections() {
+        let (client, server) = muxer_pair();
+        let mut first = client.connect().unwrap();
+        let mut second = client.connect().unwrap();
+        // Written in the opposite order to check that they don't get mixed up.
+        second.write_all(b"second").unwrap();
+        first.write_all(b"first").unwrap();
+        let mut accepted_first = server.accept().unwrap();
+        let mut accepted_second = server.accept().unwrap();
+        assert_eq!(accepted_first.id(), (0, true));
+        let mut buf = [0; 6];
+        accepted_second.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"second");
+        accepted_first.read_exact(&mut buf[..5]).unwrap();
+        assert_eq!(&buf[..5], b"first");
+
+        accepted_first.write_all(b"reply").unwrap();
+        first.read_exact(&mut buf[..5]).unwrap();
+        assert_eq!(&buf[..5], b"reply");
+
+        drop(second);
+        let mut rest = Vec::new();
+        accepted_second.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            accepted_second.write(b"late").unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+
+    #[test]
+    fn closed_connections_are_forgotten() {
+        let (client, server) = muxer_pair();
+        let mut opened = client.connect().unwrap();
+        opened.write_all(b"x").unwrap();
+        let mut accepted = server.accept().unwrap();
+        accepted.read_exact(&mut [0]).unwrap();
+
+        drop(opened);
+        let mut rest = Vec::new();
+        accepted.read_to_end(&mut rest).unwrap();
+        drop(accepted);
+        assert!(server.shared.lock_state().connections.is_empty());
+
+        // The client forgets the connection once it reads the confirmation, which
+        // comes right before the end of the transport.
+        drop(server);
+        assert_eq!(
+            client.accept().unwrap_err().kind(),
+            io::ErrorKind::ConnectionAborted
+        );
+        assert!(client.shared.lock_state().connections.is_empty());
+    }
+
+    #[test]
+    fn id_wraparound() {
+        let (client, server) = muxer_pair();
+        client.shared.lock_state().next_id = u32::MAX;
+        let mut last = client.connect().unwrap();
+        let mut first = client.connect().unwrap();
+        assert_eq!(last.id(), (u32::MAX, false));
+        assert_eq!(first.id(), (0, false));
+        // Wrapping around again with both still open skips their identifiers.
+        client.shared.lock_state().next_id = u32::MAX;
+        let mut next = client.connect().unwrap();
+        assert_eq!(next.id(), (1, false));
+
+        for stream in [&mut last, &mut first, &mut next].iter_mut() {
+            let id = stream.id().0;
+            stream.write_all(&id.to_le_bytes()).unwrap();
+        }
+        for _ in 0..3 {
+            let mut accepted = server.accept().unwrap();
+            let mut buf = [0; 4];
+            accepted.read_exact(&mut buf).unwrap();
+            assert_eq!(u32::from_le_bytes(buf), accepted.id().0);
+        }
+    }
+
+    #[test]
+    fn concurrent_readers() {
+        let (client, server) = muxer_pair();
+        let streams = vec![client.connect().unwrap(), client.connect().unwrap()];
+        // Both threads block in reads at the same time, only one of them reading
+        // from the transport, while data for both arrives interleaved.
+        let readers = streams
+            .into_iter()
+            .map(|mut stream| {
+                thread::spawn(move || {
+                    let mut received = Vec::new();
+                    stream.read_to_end(&mut received).unwrap();
+                    received
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut accepted = [server.accept().unwrap(), server.accept().unwrap()];
+        for _ in 0..100 {
+            for (i, stream) in accepted.iter_mut().enumerate() {
+                stream.write_all(&[i as u8; 1000]).unwrap();
+            }
+        }
+        drop(accepted);
+        for (i, reader) in readers.into_iter().enumerate() {
+            assert_eq!(reader.join().unwrap(), vec![i as u8; 100_000]);
+        }
+
+        drop(server);
+        // The server never read the confirmations of the closures, which makes TCP
+        // reset the connection rather than close it.
+        assert!(matches!(
+            client.accept().unwrap_err().kind(),
+            io::ErrorKind::ConnectionAborted | io::ErrorKind::ConnectionReset
+        ));
+    }
+}
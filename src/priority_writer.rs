@@ -0,0 +1,218 @@
+use crate::PartialMsgWriteError;
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+};
+
+/// The priority of a message queued in a [`PriorityWriter`].
+///
+/// [`PriorityWriter`]: struct.PriorityWriter.html " "
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// Sent before all normal-priority messages which are waiting to be sent,
+    /// in the order in which high-priority messages were queued.
+    High,
+    /// Sent in the order in which the messages were queued, after all
+    /// high-priority ones. This is the default.
+    Normal,
+}
+impl Default for Priority {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+/// Queues messages for a message-oriented writer, letting urgent ones jump
+/// ahead of the ones which are waiting to be sent.
+///
+/// Control protocols often have messages, such as a request to abort an
+/// operation, which should not have to wait behind a backlog of bulk data.
+/// Once a message has been written into a pipe, the system delivers it in
+/// order with everything else, so `PriorityWriter` keeps messages in its own
+/// queues until [`flush`] is called and sends high-priority messages first.
+/// **The reordering only applies to messages which have not been written into
+/// the underlying writer yet** — it cannot overtake anything that has already
+/// been flushed.
+///
+/// Every message is handed to the underlying writer with one `write` call, as
+/// message-mode pipes expect. Writing into the `PriorityWriter` through the
+/// [`Write`] trait queues every `write` call as a separate normal-priority
+/// message.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # #[cfg(windows)] {
+/// use interprocess::{
+///     os::windows::named_pipe::DuplexMsgPipeStream,
+///     Priority,
+///     PriorityWriter,
+/// };
+///
+/// let conn = DuplexMsgPipeStream::connect("Example")?;
+/// let mut writer = PriorityWriter::new(conn);
+/// writer.send_priority(b"chunk 1", Priority::Normal);
+/// writer.send_priority(b"chunk 2", Priority::Normal);
+/// writer.send_priority(b"abort", Priority::High);
+/// // Sends "abort" first, then both chunks.
+/// writer.flush()?;
+/// # }
+/// # Ok(()) }
+/// ```
+///
+/// [`flush`]: #method.flush " "
+/// [`Write`]: https://doc.rust-lang.org/std/io/trait.Write.html " "
+#[derive(Debug)]
+pub struct PriorityWriter<W: Write> {
+    writer: W,
+    high:   VecDeque<Vec<u8>>,
+    normal: VecDeque<Vec<u8>>,
+}
+impl<W: Write> PriorityWriter<W> {
+    /// Creates a priority writer which sends messages into the specified
+    /// writer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            high: VecDeque::new(),
+            normal: VecDeque::new(),
+        }
+    }
+    /// Queues a message with the specified priority, to be sent on the next
+    /// [`flush`].
+    ///
+    /// [`flush`]: #method.flush " "
+    pub fn send_priority(&mut self, buf: &[u8], priority: Priority) {
+        let queue = match priority {
+            Priority::High => &mut self.high,
+            Priority::Normal => &mut self.normal,
+        };
+        queue.push_back(buf.to_vec());
+    }
+    /// Sends all queued messages, high-priority ones first, and then flushes
+    /// the underlying writer.
+    ///
+    /// If the underlying writer fails, the message which was being sent stays
+    /// at the front of its queue along with all the ones after it, allowing to
+    /// retry the operation. If it writes only a part of a message, the rest of
+    /// that message is discarded and a [`PartialMsgWriteError`] is returned
+    /// with the `ErrorKind::Other` error type.
+    ///
+    /// [`PartialMsgWriteError`]: struct.PartialMsgWriteError.html " "
+    pub fn flush(&mut self) -> io::Result<()> {
+        for queue in [&mut self.high, &mut self.normal].iter_mut() {
+            while let Some(msg) = queue.front() {
+                let bytes_written = self.writer.write(msg)?;
+                let whole_message = bytes_written == msg.len();
+                queue.pop_front();
+                if !whole_message {
+                    return Err(io::Error::new(io::ErrorKind::Other, PartialMsgWriteError));
+                }
+            }
+        }
+        self.writer.flush()
+    }
+    /// Returns the amount of messages which are waiting to be sent.
+    pub fn queued(&self) -> usize {
+        self.high.len() + self.normal.len()
+    }
+    /// Discards all messages which are waiting to be sent.
+    pub fn discard(&mut self) {
+        self.high.clear();
+        self.normal.clear();
+    }
+    /// Borrows the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+    /// Mutably borrows the underlying writer. Writing into it directly sends a
+    /// message ahead of all queued ones, regardless of their priority.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+    /// Returns the underlying writer, discarding the messages which are waiting
+    /// to be sent, if any.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+/// Queues every `write` call as a normal-priority message. Flushing sends the
+/// queued messages, like the inherent [`flush`](#method.flush) method.
+impl<W: Write> Write for PriorityWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.send_priority(buf, Priority::Normal);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        PriorityWriter::flush(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Records every `write` call as a separate message, like a message-mode
+    /// pipe would, failing once a limited amount of messages has been written.
+    #[derive(Default)]
+    struct MsgSink {
+        messages:     Vec<Vec<u8>>,
+        max_messages: Option<usize>,
+    }
+    impl Write for MsgSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if Some(self.messages.len()) == self.max_messages {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            self.messages.push(buf.to_vec());
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn high_priority_first() {
+        let mut writer = PriorityWriter::new(MsgSink::default());
+        writer.send_priority(b"bulk 1", Priority::Normal);
+        writer.write_all(b"bulk 2").unwrap();
+        writer.send_priority(b"abort", Priority::High);
+        writer.send_priority(b"really abort", Priority::High);
+        assert_eq!(writer.queued(), 4);
+        assert!(writer.get_ref().messages.is_empty());
+        writer.flush().unwrap();
+        assert_eq!(writer.queued(), 0);
+        assert_eq!(
+            writer.into_inner().messages,
+            [
+                &b"abort"[..],
+                &b"really abort"[..],
+                &b"bulk 1"[..],
+                &b"bulk 2"[..]
+            ]
+        );
+    }
+
+    #[test]
+    fn retry_after_error() {
+        let sink = MsgSink {
+            max_messages: Some(1),
+            ..MsgSink::default()
+        };
+        let mut writer = PriorityWriter::new(sink);
+        writer.send_priority(b"first", Priority::Normal);
+        writer.send_priority(b"second", Priority::Normal);
+        let error = writer.flush().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(writer.queued(), 1);
+        // A high-priority message queued in the meantime still goes first.
+        writer.send_priority(b"urgent", Priority::High);
+        writer.get_mut().max_messages = None;
+        writer.flush().unwrap();
+        assert_eq!(
+            writer.into_inner().messages,
+            [&b"first"[..], &b"urgent"[..], &b"second"[..]]
+        );
+    }
+}
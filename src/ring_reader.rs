@@ -0,0 +1,261 @@
+use std::io::{self, prelude::*, IoSliceMut};
+
+/// A reader adapter which buffers data from a byte stream in a fixed-size ring
+/// buffer.
+///
+/// Regular buffering — such as with `BufReader` or a `Vec` which is drained
+/// from the front — has to move the unconsumed part of the data to the start of
+/// the buffer to make room for more, which adds up to a lot of copying for
+/// streaming parsers that consume data in small pieces while continuously
+/// reading more. A ring buffer never has to do that: new data is read into the
+/// free space following the buffered data, wrapping around to the start of the
+/// buffer, using [vectored reads] to fill both parts of the free space with a
+/// single call where possible.
+///
+/// The price is that the buffered data may be split in two: [`readable`]
+/// returns both parts, and [`make_contiguous`] can be used to join them when a
+/// parser needs to look at a whole message at once.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use interprocess::{local_socket::LocalSocketStream, RingReader};
+///
+/// let conn = LocalSocketStream::connect("/tmp/example.sock")?;
+/// let mut reader = RingReader::new(conn, 4096);
+/// // Count newline-separated records without ever copying data around.
+/// let mut records = 0;
+/// while reader.fill()? != 0 || !reader.is_empty() {
+///     let (first, second) = reader.readable();
+///     records += first.iter().chain(second).filter(|&&b| b == b'\n').count();
+///     let len = reader.len();
+///     reader.consume(len);
+/// }
+/// println!("{} records", records);
+/// # Ok(()) }
+/// ```
+///
+/// [vectored reads]: https://en.wikipedia.org/wiki/Vectored_I/O " "
+/// [`readable`]: #method.readable " "
+/// [`make_contiguous`]: #method.make_contiguous " "
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "ring_reader")))]
+#[derive(Debug)]
+pub struct RingReader<R: Read> {
+    reader: R,
+    buffer: Box<[u8]>,
+    start:  usize,
+    len:    usize,
+}
+impl<R: Read> RingReader<R> {
+    /// Creates a ring reader with a buffer of the specified size.
+    ///
+    /// # Panics
+    /// If `capacity` is zero.
+    pub fn new(reader: R, capacity: usize) -> Self {
+        assert_ne!(capacity, 0, "ring buffer capacity must not be zero");
+        Self {
+            reader,
+            buffer: vec![0; capacity].into_boxed_slice(),
+            start: 0,
+            len: 0,
+        }
+    }
+    /// Reads more data from the underlying reader into the free space of the
+    /// buffer, returning the amount of bytes read.
+    ///
+    /// Zero is returned if the underlying reader has reached end of file, or
+    /// without reading anything if the buffer is already full, which can be
+    /// told apart with [`is_full`].
+    ///
+    /// [`is_full`]: #method.is_full " "
+    pub fn fill(&mut self) -> io::Result<usize> {
+        if self.is_full() {
+            return Ok(0);
+        }
+        let capacity = self.capacity();
+        let end = (self.start + self.len) % capacity;
+        let bytes_read = if end < self.start {
+            // The free space is between the end and the start of the data.
+            self.reader.read(&mut self.buffer[end..self.start])?
+        } else if self.start == 0 {
+            // The free space is at the end of the buffer only.
+            self.reader.read(&mut self.buffer[end..])?
+        } else {
+            // The free space wraps around the end of the buffer.
+            let (wrapped, tail) = self.buffer.split_at_mut(end);
+            let mut bufs = [
+                IoSliceMut::new(tail),
+                IoSliceMut::new(&mut wrapped[..self.start]),
+            ];
+            self.reader.read_vectored(&mut bufs)?
+        };
+        self.len += bytes_read;
+        Ok(bytes_read)
+    }
+    /// Returns the buffered data, in two parts: the first one ends at the end
+    /// of the buffer and the second one, which is empty unless the data wraps
+    /// around, continues from the start of the buffer.
+    pub fn readable(&self) -> (&[u8], &[u8]) {
+        let capacity = self.capacity();
+        if self.start + self.len <= capacity {
+            (&self.buffer[self.start..self.start + self.len], &[])
+        } else {
+            let wrapped = self.start + self.len - capacity;
+            (&self.buffer[self.start..], &self.buffer[..wrapped])
+        }
+    }
+    /// Moves the buffered data to the start of the buffer, if necessary, and
+    /// returns it as one contiguous slice.
+    ///
+    /// This does copy the data, but only if it wraps around the end of the
+    /// buffer, which [`readable`] can be used to check for.
+    ///
+    /// [`readable`]: #method.readable " "
+    pub fn make_contiguous(&mut self) -> &[u8] {
+        if self.start + self.len > self.capacity() {
+            self.buffer.rotate_left(self.start);
+            self.start = 0;
+        }
+        &self.buffer[self.start..self.start + self.len]
+    }
+    /// Marks the first `amount` bytes of the buffered data as consumed, freeing
+    /// up their space for [`fill`].
+    ///
+    /// # Panics
+    /// If `amount` is bigger than the amount of buffered data.
+    ///
+    /// [`fill`]: #method.fill " "
+    pub fn consume(&mut self, amount: usize) {
+        assert!(
+            amount <= self.len,
+            "cannot consume more data than there is in the buffer"
+        );
+        self.len -= amount;
+        self.start = if self.len == 0 {
+            // Starting over from the beginning of the buffer makes it more likely for
+            // the next chunk of data to be contiguous.
+            0
+        } else {
+            (self.start + amount) % self.capacity()
+        };
+    }
+    /// Returns the amount of buffered data, in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    /// Returns `true` if there is no buffered data.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// Returns `true` if there is no free space left in the buffer.
+    pub fn is_full(&self) -> bool {
+        self.len == self.capacity()
+    }
+    /// Returns the size of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+    /// Borrows the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+    /// Mutably borrows the underlying reader. Reading from it directly skips
+    /// the buffer.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+    /// Returns the underlying reader, discarding the buffered data.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+impl<R: Read> Read for RingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.is_empty() {
+            self.fill()?;
+        }
+        let (first, second) = self.readable();
+        let from_first = first.len().min(buf.len());
+        buf[..from_first].copy_from_slice(&first[..from_first]);
+        let from_second = second.len().min(buf.len() - from_first);
+        buf[from_first..from_first + from_second].copy_from_slice(&second[..from_second]);
+        let total = from_first + from_second;
+        self.consume(total);
+        Ok(total)
+    }
+}
+impl<R: Read> BufRead for RingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.is_empty() {
+            self.fill()?;
+        }
+        Ok(self.readable().0)
+    }
+    fn consume(&mut self, amount: usize) {
+        RingReader::consume(self, amount)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Hands out data in pieces of a fixed size, to make wrap-around
+    /// predictable.
+    struct Pieces {
+        data:       Vec<u8>,
+        position:   usize,
+        piece_size: usize,
+    }
+    impl Read for Pieces {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.piece_size).min(self.data.len() - self.position);
+            buf[..len].copy_from_slice(&self.data[self.position..self.position + len]);
+            self.position += len;
+            Ok(len)
+        }
+        fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+            let piece_size = self.piece_size;
+            let mut total = 0;
+            for buf in bufs {
+                self.piece_size = piece_size - total;
+                total += self.read(buf)?;
+            }
+            self.piece_size = piece_size;
+            Ok(total)
+        }
+    }
+    fn pieces(len: usize, piece_size: usize) -> Pieces {
+        Pieces {
+            data: (0..len).map(|i| i as u8).collect(),
+            position: 0,
+            piece_size,
+        }
+    }
+
+    #[test]
+    fn wrap_around() {
+        let mut reader = RingReader::new(pieces(64, 6), 8);
+        assert_eq!(reader.fill().unwrap(), 6);
+        reader.consume(4);
+        // Two bytes at the end of the buffer, then six more which wrap around.
+        assert_eq!(reader.fill().unwrap(), 6);
+        assert!(reader.is_full());
+        assert_eq!(reader.fill().unwrap(), 0);
+        assert_eq!(reader.readable(), (&[4, 5, 6, 7][..], &[8, 9, 10, 11][..]));
+        assert_eq!(reader.make_contiguous(), &[4, 5, 6, 7, 8, 9, 10, 11]);
+        assert_eq!(reader.readable().1, &[]);
+        reader.consume(8);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn read_everything() {
+        let source = pieces(1000, 7);
+        let expected = source.data.clone();
+        let mut reader = RingReader::new(source, 16);
+        let mut received = Vec::new();
+        reader.read_to_end(&mut received).unwrap();
+        assert_eq!(received, expected);
+    }
+}
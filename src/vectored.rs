@@ -0,0 +1,168 @@
+use std::{
+    io::{IoSlice, IoSliceMut},
+    mem,
+    slice,
+};
+
+/// Advances a list of buffers for [gather output] by the specified amount of
+/// bytes, as returned by a `write_vectored` call, so that the list can be
+/// passed to the next call to write the rest of the data.
+///
+/// Buffers which have been written completely are removed from the start of
+/// the list, and the first of the remaining ones is shortened by the amount of
+/// bytes written from it. Once all data has been written, the list is empty.
+/// This is the same operation as `IoSlice::advance_slices` in the standard
+/// library, which is not available on the supported compilers.
+///
+/// # Panics
+/// If `n` is bigger than the total length of the buffers.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use interprocess::{advance_slices, local_socket::LocalSocketStream};
+/// use std::io::{prelude::*, IoSlice};
+///
+/// let mut conn = LocalSocketStream::connect("/tmp/example.sock")?;
+/// let (header, body) = (b"HEAD", b"body of the message");
+/// let mut bufs = [IoSlice::new(header), IoSlice::new(body)];
+/// let mut bufs = &mut bufs[..];
+/// while !bufs.is_empty() {
+///     let bytes_written = conn.write_vectored(bufs)?;
+///     advance_slices(&mut bufs, bytes_written);
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// [gather output]: https://en.wikipedia.org/wiki/Vectored_I/O " "
+pub fn advance_slices<'a>(bufs: &mut &mut [IoSlice<'a>], n: usize) {
+    let (skip, remainder) = count_consumed(bufs.iter().map(|buf| buf.len()), n);
+    *bufs = &mut mem::take(bufs)[skip..];
+    if let Some(first) = bufs.first_mut() {
+        let rest = unsafe {
+            // SAFETY: the IoSlice borrows the data for 'a, and it's replaced right away,
+            // so the data is never borrowed twice
+            slice::from_raw_parts(first.as_ptr().add(remainder), first.len() - remainder)
+        };
+        *first = IoSlice::new(rest);
+    }
+}
+
+/// Advances a list of buffers for [scatter input] by the specified amount of
+/// bytes, as returned by a `read_vectored` call, so that the list can be passed
+/// to the next call to fill the rest of the buffers.
+///
+/// This is the counterpart of [`advance_slices`] for reading, with the same
+/// behavior.
+///
+/// # Panics
+/// If `n` is bigger than the total length of the buffers.
+///
+/// [scatter input]: https://en.wikipedia.org/wiki/Vectored_I/O " "
+/// [`advance_slices`]: fn.advance_slices.html " "
+pub fn advance_slices_mut<'a>(bufs: &mut &mut [IoSliceMut<'a>], n: usize) {
+    let (skip, remainder) = count_consumed(bufs.iter().map(|buf| buf.len()), n);
+    *bufs = &mut mem::take(bufs)[skip..];
+    if let Some(first) = bufs.first_mut() {
+        let rest = unsafe {
+            // SAFETY: the IoSliceMut borrows the data mutably for 'a, and it's replaced
+            // right away, so the data is never borrowed twice
+            slice::from_raw_parts_mut(first.as_mut_ptr().add(remainder), first.len() - remainder)
+        };
+        *first = IoSliceMut::new(rest);
+    }
+}
+
+/// Returns the amount of buffers which `n` bytes fill up completely, and the
+/// amount of bytes left over for the buffer after them.
+fn count_consumed(lens: impl Iterator<Item = usize>, n: usize) -> (usize, usize) {
+    let mut skip = 0;
+    let mut left = n;
+    for len in lens {
+        if left < len {
+            return (skip, left);
+        }
+        left -= len;
+        skip += 1;
+    }
+    assert_eq!(left, 0, "advancing beyond the end of the buffers");
+    (skip, 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn contents(bufs: &[IoSlice<'_>]) -> Vec<Vec<u8>> {
+        bufs.iter().map(|buf| buf.to_vec()).collect()
+    }
+
+    #[test]
+    fn advance_within_and_across() {
+        let (a, b, c) = (b"abc", b"de", b"fghi");
+        let mut bufs = [IoSlice::new(a), IoSlice::new(b), IoSlice::new(c)];
+        let mut bufs = &mut bufs[..];
+        advance_slices(&mut bufs, 0);
+        assert_eq!(contents(bufs), [&b"abc"[..], b"de", b"fghi"]);
+        advance_slices(&mut bufs, 1);
+        assert_eq!(contents(bufs), [&b"bc"[..], b"de", b"fghi"]);
+        // Spans the rest of the first buffer and the whole second one.
+        advance_slices(&mut bufs, 5);
+        assert_eq!(contents(bufs), [&b"ghi"[..]]);
+        advance_slices(&mut bufs, 3);
+        assert!(bufs.is_empty());
+        advance_slices(&mut bufs, 0);
+        assert!(bufs.is_empty());
+    }
+
+    #[test]
+    fn advance_to_boundaries() {
+        let (a, b, c) = (b"ab", b"cd", b"ef");
+        let mut bufs = [IoSlice::new(a), IoSlice::new(b), IoSlice::new(c)];
+        let mut bufs = &mut bufs[..];
+        // Ending exactly at a boundary drops the buffer instead of leaving an
+        // empty one at the front.
+        advance_slices(&mut bufs, 2);
+        assert_eq!(contents(bufs), [&b"cd"[..], b"ef"]);
+        advance_slices(&mut bufs, 4);
+        assert!(bufs.is_empty());
+    }
+
+    #[test]
+    fn skip_empty_buffers() {
+        let (a, b) = (b"ab", b"cd");
+        let mut bufs = [
+            IoSlice::new(&[]),
+            IoSlice::new(a),
+            IoSlice::new(&[]),
+            IoSlice::new(b),
+        ];
+        let mut bufs = &mut bufs[..];
+        advance_slices(&mut bufs, 0);
+        assert_eq!(contents(bufs), [&b"ab"[..], b"", b"cd"]);
+        advance_slices(&mut bufs, 3);
+        assert_eq!(contents(bufs), [&b"d"[..]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "advancing beyond the end of the buffers")]
+    fn advance_too_far() {
+        let mut bufs = [IoSlice::new(b"ab")];
+        advance_slices(&mut &mut bufs[..], 3);
+    }
+
+    #[test]
+    fn advance_mut() {
+        let (mut a, mut b) = ([0_u8; 3], [0_u8; 3]);
+        {
+            let mut bufs = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+            let mut bufs = &mut bufs[..];
+            advance_slices_mut(&mut bufs, 4);
+            assert_eq!(bufs.len(), 1);
+            bufs[0].copy_from_slice(&[1, 2]);
+            advance_slices_mut(&mut bufs, 2);
+            assert!(bufs.is_empty());
+        }
+        assert_eq!((a, b), ([0; 3], [0, 1, 2]));
+    }
+}
@@ -0,0 +1,74 @@
+//! Checks that hot paths don't allocate. Kept in a test binary of its own,
+//! since counting allocations takes replacing the global allocator, which the
+//! rest of the tests have no business running with. See also the
+//! `accept_allocations` benchmark.
+#![cfg(unix)]
+
+mod common;
+
+use common::allocations_made_by;
+use interprocess::{
+    os::unix::udsocket::{UdSocket, UdStream, UdStreamListener},
+    OverflowPolicy,
+};
+use std::{env, fs::remove_file, io::IoSlice, num::NonZeroUsize, path::PathBuf, process};
+
+/// A socket path which no other test, or other run of this one, uses.
+fn socket_path(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!(
+        "interprocess_alloc_test_{}_{}.sock",
+        name,
+        process::id()
+    ));
+    let _ = remove_file(&path);
+    path
+}
+
+#[test]
+fn small_datagrams_dont_allocate() {
+    let path = socket_path("datagrams");
+    let receiver = UdSocket::bind(&*path).unwrap();
+    let sender = UdSocket::connect(&*path).unwrap();
+    let mut buffer = [0_u8; 16];
+    // Warm up lazily initialized state, if there's any.
+    sender.send(b"warmup").unwrap();
+    receiver.recv(&mut buffer).unwrap();
+    // Received after every send, since the system only lets a handful of
+    // datagrams queue up before blocking the sender.
+    let mut allocations = 0;
+    for _ in 0..50 {
+        allocations += allocations_made_by(|| {
+            sender.send(b"event").unwrap();
+        });
+        assert_eq!(receiver.recv(&mut buffer).unwrap(), (5, false));
+        allocations += allocations_made_by(|| {
+            sender.send_vectored(&[IoSlice::new(b"ev"), IoSlice::new(b"ent")]).unwrap();
+        });
+        assert_eq!(receiver.recv(&mut buffer).unwrap(), (5, false));
+        assert_eq!(&buffer[..5], b"event");
+    }
+    assert_eq!(allocations, 0);
+    let _ = remove_file(path);
+}
+
+#[test]
+fn accept_doesnt_allocate() {
+    let path = socket_path("accept");
+    let unlimited = UdStreamListener::bind(&*path).unwrap();
+    let limited_path = socket_path("accept_limited");
+    let four = NonZeroUsize::new(4).unwrap();
+    let limited =
+        UdStreamListener::bind_with_limit(&*limited_path, four, OverflowPolicy::Queue).unwrap();
+    // Lots of short-lived connections, as with a busy server. Connecting
+    // happens outside of the measurement, since resolving the path allocates.
+    let mut allocations = 0;
+    for _ in 0..100 {
+        let _client = UdStream::connect(&*path).unwrap();
+        allocations += allocations_made_by(|| drop(unlimited.accept().unwrap()));
+        let _client = UdStream::connect(&*limited_path).unwrap();
+        allocations += allocations_made_by(|| drop(limited.accept().unwrap()));
+    }
+    assert_eq!(allocations, 0);
+    let _ = remove_file(path);
+    let _ = remove_file(limited_path);
+}
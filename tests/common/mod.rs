@@ -0,0 +1,36 @@
+//! The allocation counter shared by the allocation tests and benchmarks.
+//! Including this module replaces the global allocator of the binary.
+// The const initializer syntax for thread-locals is too new for the supported
+// compilers.
+#![allow(unknown_lints, clippy::missing_const_for_thread_local)]
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+/// Counts the allocations made by each thread, so that threads running in
+/// parallel don't disturb each other's counts.
+struct CountingAllocator;
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = Cell::new(0);
+}
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // Fails if the thread is being torn down, which is fine to miss.
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns how many allocations the current thread made while running `f`.
+pub fn allocations_made_by(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}